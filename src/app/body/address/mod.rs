@@ -1,15 +1,22 @@
-use std::{str::FromStr, sync::Arc};
+use std::{fmt, str::FromStr, sync::Arc};
 
+use anyhow::{anyhow, Result};
 use druid::{
     widget::{prelude::*, Controller, CrossAxisAlignment, Flex},
     ArcStr, Data, Env, EventCtx, Insets, Lens, Widget, WidgetExt as _,
 };
 use http::Uri;
 use once_cell::sync::Lazy;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
-    app::{body::RequestState, command, sidebar::service::ServiceOptions, theme},
-    lens,
+    app::{
+        body::{Environment, RequestState},
+        command,
+        sidebar::service::ServiceOptions,
+        theme,
+    },
+    lens, template,
     theme::BODY_PADDING,
     widget::{
         error_label, input, state_icon, FormField, StateIcon, ValidationFn, ValidationState,
@@ -17,7 +24,15 @@ use crate::{
     },
 };
 
-type AddressValidationState = ValidationState<String, Uri>;
+type AddressValidationState = ValidationState<String, Address>;
+
+/// A server address, which may contain `{{variable}}` placeholders that are expanded when
+/// connecting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    Uri(Uri),
+    Template(Arc<str>),
+}
 
 #[derive(Debug, Clone, Data, Lens)]
 pub(in crate::app) struct AddressState {
@@ -81,12 +96,21 @@ impl AddressState {
         self.uri.text()
     }
 
-    pub fn uri(&self) -> Option<&Uri> {
+    pub fn address(&self) -> Option<&Address> {
         self.uri.result().ok()
     }
 
-    pub fn set_uri(&mut self, uri: &Uri) {
-        self.uri.with_text_mut(|t| *t = uri.to_string())
+    pub fn set_address(&mut self, address: &Address) {
+        self.uri.with_text_mut(|t| *t = address.to_string())
+    }
+
+    /// Gets the address to connect to, with any placeholders expanded.
+    pub fn resolve(&self, environment: &Environment) -> Option<Result<Uri>> {
+        self.address().map(|address| address.resolve(environment))
+    }
+
+    pub fn resolved_uri(&self, environment: &Environment) -> Option<Uri> {
+        self.resolve(environment)?.ok()
     }
 
     pub fn request_state(&self) -> &RequestState {
@@ -101,7 +125,8 @@ impl AddressState {
         if let Some(err) = self.uri.display_error() {
             Some(err)
         } else if let RequestState::ConnectFailed(err)
-        | RequestState::AuthorizationHookFailed(err) = self.request_state()
+        | RequestState::AuthorizationHookFailed(err)
+        | RequestState::SendFailed(err) = self.request_state()
         {
             Some(err.clone())
         } else {
@@ -116,9 +141,9 @@ impl AddressState {
             | RequestState::AuthorizationHookInProgress
             | RequestState::SendInProgress => StateIcon::InProgress,
             RequestState::Connected => StateIcon::Succeeded,
-            RequestState::ConnectFailed(_) | RequestState::AuthorizationHookFailed(_) => {
-                StateIcon::Failed
-            }
+            RequestState::ConnectFailed(_)
+            | RequestState::AuthorizationHookFailed(_)
+            | RequestState::SendFailed(_) => StateIcon::Failed,
         }
     }
 }
@@ -177,10 +202,67 @@ where
     }
 }
 
-static VALIDATE_URI: Lazy<ValidationFn<String, Uri>> = Lazy::new(|| Arc::new(validate_uri));
+impl Address {
+    pub fn resolve(&self, environment: &Environment) -> Result<Uri> {
+        match self {
+            Address::Uri(uri) => Ok(uri.clone()),
+            Address::Template(template) => {
+                let address = environment.expand(template)?;
+                parse_uri(&address).map_err(|err| anyhow!("invalid address: {}", err))
+            }
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = ArcStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if template::has_placeholders(s) {
+            template::validate(s).map_err(|err| err.to_string())?;
+            Ok(Address::Template(s.into()))
+        } else {
+            parse_uri(s).map(Address::Uri)
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Uri(uri) => fmt::Display::fmt(uri, f),
+            Address::Template(template) => fmt::Display::fmt(template, f),
+        }
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+static VALIDATE_URI: Lazy<ValidationFn<String, Address>> = Lazy::new(|| Arc::new(validate_uri));
 
 #[allow(clippy::ptr_arg)]
-fn validate_uri(s: &String) -> Result<Uri, ArcStr> {
+fn validate_uri(s: &String) -> Result<Address, ArcStr> {
+    s.parse()
+}
+
+fn parse_uri(s: &str) -> Result<Uri, ArcStr> {
     let uri = Uri::from_str(s).map_err(|err| err.to_string())?;
     if uri.scheme().is_none() {
         return Err("URI must have scheme".into());
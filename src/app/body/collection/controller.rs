@@ -0,0 +1,134 @@
+use druid::{
+    widget::{prelude::*, Controller},
+    Command, Handled, Target,
+};
+
+use crate::{
+    app::{
+        body::collection::{runner, CollectionState, CollectionTabState, Run, START_RUN},
+        command,
+    },
+    widget::update_queue::{self, UpdateQueue},
+};
+
+pub struct CollectionTabController {
+    updates: UpdateQueue<CollectionTabController, CollectionTabState>,
+}
+
+impl<W> Controller<CollectionTabState, W> for CollectionTabController
+where
+    W: Widget<CollectionTabState>,
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut CollectionTabState,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(command) if self.command(ctx, command, data) == Handled::Yes => (),
+            _ => {
+                child.event(ctx, event, data, env);
+
+                if let Some((id, collection)) = data.take_run_request() {
+                    ctx.submit_command(command::RUN_COLLECTION.with((
+                        ctx.widget_id(),
+                        id,
+                        collection,
+                    )));
+                }
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        child: &mut W,
+        ctx: &mut UpdateCtx,
+        old_data: &CollectionTabState,
+        data: &CollectionTabState,
+        env: &Env,
+    ) {
+        let changed = old_data.collections.len() != data.collections.len()
+            || old_data
+                .collections
+                .iter()
+                .zip(data.collections.iter())
+                .any(|(old, new)| !old.collection.same(&new.collection));
+        if changed {
+            ctx.submit_command(command::SET_COLLECTIONS.with(data.collections()));
+        }
+
+        child.update(ctx, old_data, data, env)
+    }
+}
+
+impl CollectionTabController {
+    pub fn new() -> Self {
+        CollectionTabController {
+            updates: UpdateQueue::new(),
+        }
+    }
+
+    fn command(
+        &mut self,
+        ctx: &mut EventCtx,
+        command: &Command,
+        data: &mut CollectionTabState,
+    ) -> Handled {
+        if let Some(run) = command.get(START_RUN) {
+            self.start_run(ctx, data, run.clone());
+            Handled::Yes
+        } else if command.is(update_queue::UPDATE) {
+            while let Some(update) = self.updates.pop() {
+                (update)(self, ctx, data)
+            }
+            Handled::Yes
+        } else {
+            Handled::No
+        }
+    }
+
+    fn start_run(&mut self, ctx: &mut EventCtx, data: &mut CollectionTabState, run: Run) {
+        let id = run.id;
+        let (environment, timeout, snapshots) = match (run.environment, run.timeout, run.snapshots)
+        {
+            (Ok(environment), Ok(timeout), Ok(snapshots)) => (environment, timeout, snapshots),
+            (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+                data.with_run_mut(id, |state| {
+                    state.error = Some(err);
+                    state.finish();
+                });
+                return;
+            }
+        };
+
+        let writer = self.updates.writer(ctx);
+        tokio::spawn(async move {
+            runner::run(
+                run.steps,
+                environment,
+                &snapshots,
+                timeout,
+                run.continue_on_failure,
+                |step_index, result| {
+                    if !result.captured.is_empty() {
+                        writer.submit_command(
+                            command::CAPTURE_VARIABLES,
+                            result.captured.clone(),
+                            Target::Auto,
+                        );
+                    }
+                    writer.write(move |_, _, data| {
+                        data.with_run_mut(id, |state| state.set_result(step_index, result))
+                    });
+                },
+            )
+            .await;
+
+            writer.write(move |_, _, data| data.with_run_mut(id, CollectionState::finish));
+        });
+    }
+}
@@ -0,0 +1,415 @@
+mod controller;
+pub(in crate::app) mod runner;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use druid::{
+    widget::{
+        prelude::*, Button, Checkbox, CrossAxisAlignment, Flex, Label, LineBreaking, List, Scroll,
+    },
+    ArcStr, Data, Lens, Selector, WidgetExt,
+};
+use serde::{Deserialize, Serialize};
+
+use self::{controller::CollectionTabController, runner::Step};
+use crate::{
+    app::{
+        body::{format_duration, Environment, Environments},
+        command,
+        config::Config,
+        sidebar::service::{parse_timeout_secs, ServiceState},
+    },
+    error::fmt_err,
+    theme::{self, BODY_PADDING, BODY_SPACER, GRID_NARROW_SPACER},
+    widget::{error_label, input, state_icon, EditableList, StateIcon},
+};
+
+/// Start running a collection, once its steps have been resolved.
+pub(in crate::app) const START_RUN: Selector<Run> = Selector::new("app.body.collection.start-run");
+
+#[derive(Default, Debug, Clone, Data, Lens)]
+pub struct CollectionTabState {
+    #[lens(name = "collections_lens")]
+    collections: Arc<Vec<CollectionState>>,
+}
+
+/// The saved collections.
+#[derive(Default, Debug, Clone, Data, Serialize, Deserialize)]
+pub struct Collections {
+    collections: Arc<Vec<Collection>>,
+}
+
+/// An ordered list of saved requests to run one after another.
+#[derive(Default, Debug, Clone, Data, Lens, Serialize, Deserialize)]
+pub struct Collection {
+    name: Arc<String>,
+    /// The environment to run with, or the active environment if empty.
+    #[serde(default)]
+    environment: Arc<String>,
+    #[serde(default)]
+    continue_on_failure: bool,
+    /// The number of seconds to wait for each step to finish, or no limit if empty.
+    #[serde(default)]
+    timeout: Arc<String>,
+    steps: Arc<Vec<CollectionStep>>,
+}
+
+#[derive(Default, Debug, Clone, Data, Lens, Serialize, Deserialize)]
+struct CollectionStep {
+    /// The method, in the form `package.Service/Method`.
+    method: Arc<String>,
+    /// The name of a saved request for the method.
+    request: Arc<String>,
+}
+
+#[derive(Default, Debug, Clone, Data, Lens)]
+struct CollectionState {
+    collection: Collection,
+    run_requested: bool,
+    running: bool,
+    /// The most recent run of this collection, which its results belong to.
+    run: Option<RunId>,
+    error: Option<ArcStr>,
+    results: Arc<Vec<StepResultState>>,
+}
+
+/// Identifies a run of a collection, so results are written to the right collection even if the
+/// list of collections changes while it runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Data)]
+pub(in crate::app) struct RunId(u32);
+
+#[derive(Debug, Clone, Data, Lens)]
+struct StepResultState {
+    label: ArcStr,
+    state: StateIcon,
+    duration: ArcStr,
+    error: Option<ArcStr>,
+    response: ArcStr,
+}
+
+/// A collection with its steps resolved against the loaded services.
+#[derive(Debug, Clone)]
+pub(in crate::app) struct Run {
+    id: RunId,
+    steps: Vec<Result<Step, ArcStr>>,
+    environment: Result<Environment, ArcStr>,
+    timeout: Result<Option<Duration>, ArcStr>,
+    snapshots: Result<PathBuf, ArcStr>,
+    continue_on_failure: bool,
+}
+
+pub fn build_body() -> impl Widget<CollectionTabState> {
+    Scroll::new(
+        Flex::column()
+            .with_child(Label::new("Collections").with_font(theme::font::HEADER_TWO))
+            .with_spacer(theme::BODY_SPACER)
+            .with_child(
+                EditableList::new(
+                    "Add collection",
+                    |_, data: &mut Arc<Vec<CollectionState>>, _| {
+                        Arc::make_mut(data).push(CollectionState::default())
+                    },
+                    build_collection,
+                )
+                .lens(CollectionTabState::collections_lens),
+            )
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .padding(BODY_PADDING),
+    )
+    .vertical()
+    .expand_height()
+    .controller(CollectionTabController::new())
+}
+
+fn build_collection() -> impl Widget<CollectionState> {
+    let run_button = theme::button_scope(
+        Button::dynamic(|data: &CollectionState, _| {
+            if data.running {
+                "Running...".to_owned()
+            } else {
+                "Run".to_owned()
+            }
+        })
+        .on_click(|_, data: &mut CollectionState, _| data.run_requested = true),
+    )
+    .disabled_if(|data: &CollectionState, _| data.running || data.collection.steps.is_empty());
+
+    let fields = Flex::row()
+        .with_flex_child(input("name").lens(Collection::name), 0.5)
+        .with_spacer(BODY_SPACER)
+        .with_flex_child(input("environment").lens(Collection::environment), 0.5)
+        .with_spacer(BODY_SPACER)
+        .with_child(
+            input("timeout (seconds)")
+                .lens(Collection::timeout)
+                .fix_width(140.0),
+        )
+        .with_spacer(BODY_SPACER)
+        .with_child(
+            theme::check_box_scope(Checkbox::new("Continue on failure"))
+                .lens(Collection::continue_on_failure),
+        );
+
+    let header = Flex::row()
+        .with_flex_child(fields.lens(CollectionState::collection), 1.0)
+        .with_spacer(BODY_SPACER)
+        .with_child(run_button.fix_width(100.0));
+
+    let steps = EditableList::new(
+        "Add step",
+        |_, data: &mut Arc<Vec<CollectionStep>>, _| {
+            Arc::make_mut(data).push(CollectionStep::default())
+        },
+        build_step,
+    )
+    .lens(Collection::steps)
+    .lens(CollectionState::collection);
+
+    let results = List::new(build_result).lens(CollectionState::results);
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(header)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(steps.padding((BODY_SPACER, 0.0, 0.0, 0.0)))
+        .with_child(
+            error_label((BODY_SPACER, GRID_NARROW_SPACER, 0.0, 0.0)).lens(CollectionState::error),
+        )
+        .with_child(results.padding((BODY_SPACER, GRID_NARROW_SPACER, 0.0, 0.0)))
+        .padding((0.0, 0.0, 0.0, BODY_SPACER))
+}
+
+fn build_step() -> impl Widget<CollectionStep> {
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_flex_child(
+            input("package.Service/Method").lens(CollectionStep::method),
+            0.5,
+        )
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_flex_child(input("saved request").lens(CollectionStep::request), 0.5)
+}
+
+fn build_result() -> impl Widget<StepResultState> {
+    let header = Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Center)
+        .with_child(state_icon((0.0, 0.0, GRID_NARROW_SPACER, 0.0)).lens(StepResultState::state))
+        .with_flex_child(
+            Label::raw()
+                .with_font(theme::font::HEADER_TWO)
+                .with_line_break_mode(LineBreaking::Clip)
+                .lens(StepResultState::label)
+                .expand_width(),
+            1.0,
+        )
+        .with_child(
+            Label::raw()
+                .with_font(theme::font::NORMAL)
+                .lens(StepResultState::duration),
+        );
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(header)
+        .with_child(error_label((0.0, GRID_NARROW_SPACER, 0.0, 0.0)).lens(StepResultState::error))
+        .with_child(
+            Label::raw()
+                .with_font(theme::font::CODE)
+                .with_line_break_mode(LineBreaking::WordWrap)
+                .lens(StepResultState::response),
+        )
+        .padding((0.0, 0.0, 0.0, GRID_NARROW_SPACER))
+}
+
+impl CollectionTabState {
+    pub fn new(collections: &Collections) -> Self {
+        CollectionTabState {
+            collections: Arc::new(
+                collections
+                    .collections
+                    .iter()
+                    .map(|collection| CollectionState {
+                        collection: collection.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn collections(&self) -> Collections {
+        Collections {
+            collections: Arc::new(
+                self.collections
+                    .iter()
+                    .map(|state| state.collection.clone())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Takes the collection which the user asked to run, if any, and marks it as running.
+    fn take_run_request(&mut self) -> Option<(RunId, Collection)> {
+        let index = self.collections.iter().position(|c| c.run_requested)?;
+        let state = &mut Arc::make_mut(&mut self.collections)[index];
+        let id = RunId::next();
+        state.run_requested = false;
+        state.running = true;
+        state.run = Some(id);
+        state.error = None;
+        state.results = Arc::new(
+            state
+                .collection
+                .steps
+                .iter()
+                .enumerate()
+                .map(|(step_index, step)| StepResultState::new(step_index, step))
+                .collect(),
+        );
+        Some((id, state.collection.clone()))
+    }
+
+    /// Calls `f` with the collection the run belongs to, unless it has since been removed or run
+    /// again.
+    fn with_run_mut(&mut self, id: RunId, f: impl FnOnce(&mut CollectionState)) {
+        if let Some(index) = self.collections.iter().position(|c| c.run == Some(id)) {
+            f(&mut Arc::make_mut(&mut self.collections)[index])
+        }
+    }
+}
+
+impl Collections {
+    pub fn iter(&self) -> impl Iterator<Item = &Collection> {
+        self.collections.iter()
+    }
+}
+
+impl Collection {
+    pub fn name(&self) -> &str {
+        self.name.trim()
+    }
+
+    /// The method and saved request name of each step.
+    pub fn steps(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.steps
+            .iter()
+            .map(|step| (step.method.trim(), step.request.trim()))
+    }
+
+    /// Resolves the steps of this collection against the loaded services, and finds the
+    /// environment to run with. Snapshots are loaded from next to the workspace file, if given.
+    pub fn resolve(
+        &self,
+        id: RunId,
+        services: &im::Vector<ServiceState>,
+        environments: &Environments,
+        workspace: Option<&Path>,
+    ) -> Run {
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| {
+                Step::resolve(services, &step.method, &step.request).map_err(|err| fmt_err(&err))
+            })
+            .collect();
+        let environment = environments
+            .named(&self.environment)
+            .ok_or_else(|| format!("environment '{}' not found", self.environment.trim()).into());
+        let timeout = parse_timeout_secs(&self.timeout).map(|secs| secs.map(Duration::from_secs));
+
+        Run {
+            id,
+            steps,
+            environment,
+            timeout,
+            snapshots: Config::snapshot_directory(workspace).map_err(|err| fmt_err(&err)),
+            continue_on_failure: self.continue_on_failure,
+        }
+    }
+}
+
+impl Run {
+    /// Runs with the given environment, instead of the one named by the collection.
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = Ok(environment);
+    }
+
+    /// Runs each step in order, calling `on_result` after each one.
+    pub async fn run(self, on_result: impl FnMut(usize, runner::StepResult)) -> Result<(), ArcStr> {
+        let environment = self.environment?;
+        let timeout = self.timeout?;
+        let snapshots = self.snapshots?;
+        runner::run(
+            self.steps,
+            environment,
+            &snapshots,
+            timeout,
+            self.continue_on_failure,
+            on_result,
+        )
+        .await;
+        Ok(())
+    }
+}
+
+impl RunId {
+    pub fn next() -> Self {
+        use std::sync::atomic::*;
+
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+        RunId(COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl CollectionState {
+    fn set_result(&mut self, step_index: usize, result: runner::StepResult) {
+        let results = Arc::make_mut(&mut self.results);
+        if let Some(state) = results.get_mut(step_index) {
+            state.state = if result.is_success() {
+                StateIcon::Succeeded
+            } else {
+                StateIcon::Failed
+            };
+            state.duration = result
+                .duration
+                .map(format_duration)
+                .unwrap_or_default()
+                .into();
+            state.error = result.error.as_ref().map(fmt_err);
+            state.response = result.responses.join("\n").into();
+        }
+        if let Some(next) = results.get_mut(step_index + 1) {
+            next.state = StateIcon::InProgress;
+        }
+    }
+
+    fn finish(&mut self) {
+        self.running = false;
+        for result in Arc::make_mut(&mut self.results) {
+            if result.state == StateIcon::InProgress {
+                result.state = StateIcon::NotStarted;
+            }
+        }
+    }
+}
+
+impl StepResultState {
+    fn new(index: usize, step: &CollectionStep) -> Self {
+        StepResultState {
+            label: format!("{} / {}", step.method.trim(), step.request.trim()).into(),
+            state: if index == 0 {
+                StateIcon::InProgress
+            } else {
+                StateIcon::NotStarted
+            },
+            duration: ArcStr::from(""),
+            error: None,
+            response: ArcStr::from(""),
+        }
+    }
+}
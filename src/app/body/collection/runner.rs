@@ -0,0 +1,314 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Error, Result};
+use druid::ArcStr;
+use futures::StreamExt;
+use http::Uri;
+use prost_reflect::{prost::Message, MethodDescriptor};
+use tonic::metadata::MetadataMap;
+
+use crate::{
+    app::{
+        body::{
+            method::{assertion, capture},
+            Address, Environment, SavedRequest,
+        },
+        metadata,
+        sidebar::service::{ServiceOptions, ServiceState},
+    },
+    assertion::Outcome,
+    auth::CallContext,
+    error::is_unauthenticated,
+    grpc,
+};
+
+/// A saved request to run as part of a collection, along with the options it is sent with.
+#[derive(Debug, Clone)]
+pub(in crate::app) struct Step {
+    method: MethodDescriptor,
+    request: SavedRequest,
+    options: ServiceOptions,
+}
+
+/// The outcome of running a single step.
+#[derive(Debug)]
+pub(in crate::app) struct StepResult {
+    pub duration: Option<Duration>,
+    pub responses: Vec<String>,
+    pub error: Option<Error>,
+    pub captured: Vec<(String, String)>,
+}
+
+impl Step {
+    pub fn new(method: MethodDescriptor, request: SavedRequest, options: ServiceOptions) -> Self {
+        Step {
+            options: request.service_options(&options),
+            method,
+            request,
+        }
+    }
+
+    /// Finds the saved request named `request` for a method given as `package.Service/Method`.
+    pub fn resolve(
+        services: &im::Vector<ServiceState>,
+        method: &str,
+        request: &str,
+    ) -> Result<Step> {
+        let (service_name, method_name) = method
+            .trim()
+            .rsplit_once('/')
+            .context("expected a method in the form 'package.Service/Method'")?;
+
+        let service = services
+            .iter()
+            .find(|service| service.service().full_name() == service_name)
+            .with_context(|| format!("service '{}' not found", service_name))?;
+        let method = service
+            .methods()
+            .find(|method| method.method().name() == method_name)
+            .with_context(|| format!("method '{}' not found", method_name))?;
+        let saved = method
+            .saved()
+            .iter()
+            .find(|saved| saved.name.as_str() == request.trim())
+            .with_context(|| format!("saved request '{}' not found", request.trim()))?;
+
+        Ok(Step::new(
+            method.method().clone(),
+            saved.clone(),
+            service.options().clone(),
+        ))
+    }
+}
+
+impl StepResult {
+    fn failed(error: Error) -> Self {
+        StepResult {
+            duration: None,
+            responses: Vec::new(),
+            error: Some(error),
+            captured: Vec::new(),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Runs each step in order, calling `on_result` after each one. Values captured by a step are
+/// available to the steps after it. A step which doesn't finish within `timeout` fails. Snapshots
+/// are loaded from the `snapshots` directory.
+pub(in crate::app) async fn run(
+    steps: Vec<Result<Step, ArcStr>>,
+    mut environment: Environment,
+    snapshots: &Path,
+    timeout: Option<Duration>,
+    continue_on_failure: bool,
+    mut on_result: impl FnMut(usize, StepResult),
+) {
+    for (index, step) in steps.into_iter().enumerate() {
+        let result = match step {
+            Ok(step) => run_step(&step, &environment, snapshots, timeout).await,
+            Err(err) => StepResult::failed(anyhow!("{}", err)),
+        };
+
+        environment.capture(&result.captured);
+        let failed = !result.is_success();
+        on_result(index, result);
+
+        if failed && !continue_on_failure {
+            break;
+        }
+    }
+}
+
+async fn run_step(
+    step: &Step,
+    environment: &Environment,
+    snapshots: &Path,
+    timeout: Option<Duration>,
+) -> StepResult {
+    let prepared = match prepare(step, environment) {
+        Ok(prepared) => prepared,
+        Err(err) => return StepResult::failed(err),
+    };
+
+    let client = match grpc::Client::new(&prepared.uri, &step.options.client_options()).await {
+        Ok(client) => client,
+        Err(err) => return StepResult::failed(err),
+    };
+
+    let auth_context = CallContext::new(
+        prepared.uri.to_string(),
+        step.method.parent_service().full_name(),
+        step.method.name(),
+    )
+    .with_request(&prepared.request.message.encode_to_vec());
+
+    let mut force_authorization = false;
+    loop {
+        let authorization = match &step.options.auth {
+            Some(auth) if force_authorization => auth.get_headers_force(&auth_context).await,
+            Some(auth) => auth.get_headers(&auth_context).await,
+            None => Ok(http::HeaderMap::new()),
+        };
+        let authorization = match authorization {
+            Ok(authorization) => authorization,
+            Err(err) => return StepResult::failed(err.context("authorization failed")),
+        };
+
+        let mut metadata = prepared.metadata.clone().into_headers();
+        metadata.extend(authorization);
+        let mut metadata = MetadataMap::from_headers(metadata);
+        if let Some(timeout) = step.options.timeout() {
+            grpc::set_timeout(&mut metadata, timeout);
+        }
+
+        let result = call(
+            client.clone(),
+            step,
+            prepared.request.clone(),
+            metadata,
+            snapshots,
+            timeout,
+        )
+        .await;
+
+        // Retry once with fresh credentials, as the method tab does.
+        let can_retry = !force_authorization
+            && result.responses.is_empty()
+            && result.error.as_ref().map_or(false, is_unauthenticated)
+            && step
+                .options
+                .auth
+                .as_ref()
+                .map_or(false, |auth| auth.can_refresh());
+        if can_retry {
+            tracing::info!("Request was unauthenticated, refreshing authorization");
+            force_authorization = true;
+        } else {
+            return result;
+        }
+    }
+}
+
+struct Prepared {
+    uri: Uri,
+    request: grpc::Request,
+    metadata: MetadataMap,
+}
+
+fn prepare(step: &Step, environment: &Environment) -> Result<Prepared> {
+    let address = if step.request.address.trim().is_empty() {
+        step.options
+            .default_address
+            .clone()
+            .context("no address is set")?
+    } else {
+        step.request
+            .address
+            .parse::<Address>()
+            .map_err(|err| anyhow!("invalid address: {}", err))?
+    };
+    let uri = address.resolve(environment)?;
+
+    let json = environment.expand_json(&step.request.request)?;
+    let request =
+        grpc::Request::from_json(step.method.input(), &json).context("invalid request")?;
+
+    let metadata =
+        metadata::EditableState::new(step.request.metadata.clone()).expand(environment)?;
+
+    Ok(Prepared {
+        uri,
+        request,
+        metadata,
+    })
+}
+
+async fn call(
+    client: grpc::Client,
+    step: &Step,
+    request: grpc::Request,
+    metadata: MetadataMap,
+    snapshots: &Path,
+    timeout: Option<Duration>,
+) -> StepResult {
+    let captures = capture::EditableState::new(step.request.captures.clone());
+    let assertions = assertion::EditableState::new(step.request.assertions.clone());
+    let mut outcome = Outcome::new();
+    assertions.load_snapshot(&mut outcome, snapshots, &step.method, &step.request.name);
+
+    let start = Instant::now();
+    let (mut call, mut responses) = client.call_stream(step.method.clone(), request, metadata);
+    // Only the saved request is sent, even for client streaming methods.
+    if step.method.is_client_streaming() {
+        call.finish();
+    }
+
+    let mut result = StepResult {
+        duration: None,
+        responses: Vec::new(),
+        error: None,
+        captured: Vec::new(),
+    };
+    let deadline = timeout.map(|timeout| (tokio::time::Instant::now() + timeout, timeout));
+    loop {
+        let response = match deadline {
+            Some((deadline, timeout)) => {
+                match tokio::time::timeout_at(deadline, responses.next()).await {
+                    Ok(response) => response,
+                    Err(_) => {
+                        // Dropping the call cancels it. Assertions aren't checked, since the
+                        // call has no outcome.
+                        result.duration = Some(start.elapsed());
+                        result.error =
+                            Some(anyhow!("timed out after {} seconds", timeout.as_secs()));
+                        return result;
+                    }
+                }
+            }
+            None => responses.next().await,
+        };
+        let Some(response) = response else { break };
+
+        match response {
+            grpc::ResponseResult::Metadata(metadata) => {
+                result.captured.extend(captures.capture_metadata(&metadata));
+                outcome.add_metadata(&metadata);
+            }
+            grpc::ResponseResult::Response(response) => {
+                let json = response.to_json();
+                result.captured.extend(captures.capture_json(&json));
+                outcome.add_response(&json, Some(start.elapsed()));
+                result.responses.push(json);
+            }
+            grpc::ResponseResult::Error(error, metadata) => {
+                result.captured.extend(captures.capture_metadata(&metadata));
+                outcome.set_error(&error, &metadata);
+                result.error = Some(error);
+                break;
+            }
+            grpc::ResponseResult::Finished => break,
+        }
+    }
+    result.duration = Some(start.elapsed());
+
+    // A call which fails with the expected status code counts as a success.
+    let failures: Vec<_> = assertions
+        .check(&outcome)
+        .into_iter()
+        .filter_map(|assertion| Some(format!("{}: {}", assertion.assertion, assertion.error?)))
+        .collect();
+    if !failures.is_empty() {
+        result.error = Some(anyhow!("assertions failed: {}", failures.join("; ")));
+    } else if assertions.has_status() {
+        result.error = None;
+    }
+
+    result
+}
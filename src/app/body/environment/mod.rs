@@ -0,0 +1,335 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::{bail, Result};
+use druid::{
+    widget::{prelude::*, Checkbox, Controller, CrossAxisAlignment, Flex, Label, Scroll},
+    Data, Lens, WidgetExt,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{command, workspace},
+    template,
+    theme::{self, BODY_PADDING, BODY_SPACER, GRID_NARROW_SPACER},
+    widget::{input, EditableList},
+};
+
+#[derive(Default, Debug, Clone, Data, Lens)]
+pub struct EnvironmentTabState {
+    #[lens(name = "environments_lens")]
+    environments: Arc<Vec<EnvironmentState>>,
+    captured: Arc<Vec<Variable>>,
+}
+
+/// The saved environments, and which one is used to expand placeholders.
+#[derive(Default, Debug, Clone, Data, Serialize, Deserialize)]
+pub struct Environments {
+    environments: Arc<Vec<Environment>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active: Option<usize>,
+    /// Variables captured from responses, which apply to whichever environment is active.
+    #[serde(default)]
+    captured: Arc<Vec<Variable>>,
+}
+
+/// A named set of variables, which may be referenced as `{{name}}` in addresses, metadata and
+/// requests.
+#[derive(Default, Debug, Clone, Data, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    name: Arc<String>,
+    variables: Arc<Vec<Variable>>,
+}
+
+#[derive(Default, Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+struct Variable {
+    name: Arc<String>,
+    value: Arc<String>,
+}
+
+#[derive(Default, Debug, Clone, Data, Lens)]
+struct EnvironmentState {
+    name: Arc<String>,
+    active: bool,
+    variables: Arc<Vec<Variable>>,
+}
+
+pub fn build_body() -> impl Widget<EnvironmentTabState> {
+    Scroll::new(
+        Flex::column()
+            .with_child(Label::new("Environments").with_font(theme::font::HEADER_TWO))
+            .with_spacer(theme::BODY_SPACER)
+            .with_child(
+                EditableList::new(
+                    "Add environment",
+                    |_, data: &mut Arc<Vec<EnvironmentState>>, _| {
+                        Arc::make_mut(data).push(EnvironmentState::default())
+                    },
+                    build_environment,
+                )
+                .lens(EnvironmentTabState::environments_lens),
+            )
+            .with_spacer(theme::BODY_SPACER)
+            .with_child(Label::new("Captured variables").with_font(theme::font::HEADER_TWO))
+            .with_spacer(theme::BODY_SPACER)
+            .with_child(
+                EditableList::new(
+                    "Add variable",
+                    |_, data: &mut Arc<Vec<Variable>>, _| {
+                        Arc::make_mut(data).push(Variable::default())
+                    },
+                    build_variable,
+                )
+                .lens(EnvironmentTabState::captured),
+            )
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .padding(BODY_PADDING),
+    )
+    .vertical()
+    .expand_height()
+    .controller(EnvironmentTabController)
+}
+
+fn build_environment() -> impl Widget<EnvironmentState> {
+    let header = Flex::row()
+        .with_flex_child(input("name").lens(EnvironmentState::name), 1.0)
+        .with_spacer(theme::BODY_SPACER)
+        .with_child(theme::check_box_scope(Checkbox::new("Active")).lens(EnvironmentState::active));
+
+    let variables = EditableList::new(
+        "Add variable",
+        |_, data: &mut Arc<Vec<Variable>>, _| Arc::make_mut(data).push(Variable::default()),
+        build_variable,
+    )
+    .lens(EnvironmentState::variables);
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(header)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(variables.padding((BODY_SPACER, 0.0, 0.0, 0.0)))
+        .padding((0.0, 0.0, 0.0, BODY_SPACER))
+}
+
+fn build_variable() -> impl Widget<Variable> {
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_flex_child(input("variable").lens(Variable::name), 0.33)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_flex_child(input("value").lens(Variable::value), 0.67)
+}
+
+impl EnvironmentTabState {
+    pub fn new(environments: &Environments) -> Self {
+        EnvironmentTabState {
+            environments: Arc::new(
+                environments
+                    .environments
+                    .iter()
+                    .enumerate()
+                    .map(|(index, environment)| EnvironmentState {
+                        name: environment.name.clone(),
+                        active: environments.active == Some(index),
+                        variables: environment.variables.clone(),
+                    })
+                    .collect(),
+            ),
+            captured: environments.captured.clone(),
+        }
+    }
+
+    pub fn set_captured(&mut self, environments: &Environments) {
+        self.captured = environments.captured.clone();
+    }
+
+    pub fn environments(&self) -> Environments {
+        Environments {
+            environments: Arc::new(
+                self.environments
+                    .iter()
+                    .map(|environment| Environment {
+                        name: environment.name.clone(),
+                        variables: environment.variables.clone(),
+                    })
+                    .collect(),
+            ),
+            active: self.environments.iter().position(|e| e.active),
+            captured: self.captured.clone(),
+        }
+    }
+}
+
+impl Environments {
+    /// Gets the active environment, or an empty one if none is active. Captured variables take
+    /// precedence over those defined in the environment.
+    pub fn active(&self) -> Environment {
+        let environment = self
+            .active
+            .and_then(|index| self.environments.get(index))
+            .cloned()
+            .unwrap_or_default();
+        self.with_captured(environment)
+    }
+
+    /// Gets the environment with the given name, or the active environment if the name is empty.
+    pub fn named(&self, name: &str) -> Option<Environment> {
+        if name.trim().is_empty() {
+            return Some(self.active());
+        }
+
+        let environment = self
+            .environments
+            .iter()
+            .find(|environment| environment.name.trim() == name.trim())?;
+        Some(self.with_captured(environment.clone()))
+    }
+
+    fn with_captured(&self, mut environment: Environment) -> Environment {
+        if !self.captured.is_empty() {
+            Arc::make_mut(&mut environment.variables).splice(0..0, self.captured.iter().cloned());
+        }
+        environment
+    }
+
+    /// Sets the values of captured variables, replacing any previous values with the same names.
+    pub fn capture(&mut self, values: &[(String, String)]) {
+        set_variables(&mut self.captured, values);
+    }
+
+    /// Removes variables captured from responses, and clears the values of variables whose names
+    /// suggest they hold secrets.
+    pub fn redact_secrets(&mut self) {
+        self.captured = Arc::default();
+        for environment in Arc::make_mut(&mut self.environments) {
+            if environment
+                .variables
+                .iter()
+                .any(|variable| workspace::is_secret_name(&variable.name))
+            {
+                for variable in Arc::make_mut(&mut environment.variables) {
+                    if workspace::is_secret_name(&variable.name) {
+                        variable.value = Arc::default();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn active_name(&self) -> Option<&str> {
+        self.active
+            .and_then(|index| self.environments.get(index))
+            .map(|environment| environment.name.as_str())
+    }
+}
+
+impl Environment {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|variable| variable.name.trim() == name)
+            .map(|variable| variable.value.as_str())
+    }
+
+    /// Sets the values of variables, replacing any previous values with the same names.
+    pub fn capture(&mut self, values: &[(String, String)]) {
+        set_variables(&mut self.variables, values);
+    }
+
+    /// Replaces `{{name}}` placeholders in the text with the values of variables, or the results
+    /// of built-in functions.
+    pub fn expand<'a>(&self, text: &'a str) -> Result<Cow<'a, str>> {
+        template::expand(text, |name| self.lookup(name))
+    }
+
+    /// Replaces placeholders in a JSON request, escaping values which are placed inside strings.
+    pub fn expand_json<'a>(&self, text: &'a str) -> Result<Cow<'a, str>> {
+        template::expand_json(text, |name| self.lookup(name))
+    }
+
+    fn lookup(&self, name: &str) -> Result<String> {
+        if let Some(value) = self.get(name) {
+            return Ok(value.to_owned());
+        }
+        if let Some(result) = template::call(name) {
+            return result;
+        }
+
+        if self.name.is_empty() {
+            bail!("variable '{}' is not defined", name)
+        } else {
+            bail!(
+                "variable '{}' is not defined in environment '{}'",
+                name,
+                self.name
+            )
+        }
+    }
+}
+
+fn set_variables(variables: &mut Arc<Vec<Variable>>, values: &[(String, String)]) {
+    if values.is_empty() {
+        return;
+    }
+
+    let variables = Arc::make_mut(variables);
+    for (name, value) in values {
+        let value = Arc::new(value.clone());
+        match variables
+            .iter_mut()
+            .find(|variable| variable.name.trim() == name)
+        {
+            Some(variable) => variable.value = value,
+            None => variables.push(Variable {
+                name: Arc::new(name.clone()),
+                value,
+            }),
+        }
+    }
+}
+
+struct EnvironmentTabController;
+
+impl<W> Controller<EnvironmentTabState, W> for EnvironmentTabController
+where
+    W: Widget<EnvironmentTabState>,
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EnvironmentTabState,
+        env: &Env,
+    ) {
+        let old_active = data.environments.iter().position(|e| e.active);
+        child.event(ctx, event, data, env);
+
+        // Only one environment may be active at a time.
+        if let Some(new_active) = data
+            .environments
+            .iter()
+            .enumerate()
+            .find(|&(index, e)| e.active && Some(index) != old_active)
+            .map(|(index, _)| index)
+        {
+            for (index, environment) in Arc::make_mut(&mut data.environments).iter_mut().enumerate()
+            {
+                environment.active = index == new_active;
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        child: &mut W,
+        ctx: &mut UpdateCtx,
+        old_data: &EnvironmentTabState,
+        data: &EnvironmentTabState,
+        env: &Env,
+    ) {
+        if !old_data.same(data) {
+            ctx.submit_command(command::SET_ENVIRONMENTS.with(data.environments()));
+        }
+
+        child.update(ctx, old_data, data, env)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+
+use druid::{
+    widget::{prelude::*, CrossAxisAlignment, Flex},
+    ArcStr, Data, Lens, WidgetExt,
+};
+use once_cell::sync::Lazy;
+use prost_reflect::MethodDescriptor;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::config::Config,
+    assertion::{Assertion, Outcome},
+    snapshot::Snapshot,
+    theme::GRID_NARROW_SPACER,
+    widget::{
+        env_error_label, input, EditableList, FinishEditController, FormField, ValidationFn,
+        ValidationState,
+    },
+};
+
+pub(in crate::app) type State = Arc<Vec<Arc<String>>>;
+
+type EntryValidationState = ValidationState<Arc<String>, Assertion>;
+
+/// Assertions to check against the result of each call.
+#[derive(Debug, Default, Clone, Data, Lens)]
+pub(in crate::app) struct EditableState {
+    entries: Arc<Vec<EntryValidationState>>,
+}
+
+/// Whether an assertion held for a call.
+#[derive(Debug, Clone, Data, Lens, Serialize, Deserialize)]
+pub(in crate::app) struct AssertionResult {
+    pub assertion: ArcStr,
+    pub error: Option<ArcStr>,
+}
+
+pub(in crate::app) fn build() -> impl Widget<EditableState> {
+    EditableList::new(
+        "Add assertion",
+        |_, data: &mut Arc<Vec<EntryValidationState>>, _| {
+            Arc::make_mut(data).push(ValidationState::new(Arc::default(), VALIDATE_ENTRY.clone()))
+        },
+        build_row,
+    )
+    .lens(EditableState::entries)
+}
+
+fn build_row() -> impl Widget<EntryValidationState> {
+    let form_id = WidgetId::next();
+
+    FormField::new(
+        form_id,
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Fill)
+            .with_child(
+                input("status OK, /id exists, /name == \"bob\", latency < 200ms or snapshot")
+                    .controller(FinishEditController::new(form_id)),
+            )
+            .with_child(env_error_label((0.0, GRID_NARROW_SPACER, 0.0, 0.0))),
+    )
+}
+
+impl EditableState {
+    pub fn new(assertions: State) -> EditableState {
+        let entries = Arc::new(
+            assertions
+                .iter()
+                .map(|entry| ValidationState::new(entry.clone(), VALIDATE_ENTRY.clone()))
+                .collect(),
+        );
+        EditableState { entries }
+    }
+
+    pub fn to_state(&self) -> State {
+        Arc::new(self.entries.iter().map(|e| e.text().clone()).collect())
+    }
+
+    pub fn add(&mut self, assertion: &str) {
+        Arc::make_mut(&mut self.entries).push(ValidationState::new(
+            Arc::new(assertion.to_owned()),
+            VALIDATE_ENTRY.clone(),
+        ));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether any assertion expects a particular status code.
+    pub fn has_status(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| matches!(entry.result(), Ok(Assertion::Status(_))))
+    }
+
+    /// Whether any assertion compares against a saved snapshot.
+    pub fn has_snapshot(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| matches!(entry.result(), Ok(Assertion::Snapshot(_))))
+    }
+
+    /// Loads the snapshot for the saved request from the given directory, if any assertion needs
+    /// it.
+    pub fn load_snapshot(
+        &self,
+        outcome: &mut Outcome,
+        directory: &Path,
+        method: &MethodDescriptor,
+        request: &str,
+    ) {
+        if self.has_snapshot() {
+            outcome.set_snapshot(Snapshot::load(&Snapshot::path(directory, method, request)));
+        }
+    }
+
+    /// Checks each valid assertion against the outcome of a call.
+    pub fn check(&self, outcome: &Outcome) -> Vec<AssertionResult> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let assertion = entry.result().ok()?;
+                Some(AssertionResult {
+                    assertion: entry.text().trim().into(),
+                    error: assertion.check(outcome).err().map(ArcStr::from),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Gets the path of the snapshot file for a saved request, next to the workspace file if one is
+/// open.
+pub(in crate::app) fn snapshot_path(
+    workspace: Option<&Path>,
+    method: &MethodDescriptor,
+    request: &str,
+) -> Result<PathBuf> {
+    Ok(Snapshot::path(
+        &Config::snapshot_directory(workspace)?,
+        method,
+        request,
+    ))
+}
+
+static VALIDATE_ENTRY: Lazy<ValidationFn<Arc<String>, Assertion>> =
+    Lazy::new(|| Arc::new(validate_entry));
+
+fn validate_entry(raw: &Arc<String>) -> Result<Assertion, ArcStr> {
+    raw.parse()
+        .map_err(|err: anyhow::Error| err.to_string().into())
+}
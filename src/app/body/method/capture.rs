@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use druid::{
+    widget::{prelude::*, CrossAxisAlignment, Flex},
+    ArcStr, Data, Lens, WidgetExt,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tonic::metadata::MetadataMap;
+
+use crate::{
+    capture::Source,
+    template,
+    theme::GRID_NARROW_SPACER,
+    widget::{
+        env_error_label, input, EditableList, FinishEditController, FormField, ValidationFn,
+        ValidationState,
+    },
+};
+
+pub(in crate::app) type State = Arc<Vec<Entry>>;
+
+type EntryValidationState = ValidationState<Entry, Source>;
+
+/// Rules for capturing values from responses into variables.
+#[derive(Debug, Default, Clone, Data, Lens)]
+pub(in crate::app) struct EditableState {
+    entries: Arc<Vec<EntryValidationState>>,
+}
+
+#[derive(Debug, Default, Clone, Data, Lens, Serialize, Deserialize)]
+pub(in crate::app) struct Entry {
+    variable: Arc<String>,
+    source: Arc<String>,
+}
+
+pub(in crate::app) fn build() -> impl Widget<EditableState> {
+    EditableList::new(
+        "Add capture",
+        |_, data: &mut Arc<Vec<EntryValidationState>>, _| {
+            Arc::make_mut(data).push(ValidationState::new(
+                Entry::default(),
+                VALIDATE_ENTRY.clone(),
+            ))
+        },
+        build_row,
+    )
+    .lens(EditableState::entries)
+}
+
+fn build_row() -> impl Widget<EntryValidationState> {
+    let form_id = WidgetId::next();
+
+    let inputs = Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_flex_child(
+            input("variable")
+                .controller(FinishEditController::new(form_id))
+                .lens(Entry::variable),
+            0.33,
+        )
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_flex_child(
+            input("/id, $.items[0].id or metadata:key")
+                .controller(FinishEditController::new(form_id))
+                .lens(Entry::source),
+            0.67,
+        );
+
+    FormField::new(
+        form_id,
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Fill)
+            .with_child(inputs)
+            .with_child(env_error_label((0.0, GRID_NARROW_SPACER, 0.0, 0.0))),
+    )
+}
+
+impl EditableState {
+    pub fn new(captures: State) -> EditableState {
+        let entries = Arc::new(
+            captures
+                .iter()
+                .map(|entry| ValidationState::new(entry.clone(), VALIDATE_ENTRY.clone()))
+                .collect(),
+        );
+        EditableState { entries }
+    }
+
+    pub fn to_state(&self) -> State {
+        Arc::new(self.entries.iter().map(|e| e.text().clone()).collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the values to capture from a response message.
+    pub fn capture_json(&self, json: &str) -> Vec<(String, String)> {
+        if !self.sources().any(|(_, source)| source.is_json()) {
+            return Vec::new();
+        }
+
+        let json = match serde_json::from_str(json) {
+            Ok(json) => json,
+            Err(err) => {
+                tracing::warn!("Failed to parse response for captures: {}", err);
+                return Vec::new();
+            }
+        };
+
+        self.sources()
+            .filter_map(|(variable, source)| Some((variable.to_owned(), source.from_json(&json)?)))
+            .collect()
+    }
+
+    /// Gets the values to capture from response headers or trailers.
+    pub fn capture_metadata(&self, metadata: &MetadataMap) -> Vec<(String, String)> {
+        self.sources()
+            .filter_map(|(variable, source)| {
+                Some((variable.to_owned(), source.from_metadata(metadata)?))
+            })
+            .collect()
+    }
+
+    fn sources(&self) -> impl Iterator<Item = (&str, &Source)> {
+        self.entries.iter().filter_map(|entry| {
+            let source = entry.result().ok()?;
+            Some((entry.text().variable.trim(), source))
+        })
+    }
+}
+
+static VALIDATE_ENTRY: Lazy<ValidationFn<Entry, Source>> = Lazy::new(|| Arc::new(validate_entry));
+
+fn validate_entry(raw: &Entry) -> Result<Source, ArcStr> {
+    let variable = raw.variable.trim();
+    if variable.is_empty() {
+        return Err("expected a variable name".into());
+    }
+    if template::has_placeholders(variable) || variable.contains("}}") {
+        return Err("invalid variable name".into());
+    }
+
+    raw.source
+        .parse()
+        .map_err(|err: anyhow::Error| err.to_string().into())
+}
@@ -1,17 +1,35 @@
+use std::{mem, path::PathBuf};
+
+use anyhow::{Context, Result};
+
 use druid::{
     widget::{prelude::*, Controller},
-    Command, Handled,
+    Application, Command, Handled,
 };
+use prost_reflect::prost::Message;
+use tokio::task::{self, JoinHandle};
 use tonic::metadata::MetadataMap;
 
 use crate::{
     app::{
-        body::{method::MethodTabState, RequestState},
+        body::{
+            method::{
+                assertion, request,
+                stream::{self, EXPORT_HISTORY, SAVE_SNAPSHOT},
+                MethodTabState, COPY_AS_GRPCURL,
+            },
+            RequestState,
+        },
         command,
+        config::Config,
     },
-    error::{fmt_connect_err, fmt_err},
-    grpc,
+    assertion::Outcome,
+    auth::CallContext,
+    error::{fmt_connect_err, fmt_err, is_unauthenticated},
+    grpc, grpcurl,
     json::JsonText,
+    snapshot::Snapshot,
+    snippet::{self, Snippet},
     widget::update_queue::{self, UpdateQueue},
 };
 
@@ -19,6 +37,17 @@ pub struct MethodTabController {
     updates: UpdateQueue<MethodTabController, MethodTabState>,
     client: Option<grpc::Client>,
     call: Option<grpc::Call>,
+    /// The request for the active call, kept until the first response in case the server rejects
+    /// our authorization and the request needs to be sent again.
+    retry_request: Option<(grpc::Request, JsonText)>,
+    /// The task running the authorization hook, if any. Aborting it kills the hook process.
+    authorization_task: Option<JoinHandle<()>>,
+    /// The result of the active call so far, checked against the request's assertions once it
+    /// completes.
+    outcome: Outcome,
+    /// The index of the call to export once a file is chosen, or `None` to export the whole
+    /// history.
+    export_call: Option<usize>,
 }
 
 impl MethodTabController {
@@ -27,6 +56,10 @@ impl MethodTabController {
             updates: UpdateQueue::new(),
             client: None,
             call: None,
+            retry_request: None,
+            authorization_task: None,
+            outcome: Outcome::new(),
+            export_call: None,
         }
     }
 }
@@ -45,6 +78,36 @@ where
     ) {
         match event {
             Event::Command(command) if self.command(ctx, command, data) == Handled::Yes => (),
+            Event::Notification(notification) if notification.is(SAVE_SNAPSHOT) => {
+                ctx.set_handled();
+                let json = notification.get(SAVE_SNAPSHOT).unwrap();
+                match save_snapshot(data, json) {
+                    Ok(path) => {
+                        tracing::info!("Saved snapshot to `{}`", path.display());
+                        if !data.request.assertions().has_snapshot() {
+                            data.request.assertions_mut().add("snapshot");
+                        }
+                    }
+                    Err(err) => data
+                        .address
+                        .set_request_state(RequestState::SendFailed(fmt_err(&err))),
+                }
+            }
+            Event::Notification(notification) if notification.is(EXPORT_HISTORY) => {
+                ctx.set_handled();
+                self.export_call = *notification.get(EXPORT_HISTORY).unwrap();
+                ctx.submit_command(command::export_history(data.method.name()));
+            }
+            Event::Notification(notification) if notification.is(COPY_AS_GRPCURL) => {
+                ctx.set_handled();
+                match notification.get(COPY_AS_GRPCURL).unwrap() {
+                    Some((json, call)) => copy_as_grpcurl(ctx, data, Ok(json.as_str()), *call),
+                    None => self.expand_request(ctx, data, |ctx, data, result| {
+                        let json = result.map(|(_, json)| json);
+                        copy_as_grpcurl(ctx, data, json.as_ref().map(JsonText::original_data), None)
+                    }),
+                }
+            }
             _ => child.event(ctx, event, data, env),
         }
     }
@@ -57,8 +120,9 @@ where
         data: &MethodTabState,
         env: &Env,
     ) {
-        if old_data.address.uri() != data.address.uri()
-            || old_data.service_options.verify_certs != data.service_options.verify_certs
+        if old_data.address.resolved_uri(&old_data.environment)
+            != data.address.resolved_uri(&data.environment)
+            || old_data.options().verify_certs != data.options().verify_certs
         {
             ctx.submit_command(command::DISCONNECT.to(ctx.widget_id()));
         }
@@ -85,9 +149,36 @@ impl MethodTabController {
         } else if command.is(command::FINISH) {
             self.finish_send();
             Handled::Yes
+        } else if command.is(command::CANCEL_AUTHORIZATION) {
+            self.cancel_authorization(data);
+            Handled::Yes
         } else if command.is(command::DISCONNECT) {
             self.disconnect(ctx, data);
             Handled::Yes
+        } else if command.is(command::EXPORT_HISTORY) {
+            self.export_call = None;
+            ctx.submit_command(command::export_history(data.method.name()));
+            Handled::Yes
+        } else if let Some(file) = command.get(command::EXPORT_HISTORY_ACCEPT) {
+            if let Err(err) = data.stream.export(file.path(), self.export_call) {
+                ctx.submit_command(
+                    command::SHOW_ERROR
+                        .with(format!("Error exporting history: {}", fmt_err(&err)).into()),
+                );
+            }
+            Handled::Yes
+        } else if let Some(&language) = command.get(command::COPY_SNIPPET) {
+            self.expand_request(ctx, data, move |ctx, data, result| {
+                match result.and_then(|(request, _)| code_snippet(data, &request, language)) {
+                    Ok(snippet) => Application::global().clipboard().put_string(snippet),
+                    Err(err) => {
+                        ctx.submit_command(command::SHOW_ERROR.with(
+                            format!("Error generating code snippet: {}", fmt_err(&err)).into(),
+                        ))
+                    }
+                }
+            });
+            Handled::Yes
         } else if command.is(update_queue::UPDATE) {
             while let Some(update) = self.updates.pop() {
                 (update)(self, ctx, data)
@@ -98,9 +189,41 @@ impl MethodTabController {
         }
     }
 
+    /// Expands the placeholders in the request editor, then calls `f` with the request and its
+    /// JSON text. Expanding may read files, so it runs off the UI thread.
+    fn expand_request(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut MethodTabState,
+        f: impl FnOnce(&mut EventCtx, &mut MethodTabState, Result<(grpc::Request, JsonText)>)
+            + Send
+            + 'static,
+    ) {
+        let template = match data.request().prepare() {
+            request::Prepared::Ready(request, json) => return f(ctx, data, Ok((request, json))),
+            request::Prepared::Template(template) => template,
+        };
+
+        let environment = data.environment.clone();
+        let update_writer = self.updates.writer(ctx);
+        tokio::spawn(async move {
+            let result = task::spawn_blocking(move || template.expand(&environment))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+                .map(|(request, json)| (request, JsonText::pretty(json)));
+            update_writer.write(move |_, ctx, data| f(ctx, data, result));
+        });
+    }
+
     fn start_connect(&mut self, ctx: &mut EventCtx, data: &mut MethodTabState) {
-        let uri = match data.address.uri() {
-            Some(uri) => uri.clone(),
+        let uri = match data.address.resolve(&data.environment) {
+            Some(Ok(uri)) => uri,
+            Some(Err(err)) => {
+                data.address
+                    .set_request_state(RequestState::ConnectFailed(fmt_err(&err)));
+                return;
+            }
             None => {
                 tracing::error!("Connect called with no address");
                 return;
@@ -112,9 +235,9 @@ impl MethodTabController {
         }
 
         let update_writer = self.updates.writer(ctx);
-        let verify_certs = data.service_options.verify_certs;
+        let client_options = data.options().client_options();
         tokio::spawn(async move {
-            let result = grpc::Client::new(&uri, verify_certs).await;
+            let result = grpc::Client::new(&uri, &client_options).await;
             update_writer.write(|controller, _, data| controller.finish_connect(data, result));
         });
 
@@ -136,47 +259,92 @@ impl MethodTabController {
     }
 
     fn start_send(&mut self, ctx: &mut EventCtx, data: &mut MethodTabState) {
-        if let Some(hook) = &data.service_options.auth_hook {
-            let hook = hook.clone();
-            let update_writer = self.updates.writer(ctx);
-            tokio::spawn(async move {
-                let result = hook.get_headers().await;
-                update_writer.write(|controller, ctx, data| match result {
-                    Ok(authorization) => {
-                        controller.start_send_with_authorization(ctx, data, authorization)
+        // Placeholders are expanded before authorization, so that hooks see the message which is
+        // actually sent. Expanding may read files, so it runs off the UI thread.
+        let template = match data.request().prepare() {
+            request::Prepared::Ready(request, json) => {
+                return self.start_authorization(ctx, data, request, json);
+            }
+            request::Prepared::Template(template) => template,
+        };
+
+        // The expansion is tracked like an authorization hook, so it can be cancelled and further
+        // requests wait for it to finish.
+        let environment = data.environment.clone();
+        let update_writer = self.updates.writer(ctx);
+        self.authorization_task = Some(tokio::spawn(async move {
+            let result = task::spawn_blocking(move || template.expand(&environment))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result);
+            update_writer.write(|controller, ctx, data| {
+                controller.authorization_task = None;
+                match result {
+                    Ok((request, json)) => {
+                        controller.start_authorization(ctx, data, request, JsonText::pretty(json))
                     }
                     Err(err) => data
                         .address
-                        .set_request_state(RequestState::AuthorizationHookFailed(fmt_err(&err))),
-                });
+                        .set_request_state(RequestState::SendFailed(fmt_err(&err))),
+                }
             });
+        }));
 
-            data.address
-                .set_request_state(RequestState::AuthorizationHookInProgress);
-        } else {
-            self.start_send_with_authorization(ctx, data, http::HeaderMap::new())
-        }
+        data.address
+            .set_request_state(RequestState::AuthorizationHookInProgress);
     }
 
-    fn start_send_with_authorization(
+    fn start_authorization(
         &mut self,
         ctx: &mut EventCtx,
         data: &mut MethodTabState,
-        authorization: http::HeaderMap,
+        request: grpc::Request,
+        json: JsonText,
     ) {
-        let request = match data.request().get() {
-            Some(request) => request.clone(),
-            None => {
-                tracing::error!("Send called with no request");
-                self.set_request_state(data);
-                return;
-            }
+        let Some(auth) = data.options().auth else {
+            self.send_request(ctx, data, request, json, http::HeaderMap::new(), false);
+            return;
         };
 
-        let json = data.request().get_json().clone();
-        data.stream.add_request(json);
+        let allow_retry = auth.can_refresh();
+        let context = call_context(data, &request);
+        let update_writer = self.updates.writer(ctx);
+        self.authorization_task = Some(tokio::spawn(async move {
+            let result = auth.get_headers(&context).await;
+            update_writer.write(move |controller, ctx, data| {
+                controller.authorization_task = None;
+                match result {
+                    Ok(authorization) => controller.send_request(
+                        ctx,
+                        data,
+                        request,
+                        json,
+                        authorization,
+                        allow_retry,
+                    ),
+                    Err(err) => data
+                        .address
+                        .set_request_state(RequestState::AuthorizationHookFailed(fmt_err(&err))),
+                }
+            });
+        }));
+
+        data.address
+            .set_request_state(RequestState::AuthorizationHookInProgress);
+    }
 
+    fn send_request(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut MethodTabState,
+        request: grpc::Request,
+        json: JsonText,
+        authorization: http::HeaderMap,
+        allow_retry: bool,
+    ) {
         if let Some(call) = &mut self.call {
+            data.stream.add_request(json);
+
             if data.method.is_client_streaming() {
                 call.send(request);
             } else {
@@ -191,16 +359,48 @@ impl MethodTabController {
                 }
             };
 
-            let mut metadata = data.request().tonic_metadata().into_headers();
+            let mut metadata = match data.request().tonic_metadata(&data.environment) {
+                Ok(metadata) => metadata.into_headers(),
+                Err(err) => {
+                    data.address
+                        .set_request_state(RequestState::SendFailed(fmt_err(&err)));
+                    return;
+                }
+            };
+            let address = data
+                .address
+                .resolved_uri(&data.environment)
+                .map(|uri| uri.to_string())
+                .unwrap_or_default();
+            data.stream.start_call(stream::Call::new(
+                &data.method,
+                address,
+                &MetadataMap::from_headers(metadata.clone()),
+            ));
+
             metadata.extend(authorization);
-            let metadata = MetadataMap::from_headers(metadata);
+            let mut metadata = MetadataMap::from_headers(metadata);
+            if let Some(timeout) = data.options().timeout() {
+                grpc::set_timeout(&mut metadata, timeout);
+            }
+
+            data.stream.add_request(json.clone());
+            self.outcome = Outcome::new();
+
+            // Requests from a client streaming call can't be replayed, so only retry if the whole
+            // call is described by a single request.
+            self.retry_request = if allow_retry && !data.method.is_client_streaming() {
+                Some((request.clone(), json))
+            } else {
+                None
+            };
 
             let update_writer = self.updates.writer(ctx);
             self.call =
                 Some(
                     client.call(data.method.clone(), request, metadata, move |response| {
-                        update_writer.write(|controller, _, data| {
-                            controller.handle_response(data, response)
+                        update_writer.write(|controller, ctx, data| {
+                            controller.handle_response(ctx, data, response)
                         });
                     }),
                 );
@@ -215,39 +415,135 @@ impl MethodTabController {
         }
     }
 
-    fn handle_response(&mut self, data: &mut MethodTabState, response: grpc::ResponseResult) {
+    fn handle_response(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut MethodTabState,
+        response: grpc::ResponseResult,
+    ) {
         match response {
             grpc::ResponseResult::Response(response) => {
+                self.retry_request = None;
+
                 let duration = match &mut self.call {
                     Some(call) => call.duration(&response),
                     _ => None,
                 };
 
-                let json_result = JsonText::short(response.to_json());
+                let json = response.to_json();
+                submit_captures(ctx, data.request().captures().capture_json(&json));
+                self.outcome.add_response(&json, duration);
+
+                let json_result = JsonText::short(json);
 
                 data.stream
                     .add_response(data.method.parent_pool(), Ok(json_result), duration);
             }
             grpc::ResponseResult::Error(error, metadata) => {
+                let retry_request = if is_unauthenticated(&error) {
+                    self.retry_request.take()
+                } else {
+                    None
+                };
+
+                submit_captures(ctx, data.request().captures().capture_metadata(&metadata));
+                self.outcome.set_error(&error, &metadata);
+
                 data.stream
                     .add_response(data.method.parent_pool(), Err(error), None);
                 data.stream.add_metadata(metadata);
                 self.call = None;
+
+                if let Some((request, json)) = retry_request {
+                    self.retry_with_new_authorization(ctx, data, request, json);
+                    return;
+                }
+                self.check_assertions(data);
             }
             grpc::ResponseResult::Metadata(metadata) => {
+                submit_captures(ctx, data.request().captures().capture_metadata(&metadata));
+                self.outcome.add_metadata(&metadata);
                 data.stream.add_metadata(metadata);
             }
             grpc::ResponseResult::Finished => {
+                data.stream.finish_call();
                 self.call = None;
+                self.check_assertions(data);
+            }
+        }
+
+        if self.call.is_none() {
+            self.retry_request = None;
+        }
+        self.set_request_state(data);
+    }
+
+    fn check_assertions(&mut self, data: &mut MethodTabState) {
+        let mut outcome = mem::take(&mut self.outcome);
+        let assertions = data.request().assertions();
+        match Config::snapshot_directory(data.workspace()) {
+            Ok(directory) => {
+                assertions.load_snapshot(&mut outcome, &directory, data.method(), data.name())
             }
+            Err(err) => outcome.set_snapshot(Err(err)),
+        }
+        let results = assertions.check(&outcome);
+        if !results.is_empty() {
+            data.stream.add_assertions(results);
+        }
+    }
+
+    fn retry_with_new_authorization(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut MethodTabState,
+        request: grpc::Request,
+        json: JsonText,
+    ) {
+        let Some(auth) = data.options().auth else {
+            self.set_request_state(data);
+            return;
+        };
+
+        tracing::info!("Request was unauthenticated, refreshing authorization");
+
+        let context = call_context(data, &request);
+        let update_writer = self.updates.writer(ctx);
+        self.authorization_task = Some(tokio::spawn(async move {
+            let result = auth.get_headers_force(&context).await;
+            update_writer.write(|controller, ctx, data| {
+                controller.authorization_task = None;
+                match result {
+                    Ok(authorization) => {
+                        controller.send_request(ctx, data, request, json, authorization, false)
+                    }
+                    Err(err) => data
+                        .address
+                        .set_request_state(RequestState::AuthorizationHookFailed(fmt_err(&err))),
+                }
+            });
+        }));
+
+        data.address
+            .set_request_state(RequestState::AuthorizationHookInProgress);
+    }
+
+    fn cancel_authorization(&mut self, data: &mut MethodTabState) {
+        if let Some(task) = self.authorization_task.take() {
+            tracing::info!("Cancelling authorization hook");
+            task.abort();
         }
 
         self.set_request_state(data);
     }
 
     fn disconnect(&mut self, _: &mut EventCtx, data: &mut MethodTabState) {
+        if let Some(task) = self.authorization_task.take() {
+            task.abort();
+        }
         self.client = None;
         self.call = None;
+        self.retry_request = None;
 
         self.set_request_state(data);
 
@@ -271,3 +567,100 @@ impl MethodTabController {
         data.address.set_request_state(request_state);
     }
 }
+
+fn submit_captures(ctx: &mut EventCtx, values: Vec<(String, String)>) {
+    if !values.is_empty() {
+        ctx.submit_command(command::CAPTURE_VARIABLES.with(values));
+    }
+}
+
+fn call_context(data: &MethodTabState, request: &grpc::Request) -> CallContext {
+    let address = data
+        .address
+        .resolved_uri(&data.environment)
+        .map(|uri| uri.to_string())
+        .unwrap_or_default();
+    CallContext::new(
+        address,
+        data.method.parent_service().full_name(),
+        data.method.name(),
+    )
+    .with_request(&request.message.encode_to_vec())
+}
+
+/// Stores a response as the golden snapshot for the tab's request.
+fn save_snapshot(data: &MethodTabState, json: &str) -> Result<PathBuf> {
+    let path = assertion::snapshot_path(data.workspace(), data.method(), data.name())?;
+    let snapshot = Snapshot {
+        method: format!(
+            "{}/{}",
+            data.method().parent_service().full_name(),
+            data.method().name()
+        ),
+        request: data.name().to_owned(),
+        response: serde_json::from_str(json)?,
+    };
+    snapshot.store(&path)?;
+    Ok(path)
+}
+
+/// Submits a command to copy a grpcurl command with the given request body, or the error from
+/// building it.
+fn copy_as_grpcurl(
+    ctx: &mut EventCtx,
+    data: &MethodTabState,
+    json: Result<&str, &anyhow::Error>,
+    call: Option<usize>,
+) {
+    let grpcurl = match json {
+        Ok(json) => grpcurl_command(data, json, call).map_err(|err| fmt_err(&err)),
+        Err(err) => Err(fmt_err(err)),
+    };
+    ctx.submit_command(command::COPY_AS_GRPCURL.with((data.method.clone(), grpcurl)));
+}
+
+/// Builds a grpcurl command for the tab's address, with the given request body and the metadata
+/// of the call it was sent in, or else the metadata in the editor. Authorization metadata is not
+/// included.
+fn grpcurl_command(
+    data: &MethodTabState,
+    json: &str,
+    call: Option<usize>,
+) -> Result<grpcurl::Command> {
+    let uri = data
+        .address
+        .resolve(&data.environment)
+        .context("no address is set")??;
+
+    let mut grpcurl = grpcurl::Command::new(&data.method, uri, data.options().verify_certs);
+    let metadata = match call.and_then(|call| data.stream.request_metadata(call)) {
+        Some(metadata) => metadata,
+        None => data.request().tonic_metadata(&data.environment)?,
+    };
+    grpcurl.set_metadata(metadata);
+    grpcurl.set_data(json);
+    Ok(grpcurl)
+}
+
+/// Generates client code which sends the expanded request from the editor to the tab's address,
+/// with its metadata.
+fn code_snippet(
+    data: &MethodTabState,
+    request: &grpc::Request,
+    language: snippet::Language,
+) -> Result<String> {
+    let uri = data
+        .address
+        .resolve(&data.environment)
+        .context("no address is set")??;
+    let metadata = data.request().tonic_metadata(&data.environment)?;
+
+    Ok(Snippet::new(
+        &data.method,
+        &request.message,
+        &uri,
+        data.options().verify_certs,
+        metadata,
+    )
+    .generate(language))
+}
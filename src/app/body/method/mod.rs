@@ -1,25 +1,45 @@
+pub(in crate::app) mod assertion;
+pub(in crate::app) mod capture;
 mod controller;
+mod options;
 mod request;
 mod stream;
 
-pub(in crate::app) use self::stream::State as StreamState;
+pub(in crate::app) use self::{
+    assertion::State as AssertionState,
+    capture::State as CaptureState,
+    options::OptionsOverride,
+    stream::{format_duration, State as StreamState, Summary as StreamSummary},
+};
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use druid::{
     widget::{prelude::*, Button, CrossAxisAlignment, Flex, Label, Split},
-    Data, Lens, WidgetExt,
+    ArcStr, Data, Lens, Selector, WidgetExt,
 };
+use serde::{Deserialize, Serialize};
 
 use self::controller::MethodTabController;
 use crate::{
     app::{
-        body::{address, RequestState},
+        body::{address, Environment, RequestState},
         command, metadata,
         sidebar::service::ServiceOptions,
     },
     json::JsonText,
     theme,
+    widget::{input, Icon},
 };
 
+/// Copy the given request JSON and the index of the call it was sent in, or the request in the
+/// editor if `None`, as a grpcurl command. Submitted as a notification.
+pub(in crate::app) const COPY_AS_GRPCURL: Selector<Option<(String, Option<usize>)>> =
+    Selector::new("app.body.method.copy-as-grpcurl");
+
 #[derive(Debug, Clone, Data, Lens)]
 pub struct MethodTabState {
     #[lens(ignore)]
@@ -33,6 +53,41 @@ pub struct MethodTabState {
     stream: stream::State,
     #[lens(ignore)]
     service_options: ServiceOptions,
+    /// The options saved with the request, which override the service's options.
+    #[lens(name = "options_lens")]
+    options: options::EditableState,
+    #[lens(ignore)]
+    environment: Environment,
+    /// The open workspace file, which snapshots are stored next to.
+    #[lens(ignore)]
+    workspace: Option<Arc<PathBuf>>,
+    #[lens(name = "name_lens")]
+    name: Arc<String>,
+}
+
+/// A named request for a method, which can be saved and later restored into a tab.
+#[derive(Debug, Default, Clone, Data, Serialize, Deserialize)]
+pub(in crate::app) struct SavedRequest {
+    pub name: Arc<String>,
+    pub address: Arc<String>,
+    pub request: Arc<String>,
+    #[serde(default)]
+    pub metadata: metadata::State,
+    #[serde(default)]
+    pub captures: CaptureState,
+    #[serde(default)]
+    pub assertions: AssertionState,
+    /// Options which override the service's options, such as its address, certificate
+    /// verification and authorization, when sending this request.
+    #[serde(default, skip_serializing_if = "OptionsOverride::is_empty")]
+    pub options: OptionsOverride,
+}
+
+impl SavedRequest {
+    /// Gets the options to send this request with, given the options of its service.
+    pub fn service_options(&self, service_options: &ServiceOptions) -> ServiceOptions {
+        self.options.apply(service_options)
+    }
 }
 
 pub fn build_body() -> impl Widget<MethodTabState> {
@@ -42,12 +97,9 @@ pub fn build_body() -> impl Widget<MethodTabState> {
         Flex::column()
             .with_child(build_address_bar(id))
             .with_spacer(theme::BODY_SPACER)
-            .with_child(
-                Label::new("Request editor")
-                    .with_font(theme::font::HEADER_TWO)
-                    .lens(MethodTabState::request_lens),
-            )
+            .with_child(build_request_header())
             .with_spacer(theme::BODY_SPACER)
+            .with_child(options::build().lens(MethodTabState::options_lens))
             .with_flex_child(request::build().lens(MethodTabState::request_lens), 1.0)
             .cross_axis_alignment(CrossAxisAlignment::Start)
             .padding(theme::BODY_PADDING),
@@ -74,9 +126,9 @@ fn build_address_bar(body_id: WidgetId) -> impl Widget<MethodTabState> {
             |data: &MethodTabState, _| match data.address.request_state() {
                 RequestState::NotStarted | RequestState::ConnectFailed(_) => "Connect".to_owned(),
                 RequestState::ConnectInProgress => "Connecting...".to_owned(),
-                RequestState::Connected | RequestState::AuthorizationHookFailed(_) => {
-                    "Send".to_owned()
-                }
+                RequestState::Connected
+                | RequestState::AuthorizationHookFailed(_)
+                | RequestState::SendFailed(_) => "Send".to_owned(),
                 RequestState::SendInProgress if data.method.is_client_streaming() => {
                     "Send".to_owned()
                 }
@@ -97,7 +149,8 @@ fn build_address_bar(body_id: WidgetId) -> impl Widget<MethodTabState> {
                     }
                     RequestState::Connected
                     | RequestState::SendInProgress
-                    | RequestState::AuthorizationHookFailed(_) => {
+                    | RequestState::AuthorizationHookFailed(_)
+                    | RequestState::SendFailed(_) => {
                         debug_assert!(data.can_send());
                         ctx.submit_command(command::SEND.to(body_id));
                     }
@@ -113,6 +166,7 @@ fn build_address_bar(body_id: WidgetId) -> impl Widget<MethodTabState> {
                 RequestState::SendInProgress if data.method.is_client_streaming() => {
                     "Finish".to_owned()
                 }
+                RequestState::AuthorizationHookInProgress => "Cancel".to_owned(),
                 _ => "Disconnect".to_owned(),
             },
         )
@@ -124,11 +178,14 @@ fn build_address_bar(body_id: WidgetId) -> impl Widget<MethodTabState> {
                     RequestState::SendInProgress if data.method.is_client_streaming() => {
                         ctx.submit_command(command::FINISH.to(body_id));
                     }
+                    RequestState::AuthorizationHookInProgress => {
+                        ctx.submit_command(command::CANCEL_AUTHORIZATION.to(body_id));
+                    }
                     RequestState::ConnectInProgress
-                    | RequestState::AuthorizationHookInProgress
                     | RequestState::Connected
                     | RequestState::SendInProgress
-                    | RequestState::AuthorizationHookFailed(_) => {
+                    | RequestState::AuthorizationHookFailed(_)
+                    | RequestState::SendFailed(_) => {
                         ctx.submit_command(command::DISCONNECT.to(body_id));
                     }
                 }
@@ -145,6 +202,39 @@ fn build_address_bar(body_id: WidgetId) -> impl Widget<MethodTabState> {
         .with_child(finish_button.fix_width(100.0))
 }
 
+fn build_request_header() -> impl Widget<MethodTabState> {
+    let save_button = theme::button_scope(Button::new("Save").on_click(
+        |ctx: &mut EventCtx, _: &mut MethodTabState, _: &Env| {
+            ctx.submit_command(command::SAVE_REQUEST);
+        },
+    ))
+    .disabled_if(|data: &MethodTabState, _| !data.can_save());
+
+    let copy_as_grpcurl = Icon::terminal().button(|ctx, _: &mut MethodTabState, _| {
+        ctx.submit_notification(COPY_AS_GRPCURL.with(None));
+    });
+
+    let edit_options = Icon::settings().button(|_, data: &mut MethodTabState, _| {
+        data.options.toggle_expanded();
+    });
+
+    Flex::row()
+        .with_child(Label::new("Request editor").with_font(theme::font::HEADER_TWO))
+        .with_flex_spacer(1.0)
+        .with_child(edit_options)
+        .with_spacer(theme::BODY_SPACER)
+        .with_child(copy_as_grpcurl)
+        .with_spacer(theme::BODY_SPACER)
+        .with_child(
+            input("request name")
+                .lens(MethodTabState::name_lens)
+                .fix_width(200.0),
+        )
+        .with_spacer(theme::BODY_SPACER)
+        .with_child(save_button.fix_width(100.0))
+        .must_fill_main_axis(true)
+}
+
 impl MethodTabState {
     pub fn empty(method: prost_reflect::MethodDescriptor, service_options: ServiceOptions) -> Self {
         MethodTabState {
@@ -154,25 +244,37 @@ impl MethodTabState {
                 method.input(),
                 service_options.default_metadata.clone(),
             ),
+            options: options::EditableState::new(&OptionsOverride::default(), &service_options),
             service_options,
             method,
+            environment: Environment::default(),
+            workspace: None,
+            name: Arc::default(),
         }
     }
 
     pub fn new(
         method: prost_reflect::MethodDescriptor,
-        address: String,
-        request: impl Into<JsonText>,
-        request_metadata: metadata::State,
+        saved: SavedRequest,
         stream: stream::State,
         service_options: ServiceOptions,
     ) -> Self {
         MethodTabState {
-            address: address::AddressState::new(address),
-            request: request::State::with_text(method.input(), request, request_metadata),
+            address: address::AddressState::new(saved.address.as_ref().clone()),
+            request: request::State::with_text(
+                method.input(),
+                JsonText::pretty(saved.request.as_str()),
+                saved.metadata,
+                saved.captures,
+                saved.assertions,
+            ),
             method,
             stream,
+            options: options::EditableState::new(&saved.options, &service_options),
             service_options,
+            environment: Environment::default(),
+            workspace: None,
+            name: saved.name,
         }
     }
 
@@ -180,6 +282,35 @@ impl MethodTabState {
         &self.method
     }
 
+    pub fn workspace(&self) -> Option<&Path> {
+        self.workspace.as_deref().map(PathBuf::as_path)
+    }
+
+    /// The name this tab's request is saved under, or an empty string if it has not been saved.
+    pub fn name(&self) -> &str {
+        self.name.trim()
+    }
+
+    pub fn label(&self) -> ArcStr {
+        if self.name().is_empty() {
+            self.method.name().into()
+        } else {
+            format!("{} ({})", self.method.name(), self.name()).into()
+        }
+    }
+
+    pub(in crate::app) fn saved_request(&self) -> SavedRequest {
+        SavedRequest {
+            name: Arc::new(self.name().to_owned()),
+            address: Arc::new(self.address.text().to_owned()),
+            request: Arc::new(self.request.text().as_str().to_owned()),
+            metadata: self.request.serde_metadata(),
+            captures: self.request.captures().to_state(),
+            assertions: self.request.assertions().to_state(),
+            options: self.options.to_override(),
+        }
+    }
+
     pub(in crate::app) fn address(&self) -> &address::AddressState {
         &self.address
     }
@@ -204,7 +335,7 @@ impl MethodTabState {
                 | RequestState::ConnectInProgress
                 | RequestState::AuthorizationHookInProgress
                 | RequestState::ConnectFailed(_) => false,
-                RequestState::Connected => true,
+                RequestState::Connected | RequestState::SendFailed(_) => true,
                 RequestState::SendInProgress | RequestState::AuthorizationHookFailed(_) => {
                     self.method.is_client_streaming()
                 }
@@ -219,10 +350,15 @@ impl MethodTabState {
                 | RequestState::ConnectInProgress
                 | RequestState::AuthorizationHookInProgress
                 | RequestState::SendInProgress
-                | RequestState::AuthorizationHookFailed(_) => false,
+                | RequestState::AuthorizationHookFailed(_)
+                | RequestState::SendFailed(_) => false,
             }
     }
 
+    pub fn can_save(&self) -> bool {
+        !self.name().is_empty()
+    }
+
     pub fn can_finish(&self) -> bool {
         matches!(self.address.request_state(), RequestState::SendInProgress)
             && self.method.is_client_streaming()
@@ -234,16 +370,36 @@ impl MethodTabState {
             | RequestState::AuthorizationHookInProgress
             | RequestState::Connected
             | RequestState::SendInProgress
-            | RequestState::AuthorizationHookFailed(_) => true,
+            | RequestState::AuthorizationHookFailed(_)
+            | RequestState::SendFailed(_) => true,
             RequestState::NotStarted | RequestState::ConnectFailed(_) => false,
         }
     }
 
+    /// The options of this tab's service, without the request's overrides.
     pub fn service_options(&self) -> &ServiceOptions {
         &self.service_options
     }
 
+    /// The options to send this tab's request with.
+    pub fn options(&self) -> ServiceOptions {
+        self.options.to_override().apply(&self.service_options)
+    }
+
+    pub(in crate::app) fn options_override(&self) -> OptionsOverride {
+        self.options.to_override()
+    }
+
     pub fn set_service_options(&mut self, options: ServiceOptions) {
+        self.options.set_service_options(&options);
         self.service_options = options;
     }
+
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
+    pub fn set_workspace(&mut self, workspace: Option<Arc<PathBuf>>) {
+        self.workspace = workspace;
+    }
 }
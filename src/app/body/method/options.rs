@@ -0,0 +1,381 @@
+use std::sync::Arc;
+
+use druid::{
+    widget::{prelude::*, Checkbox, CrossAxisAlignment, Either, Flex, Label},
+    ArcStr, Data, Insets, Lens, WidgetExt,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    app::{
+        body::{options::auth, Address},
+        metadata,
+        sidebar::service::{parse_timeout_secs, ServiceOptions},
+    },
+    auth::AuthProvider,
+    lens, theme,
+    widget::{empty, error_label, input},
+};
+
+/// Options saved with a request. Each field that is set replaces the same option of the request's
+/// service.
+#[derive(Debug, Default, Clone, Data, Serialize, Deserialize)]
+pub(in crate::app) struct OptionsOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[data(same_fn = "PartialEq::eq")]
+    pub default_address: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_certs: Option<bool>,
+    /// The timeout in seconds, or `Some(None)` for no timeout.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub timeout_secs: Option<Option<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_metadata: Option<metadata::State>,
+    /// The authorization provider, or `Some(None)` to send the request without authorization.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub auth: Option<Option<Arc<AuthProvider>>>,
+}
+
+/// The editor for the options overridden by a method tab's request.
+#[derive(Debug, Clone, Data, Lens)]
+pub(in crate::app) struct EditableState {
+    /// Whether the editor is shown.
+    expanded: bool,
+    override_address: bool,
+    address: Arc<String>,
+    override_verify_certs: bool,
+    verify_certs: bool,
+    override_timeout: bool,
+    timeout: Arc<String>,
+    override_metadata: bool,
+    metadata: metadata::EditableState,
+    override_auth: bool,
+    auth: auth::State,
+}
+
+const FIELD_LABEL_WIDTH: f64 = 160.0;
+
+pub(in crate::app) fn build() -> impl Widget<EditableState> {
+    let address = Flex::row()
+        .with_child(override_checkbox(
+            "Address",
+            EditableState::override_address,
+        ))
+        .with_flex_child(
+            input("https://localhost:443")
+                .lens(EditableState::address)
+                .disabled_if(|data: &EditableState, _| !data.override_address),
+            1.0,
+        );
+
+    let verify_certs = Flex::row()
+        .with_child(override_checkbox(
+            "Certificate verification",
+            EditableState::override_verify_certs,
+        ))
+        .with_child(
+            theme::check_box_scope(Checkbox::new("Enable certificate verification"))
+                .lens(EditableState::verify_certs)
+                .disabled_if(|data: &EditableState, _| !data.override_verify_certs),
+        );
+
+    let timeout = Flex::row()
+        .with_child(override_checkbox(
+            "Timeout (seconds)",
+            EditableState::override_timeout,
+        ))
+        .with_child(
+            input("None")
+                .lens(EditableState::timeout)
+                .disabled_if(|data: &EditableState, _| !data.override_timeout)
+                .fix_width(80.0),
+        );
+
+    let metadata = Either::new(
+        |data: &EditableState, _| data.override_metadata,
+        metadata::build_editable().lens(EditableState::metadata),
+        empty(),
+    );
+
+    let auth = Either::new(
+        |data: &EditableState, _| data.override_auth,
+        auth::build().lens(EditableState::auth),
+        empty(),
+    );
+
+    let error = error_label(Insets::ZERO)
+        .expand_width()
+        .lens(lens::Project::new(|data: &EditableState| data.error()));
+
+    let form = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Options").with_font(theme::font::HEADER_TWO))
+        .with_spacer(theme::BODY_SPACER)
+        .with_child(address)
+        .with_spacer(theme::GRID_NARROW_SPACER)
+        .with_child(verify_certs)
+        .with_spacer(theme::GRID_NARROW_SPACER)
+        .with_child(timeout)
+        .with_spacer(theme::GRID_NARROW_SPACER)
+        .with_child(override_checkbox(
+            "Default metadata",
+            EditableState::override_metadata,
+        ))
+        .with_child(metadata)
+        .with_spacer(theme::GRID_NARROW_SPACER)
+        .with_child(override_checkbox(
+            "Authorization",
+            EditableState::override_auth,
+        ))
+        .with_child(auth)
+        .with_child(error)
+        .padding((0.0, 0.0, 0.0, theme::BODY_SPACER));
+
+    Either::new(|data: &EditableState, _| data.expanded, form, empty())
+}
+
+fn override_checkbox(
+    label: &str,
+    lens: impl Lens<EditableState, bool> + 'static,
+) -> impl Widget<EditableState> {
+    theme::check_box_scope(Checkbox::new(label))
+        .lens(lens)
+        .fix_width(FIELD_LABEL_WIDTH)
+}
+
+impl OptionsOverride {
+    pub fn is_empty(&self) -> bool {
+        self.default_address.is_none()
+            && self.verify_certs.is_none()
+            && self.timeout_secs.is_none()
+            && self.default_metadata.is_none()
+            && self.auth.is_none()
+    }
+
+    /// Gets the options of the request's service, with the fields that are set replaced.
+    pub fn apply(&self, options: &ServiceOptions) -> ServiceOptions {
+        ServiceOptions {
+            default_address: self
+                .default_address
+                .clone()
+                .or_else(|| options.default_address.clone()),
+            verify_certs: self.verify_certs.unwrap_or(options.verify_certs),
+            default_metadata: self
+                .default_metadata
+                .clone()
+                .unwrap_or_else(|| options.default_metadata.clone()),
+            auth: self.auth.clone().unwrap_or_else(|| options.auth.clone()),
+            timeout_secs: self.timeout_secs.unwrap_or(options.timeout_secs),
+        }
+    }
+
+    /// Removes the authorization provider, and the values of default metadata with secret names.
+    pub fn redact_secrets(&mut self) {
+        self.auth = None;
+        if let Some(metadata) = &mut self.default_metadata {
+            metadata::redact_secrets(metadata);
+        }
+    }
+}
+
+/// Deserializes a field which is `Some` whenever it is present, even if its value is `null`.
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl EditableState {
+    /// Creates the editor for a request's overrides. Fields which aren't overridden show the
+    /// service's options.
+    pub fn new(options: &OptionsOverride, service_options: &ServiceOptions) -> Self {
+        let mut state = EditableState {
+            expanded: false,
+            override_address: options.default_address.is_some(),
+            address: Arc::default(),
+            override_verify_certs: options.verify_certs.is_some(),
+            verify_certs: true,
+            override_timeout: options.timeout_secs.is_some(),
+            timeout: Arc::default(),
+            override_metadata: options.default_metadata.is_some(),
+            metadata: metadata::EditableState::default(),
+            override_auth: options.auth.is_some(),
+            auth: auth::State::default(),
+        };
+        state.set_fields(&options.apply(service_options), true);
+        state
+    }
+
+    pub fn toggle_expanded(&mut self) {
+        self.expanded = !self.expanded;
+    }
+
+    /// Updates the fields which aren't overridden to show the service's new options.
+    pub fn set_service_options(&mut self, service_options: &ServiceOptions) {
+        self.set_fields(service_options, false);
+    }
+
+    /// Sets the fields from `options`, or only those which aren't overridden unless `all` is set.
+    fn set_fields(&mut self, options: &ServiceOptions, all: bool) {
+        if all || !self.override_address {
+            self.address = Arc::new(
+                options
+                    .default_address
+                    .as_ref()
+                    .map(|address| address.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        if all || !self.override_verify_certs {
+            self.verify_certs = options.verify_certs;
+        }
+        if all || !self.override_timeout {
+            self.timeout = Arc::new(
+                options
+                    .timeout_secs
+                    .map(|secs| secs.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        if all || !self.override_metadata {
+            self.metadata = metadata::EditableState::new(options.default_metadata.clone());
+        }
+        if all || !self.override_auth {
+            self.auth = auth::State::new(&options.auth);
+        }
+    }
+
+    /// Gets the overridden options. Fields with invalid values are left unset.
+    pub fn to_override(&self) -> OptionsOverride {
+        OptionsOverride {
+            default_address: if self.override_address {
+                self.address.parse().ok()
+            } else {
+                None
+            },
+            verify_certs: self.override_verify_certs.then_some(self.verify_certs),
+            timeout_secs: if self.override_timeout {
+                parse_timeout_secs(&self.timeout).ok()
+            } else {
+                None
+            },
+            default_metadata: self.override_metadata.then(|| self.metadata.to_state()),
+            auth: self.override_auth.then(|| self.auth.provider()),
+        }
+    }
+
+    pub fn error(&self) -> Option<ArcStr> {
+        if self.override_address {
+            if let Err(err) = self.address.parse::<Address>() {
+                return Some(format!("invalid address: {}", err).into());
+            }
+        }
+        if self.override_timeout {
+            if let Err(err) = parse_timeout_secs(&self.timeout) {
+                return Some(err);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::auth::BuiltinAuth;
+
+    fn bearer(token: &str) -> Arc<AuthProvider> {
+        Arc::new(AuthProvider::Builtin(BuiltinAuth::Bearer {
+            token: token.to_owned(),
+        }))
+    }
+
+    fn service_options() -> ServiceOptions {
+        ServiceOptions {
+            default_address: Some("https://service.example.com".parse().unwrap()),
+            verify_certs: true,
+            default_metadata: Arc::new(vec![metadata::Entry::new(
+                "x-service".to_owned(),
+                "1".to_owned(),
+            )]),
+            auth: Some(bearer("service")),
+            timeout_secs: Some(30),
+        }
+    }
+
+    #[test]
+    fn empty_override_keeps_service_options() {
+        let options = OptionsOverride::default().apply(&service_options());
+
+        assert_eq!(options.default_address, service_options().default_address);
+        assert!(options.verify_certs);
+        assert_eq!(options.timeout_secs, Some(30));
+        assert_eq!(options.default_metadata[0].key(), "x-service");
+        assert!(options.auth.is_some());
+    }
+
+    #[test]
+    fn override_replaces_set_fields() {
+        let overrides = OptionsOverride {
+            verify_certs: Some(false),
+            timeout_secs: Some(None),
+            auth: Some(None),
+            ..Default::default()
+        };
+        let options = overrides.apply(&service_options());
+
+        assert_eq!(options.default_address, service_options().default_address);
+        assert!(!options.verify_certs);
+        assert_eq!(options.timeout_secs, None);
+        assert_eq!(options.default_metadata[0].key(), "x-service");
+        assert!(options.auth.is_none());
+    }
+
+    #[test]
+    fn deserialize_full_options() {
+        // Requests saved by older versions have every field of the service options.
+        let overrides: OptionsOverride = serde_json::from_value(json!({
+            "default_address": null,
+            "verify_certs": false,
+            "default_metadata": [],
+            "auth": null
+        }))
+        .unwrap();
+
+        assert!(overrides.default_address.is_none());
+        assert_eq!(overrides.verify_certs, Some(false));
+        assert!(overrides.timeout_secs.is_none());
+        assert_eq!(overrides.default_metadata.as_deref().map(Vec::len), Some(0));
+        assert!(matches!(overrides.auth, Some(None)));
+    }
+
+    #[test]
+    fn serialize_only_set_fields() {
+        let overrides = OptionsOverride {
+            timeout_secs: Some(Some(5)),
+            auth: Some(None),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&overrides).unwrap(),
+            json!({ "timeout_secs": 5, "auth": null })
+        );
+        assert_eq!(
+            serde_json::to_value(OptionsOverride::default()).unwrap(),
+            json!({})
+        );
+    }
+}
@@ -1,28 +1,56 @@
 use std::sync::Arc;
 
+use anyhow::{Context, Result};
 use druid::{
     piet::TextStorage,
     widget::{prelude::*, Controller, CrossAxisAlignment, Flex},
-    Data, Insets, Lens, Point, Widget, WidgetExt as _, WidgetPod,
+    ArcStr, Data, Insets, Lens, Point, Widget, WidgetExt as _, WidgetPod,
 };
 use prost_reflect::{DynamicMessage, MessageDescriptor, ReflectMessage};
 use tonic::metadata::MetadataMap;
 
 use crate::{
-    app::metadata,
+    app::{
+        body::{
+            method::{assertion, capture},
+            Environment,
+        },
+        metadata,
+    },
     grpc,
     json::JsonText,
-    lens,
-    theme::BODY_SPACER,
+    lens, template,
+    theme::{BODY_SPACER, GRID_NARROW_SPACER},
     widget::{code_area, error_label, FormField, ValidationState, FINISH_EDIT},
 };
 
-type RequestValidationState = ValidationState<JsonText, grpc::Request>;
+/// The parsed request, or `None` if it contains placeholders which can only be expanded when it
+/// is sent.
+type RequestValidationState = ValidationState<JsonText, Option<grpc::Request>>;
 
 #[derive(Debug, Clone, Data, Lens)]
 pub(in crate::app) struct State {
     metadata: metadata::EditableState,
+    #[lens(name = "captures_lens")]
+    captures: capture::EditableState,
+    #[lens(name = "assertions_lens")]
+    assertions: assertion::EditableState,
     body: RequestValidationState,
+    #[data(ignore)]
+    #[lens(ignore)]
+    descriptor: MessageDescriptor,
+}
+
+/// A request ready to be sent, or a template whose placeholders must be expanded first.
+pub(in crate::app) enum Prepared {
+    Ready(grpc::Request, JsonText),
+    Template(Template),
+}
+
+#[derive(Debug)]
+pub(in crate::app) struct Template {
+    descriptor: MessageDescriptor,
+    text: String,
 }
 
 struct RequestController;
@@ -42,38 +70,66 @@ pub(in crate::app) fn build() -> impl Widget<State> {
         .with_flex_child(textbox, 1.0)
         .with_child(error);
     let metadata = metadata::build_editable();
+    let rules = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(capture::build().lens(State::captures_lens))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(assertion::build().lens(State::assertions_lens));
 
     RequestLayout {
         body: WidgetPod::new(body.boxed()),
         metadata: WidgetPod::new(metadata.boxed()),
+        rules: WidgetPod::new(rules.boxed()),
     }
 }
 
 struct RequestLayout {
     body: WidgetPod<RequestValidationState, Box<dyn Widget<RequestValidationState>>>,
     metadata: WidgetPod<metadata::EditableState, Box<dyn Widget<metadata::EditableState>>>,
+    /// The captures and assertions.
+    rules: WidgetPod<State, Box<dyn Widget<State>>>,
 }
 
 impl State {
     pub fn empty(request: prost_reflect::MessageDescriptor, metadata: metadata::State) -> Self {
         let json = make_template_message_json(request.clone());
-        State::with_text(request, json, metadata)
+        State::with_text(
+            request,
+            json,
+            metadata,
+            capture::State::default(),
+            assertion::State::default(),
+        )
     }
 
     pub fn with_text(
         request: prost_reflect::MessageDescriptor,
         json: impl Into<JsonText>,
         metadata: metadata::State,
+        captures: capture::State,
+        assertions: assertion::State,
     ) -> Self {
         State {
             metadata: metadata::EditableState::new(metadata),
+            captures: capture::EditableState::new(captures),
+            assertions: assertion::EditableState::new(assertions),
             body: ValidationState::dirty(
                 json.into(),
-                Arc::new(move |s| {
-                    grpc::Request::from_json(request.clone(), s.as_str())
-                        .map_err(|e| e.to_string().into())
+                Arc::new({
+                    let request = request.clone();
+                    move |s: &JsonText| -> Result<Option<grpc::Request>, ArcStr> {
+                        if template::has_placeholders(s.as_str()) {
+                            template::validate(s.as_str()).map_err(|e| e.to_string())?;
+                            return Ok(None);
+                        }
+
+                        grpc::Request::from_json(request.clone(), s.as_str())
+                            .map(Some)
+                            .map_err(|e| e.to_string().into())
+                    }
                 }),
             ),
+            descriptor: request,
         }
     }
 
@@ -81,20 +137,41 @@ impl State {
         self.body.is_valid() && self.metadata.is_valid()
     }
 
+    /// Gets the parsed request, if it does not contain any placeholders.
     pub(in crate::app) fn get(&self) -> Option<&grpc::Request> {
-        self.body.result().ok()
+        self.body.result().ok().and_then(Option::as_ref)
+    }
+
+    /// Gets the parsed request if it has no placeholders, or else a template to expand when it
+    /// is sent.
+    pub(in crate::app) fn prepare(&self) -> Prepared {
+        match self.body.result() {
+            Ok(Some(request)) => Prepared::Ready(request.clone(), self.body.text().clone()),
+            _ => Prepared::Template(Template {
+                descriptor: self.descriptor.clone(),
+                text: self.body.text().as_str().to_owned(),
+            }),
+        }
     }
 
-    pub(in crate::app) fn tonic_metadata(&self) -> MetadataMap {
-        self.metadata.metadata()
+    pub(in crate::app) fn tonic_metadata(&self, environment: &Environment) -> Result<MetadataMap> {
+        self.metadata.expand(environment)
     }
 
     pub(in crate::app) fn serde_metadata(&self) -> metadata::State {
         self.metadata.to_state()
     }
 
-    pub(in crate::app) fn get_json(&self) -> &JsonText {
-        self.body.text()
+    pub(in crate::app) fn captures(&self) -> &capture::EditableState {
+        &self.captures
+    }
+
+    pub(in crate::app) fn assertions(&self) -> &assertion::EditableState {
+        &self.assertions
+    }
+
+    pub(in crate::app) fn assertions_mut(&mut self) -> &mut assertion::EditableState {
+        &mut self.assertions
     }
 
     pub fn text(&self) -> &JsonText {
@@ -102,20 +179,37 @@ impl State {
     }
 }
 
+impl Template {
+    /// Expands the placeholders in the request, returning the message and its JSON text. Functions
+    /// such as `{{base64File path}}` read files, so this shouldn't be called on the UI thread.
+    pub(in crate::app) fn expand(
+        &self,
+        environment: &Environment,
+    ) -> Result<(grpc::Request, String)> {
+        let json = environment.expand_json(&self.text)?;
+        let request =
+            grpc::Request::from_json(self.descriptor.clone(), &json).context("invalid request")?;
+        Ok((request, json.into_owned()))
+    }
+}
+
 impl Widget<State> for RequestLayout {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut State, env: &Env) {
         self.body.event(ctx, event, &mut data.body, env);
         self.metadata.event(ctx, event, &mut data.metadata, env);
+        self.rules.event(ctx, event, data, env);
     }
 
     fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &State, env: &Env) {
         self.body.lifecycle(ctx, event, &data.body, env);
         self.metadata.lifecycle(ctx, event, &data.metadata, env);
+        self.rules.lifecycle(ctx, event, data, env);
     }
 
     fn update(&mut self, ctx: &mut UpdateCtx, _: &State, data: &State, env: &Env) {
         self.body.update(ctx, &data.body, env);
         self.metadata.update(ctx, &data.metadata, env);
+        self.rules.update(ctx, data, env);
     }
 
     fn layout(
@@ -130,18 +224,33 @@ impl Widget<State> for RequestLayout {
         } else {
             BODY_SPACER
         };
+        let rules_spacer = if data.captures.is_empty() && data.assertions.is_empty() {
+            0.0
+        } else {
+            BODY_SPACER
+        };
+
+        // Metadata, captures and assertions share at most half of the available height.
+        let max_height = (bc.max().height - body_spacer - rules_spacer).max(bc.min().height) / 2.0;
+
+        let rules_bc = BoxConstraints::new(
+            Size::new(bc.min().width, 0.0),
+            Size::new(bc.max().width, max_height / 2.0),
+        );
+        let rules_size = self.rules.layout(ctx, &rules_bc, data, env);
 
         let metadata_bc = BoxConstraints::new(
             Size::new(bc.min().width, 0.0),
-            Size::new(
-                bc.max().width,
-                (bc.max().height - body_spacer).max(bc.min().height) / 2.0,
-            ),
+            Size::new(bc.max().width, (max_height - rules_size.height).max(0.0)),
         );
         let metadata_size = self.metadata.layout(ctx, &metadata_bc, &data.metadata, env);
 
-        let remaining_height =
-            (bc.max().height - body_spacer - metadata_size.height).max(bc.min().height);
+        let remaining_height = (bc.max().height
+            - body_spacer
+            - metadata_size.height
+            - rules_spacer
+            - rules_size.height)
+            .max(bc.min().height);
         let body_bc = BoxConstraints::new(
             Size::new(bc.min().width, remaining_height),
             Size::new(bc.max().width, remaining_height),
@@ -151,16 +260,31 @@ impl Widget<State> for RequestLayout {
         self.body.set_origin(ctx, Point::ZERO);
         self.metadata
             .set_origin(ctx, Point::new(0.0, body_size.height + body_spacer));
+        self.rules.set_origin(
+            ctx,
+            Point::new(
+                0.0,
+                body_size.height + body_spacer + metadata_size.height + rules_spacer,
+            ),
+        );
 
         Size::new(
-            metadata_size.width.max(body_size.width),
-            metadata_size.height + body_size.height + body_spacer,
+            metadata_size
+                .width
+                .max(body_size.width)
+                .max(rules_size.width),
+            body_size.height
+                + body_spacer
+                + metadata_size.height
+                + rules_spacer
+                + rules_size.height,
         )
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &State, env: &Env) {
         self.body.paint(ctx, &data.body, env);
         self.metadata.paint(ctx, &data.metadata, env);
+        self.rules.paint(ctx, data, env);
     }
 }
 
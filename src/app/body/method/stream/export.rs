@@ -0,0 +1,201 @@
+use std::{io::Write, path::Path};
+
+use anyhow::Result;
+use prost_reflect::MethodDescriptor;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tonic::metadata::MetadataMap;
+
+use crate::{
+    app::{metadata, workspace},
+    error::fmt_err,
+};
+
+/// The details of a call in the request history. Its messages are kept in the history items, and
+/// are only combined with it when exporting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(in crate::app) struct Call {
+    #[serde(with = "time::serde::rfc3339")]
+    started_at: OffsetDateTime,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    finished_at: Option<OffsetDateTime>,
+    /// The method, in the form `package.Service/Method`.
+    method: String,
+    address: String,
+    request_metadata: Vec<Header>,
+    status: Option<Status>,
+    duration_ms: Option<f64>,
+}
+
+/// A call and its messages, exported as one line of a JSON Lines file.
+#[derive(Debug, Serialize)]
+pub(super) struct Record<'a> {
+    #[serde(flatten)]
+    call: &'a Call,
+    requests: Vec<Message>,
+    responses: Vec<Message>,
+    response_metadata: Vec<Header>,
+}
+
+#[derive(Debug, Serialize)]
+struct Message {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    timestamp: Option<OffsetDateTime>,
+    /// For responses, the time since the previous request was sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<f64>,
+    body: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Header {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Status {
+    code: i32,
+    name: String,
+    message: String,
+}
+
+impl Call {
+    /// Starts a record of a call. The metadata shouldn't include authorization headers, since
+    /// exports are meant to be shared.
+    pub fn new(method: &MethodDescriptor, address: String, metadata: &MetadataMap) -> Self {
+        Call {
+            started_at: OffsetDateTime::now_utc(),
+            finished_at: None,
+            method: format!("{}/{}", method.parent_service().full_name(), method.name()),
+            address,
+            request_metadata: headers(metadata),
+            status: None,
+            duration_ms: None,
+        }
+    }
+
+    pub fn request_metadata(&self) -> MetadataMap {
+        let mut metadata = http::HeaderMap::with_capacity(self.request_metadata.len());
+        for header in &self.request_metadata {
+            match (
+                http::header::HeaderName::from_bytes(header.name.as_bytes()),
+                http::HeaderValue::from_str(&header.value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    metadata.append(name, value);
+                }
+                _ => tracing::warn!("Ignoring invalid metadata '{}'", header.name),
+            }
+        }
+        MetadataMap::from_headers(metadata)
+    }
+
+    /// Clears the values of request metadata whose names suggest they hold secrets.
+    pub fn redact_secrets(&mut self) {
+        for header in &mut self.request_metadata {
+            if workspace::is_secret_name(&header.name) {
+                header.value.clear();
+            }
+        }
+    }
+
+    /// Records the end of the call, with the error it failed with, if any.
+    pub fn finish(&mut self, error: Option<&anyhow::Error>) {
+        let finished_at = OffsetDateTime::now_utc();
+        self.finished_at = Some(finished_at);
+        self.duration_ms = Some((finished_at - self.started_at).as_seconds_f64() * 1000.0);
+        self.status = Some(match error {
+            None => Status {
+                code: tonic::Code::Ok as i32,
+                name: format!("{:?}", tonic::Code::Ok),
+                message: String::new(),
+            },
+            Some(error) => {
+                let (code, message) = match error.downcast_ref::<tonic::Status>() {
+                    Some(status) => (status.code(), status.message().to_owned()),
+                    None => (tonic::Code::Unknown, fmt_err(error).to_string()),
+                };
+                Status {
+                    code: code as i32,
+                    name: format!("{:?}", code),
+                    message,
+                }
+            }
+        });
+    }
+}
+
+impl<'a> Record<'a> {
+    pub fn new(call: &'a Call) -> Self {
+        Record {
+            call,
+            requests: Vec::new(),
+            responses: Vec::new(),
+            response_metadata: Vec::new(),
+        }
+    }
+
+    pub fn add_request(&mut self, json: &str, timestamp: Option<OffsetDateTime>) {
+        self.requests.push(Message {
+            timestamp,
+            duration_ms: None,
+            body: body(json),
+        });
+    }
+
+    pub fn add_response(
+        &mut self,
+        json: &str,
+        timestamp: Option<OffsetDateTime>,
+        duration_ms: Option<f64>,
+    ) {
+        self.responses.push(Message {
+            timestamp,
+            duration_ms,
+            body: body(json),
+        });
+    }
+
+    pub fn add_metadata(&mut self, metadata: &metadata::State) {
+        self.response_metadata
+            .extend(metadata.iter().map(|entry| Header {
+                name: entry.key().to_owned(),
+                value: entry.value().to_owned(),
+            }));
+    }
+}
+
+/// Writes calls to a file in the JSON Lines format, one call per line.
+pub(super) fn write<'a, 'b: 'a>(
+    path: &Path,
+    records: impl IntoIterator<Item = &'a Record<'b>>,
+) -> Result<()> {
+    let mut writer = std::io::BufWriter::new(fs_err::File::create(path)?);
+    for record in records {
+        serde_json::to_writer(&mut writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn headers(metadata: &MetadataMap) -> Vec<Header> {
+    // Binary values are kept base64 encoded, as they are sent.
+    metadata
+        .clone()
+        .into_headers()
+        .iter()
+        .map(|(name, value)| Header {
+            name: name.as_str().to_owned(),
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        })
+        .collect()
+}
+
+fn body(json: &str) -> serde_json::Value {
+    serde_json::from_str(json).unwrap_or_else(|_| serde_json::Value::String(json.to_owned()))
+}
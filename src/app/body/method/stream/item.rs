@@ -1,9 +1,12 @@
-use std::mem::{self, Discriminant};
+use std::{
+    mem::{self, Discriminant},
+    sync::Arc,
+};
 
 use anyhow::Error;
 use druid::{
     lens::Field,
-    widget::{CrossAxisAlignment, Flex, Maybe, ViewSwitcher},
+    widget::{CrossAxisAlignment, Flex, Label, LineBreaking, List, Maybe, ViewSwitcher},
     Application, ArcStr, Data, Env, Insets, Lens, Widget, WidgetExt as _,
 };
 use prost_reflect::{DescriptorPool, DynamicMessage, Value};
@@ -11,13 +14,13 @@ use serde::{Deserialize, Serialize};
 use tonic::{metadata::MetadataMap, Status};
 
 use crate::{
-    app::metadata,
+    app::{body::method::assertion::AssertionResult, metadata},
     error::fmt_grpc_err,
     grpc,
     json::{self, JsonText},
     lens,
-    theme::INVALID,
-    widget::{code_area, empty, error_label},
+    theme::{self, GRID_NARROW_SPACER, INVALID},
+    widget::{code_area, empty, error_label, state_icon, StateIcon},
 };
 
 #[derive(Debug, Clone, Data, Serialize, Deserialize)]
@@ -26,6 +29,7 @@ pub(in crate::app) enum State {
     Payload(JsonText),
     Error(ErrorDetail),
     Metadata(metadata::State),
+    Assertions(Arc<Vec<AssertionResult>>),
 }
 
 #[derive(Debug, Clone, Lens, Data, Serialize, Deserialize)]
@@ -58,10 +62,39 @@ pub(in crate::app) fn build() -> impl Widget<State> {
                 .lens(State::error_lens())
                 .boxed(),
             State::Metadata(_) => metadata::build().lens(State::metadata_lens()).boxed(),
+            State::Assertions(_) => List::new(build_assertion)
+                .lens(State::assertions_lens())
+                .boxed(),
         },
     )
 }
 
+fn build_assertion() -> impl Widget<AssertionResult> {
+    let icon = state_icon((0.0, 0.0, GRID_NARROW_SPACER, 0.0)).lens(lens::Project::new(
+        |data: &AssertionResult| {
+            if data.error.is_none() {
+                StateIcon::Succeeded
+            } else {
+                StateIcon::Failed
+            }
+        },
+    ));
+
+    let label = Label::raw()
+        .with_font(theme::font::CODE)
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .lens(AssertionResult::assertion);
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(
+            Flex::row()
+                .with_child(icon)
+                .with_flex_child(label.expand_width(), 1.0),
+        )
+        .with_child(error_label((0.0, 0.0, 0.0, GRID_NARROW_SPACER)).lens(AssertionResult::error))
+}
+
 impl State {
     fn payload_lens() -> impl Lens<State, JsonText> {
         Field::new(
@@ -102,6 +135,19 @@ impl State {
         )
     }
 
+    fn assertions_lens() -> impl Lens<State, Arc<Vec<AssertionResult>>> {
+        Field::new(
+            |data| match data {
+                State::Assertions(assertions) => assertions,
+                _ => panic!("unexpected variant"),
+            },
+            |data| match data {
+                State::Assertions(assertions) => assertions,
+                _ => panic!("unexpected variant"),
+            },
+        )
+    }
+
     pub fn from_request(json: JsonText) -> Self {
         State::Payload(json)
     }
@@ -125,10 +171,37 @@ impl State {
         State::Metadata(metadata::state_from_tonic(metadata))
     }
 
+    pub fn from_assertions(results: Vec<AssertionResult>) -> State {
+        State::Assertions(Arc::new(results))
+    }
+
     pub fn can_copy(&self) -> bool {
         match self {
             State::Payload(_) | State::Error(_) => true,
-            State::Metadata(_) => false,
+            State::Metadata(_) | State::Assertions(_) => false,
+        }
+    }
+
+    /// The JSON of a request or response message.
+    pub fn payload(&self) -> Option<&str> {
+        match self {
+            State::Payload(payload) => Some(payload.original_data()),
+            State::Error(_) | State::Metadata(_) | State::Assertions(_) => None,
+        }
+    }
+
+    /// The entries of a metadata item.
+    pub fn metadata(&self) -> Option<&metadata::State> {
+        match self {
+            State::Metadata(metadata) => Some(metadata),
+            State::Payload(_) | State::Error(_) | State::Assertions(_) => None,
+        }
+    }
+
+    /// Clears the values of metadata whose names suggest they hold secrets.
+    pub fn redact_secrets(&mut self) {
+        if let State::Metadata(entries) = self {
+            metadata::redact_secrets(entries);
         }
     }
 
@@ -142,7 +215,7 @@ impl State {
                     err.message.as_ref()
                 }
             }
-            State::Metadata(_) => return,
+            State::Metadata(_) | State::Assertions(_) => return,
         };
 
         Application::global().clipboard().put_string(data);
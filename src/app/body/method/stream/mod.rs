@@ -1,29 +1,69 @@
+mod export;
 mod item;
 
-use std::time::Duration;
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::Error;
+use anyhow::{bail, Error, Result};
 use druid::{
     widget::{
         prelude::*, CrossAxisAlignment, Flex, Label, LineBreaking, List, MainAxisAlignment, Scroll,
     },
-    ArcStr, Data, Lens, WidgetExt,
+    ArcStr, Data, Lens, Selector, WidgetExt,
 };
 use prost_reflect::DescriptorPool;
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use tonic::metadata::MetadataMap;
 
+pub(in crate::app) use self::export::Call;
+
+use super::{assertion::AssertionResult, COPY_AS_GRPCURL};
 use crate::{
+    app::serde::StoredHistory,
     json::JsonText,
     theme,
     widget::{expander, ExpanderData, Icon},
 };
 
+/// Save a response as the golden snapshot for the tab's request. Submitted as a notification.
+pub(in crate::app) const SAVE_SNAPSHOT: Selector<String> =
+    Selector::new("app.body.method.stream.save-snapshot");
+
+/// Export the call at the given index, or the whole history, to a file. Submitted as a
+/// notification.
+pub(in crate::app) const EXPORT_HISTORY: Selector<Option<usize>> =
+    Selector::new("app.body.method.stream.export-history");
+
 #[derive(Debug, Clone, Data, Lens, Serialize, Deserialize)]
 pub struct State {
     items: im::Vector<ItemExpanderState>,
     response_count: usize,
     request_count: usize,
+    #[serde(default)]
+    #[data(same_fn = "PartialEq::eq")]
+    calls: im::Vector<Call>,
+    /// The file the items were loaded from, which new items are appended to when the config is
+    /// saved.
+    #[serde(skip)]
+    #[data(ignore)]
+    stored: Option<Arc<StoredHistory>>,
+}
+
+/// The state of a request history other than its items, which are stored in a separate file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(in crate::app) struct Summary {
+    response_count: usize,
+    request_count: usize,
+    #[serde(default, skip_serializing_if = "im::Vector::is_empty")]
+    calls: im::Vector<Call>,
+    /// The indices of the items which are expanded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    expanded: Vec<usize>,
 }
 
 #[derive(Debug, Clone, Data, Lens, Serialize, Deserialize)]
@@ -33,6 +73,16 @@ struct ItemExpanderState {
     expanded: bool,
     data: item::State,
     duration: ArcStr,
+    /// The index of the call a request, response or metadata belongs to.
+    #[serde(default)]
+    call: Option<usize>,
+    /// When a request was sent or a response was received.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[data(same_fn = "PartialEq::eq")]
+    timestamp: Option<OffsetDateTime>,
+    /// For responses, the time since the previous request was sent.
+    #[serde(default)]
+    duration_ms: Option<f64>,
 }
 
 #[derive(Debug, Clone, Data, Serialize, Deserialize, PartialEq, Eq)]
@@ -40,6 +90,7 @@ enum ItemKind {
     Request,
     Response,
     Metadata,
+    Assertions,
 }
 
 pub fn build() -> impl Widget<State> {
@@ -57,6 +108,10 @@ pub fn build_header() -> impl Widget<State> {
                 .expand_width(),
             1.0,
         )
+        .with_child(
+            Icon::download()
+                .button(|ctx, _: &mut State, _| ctx.submit_notification(EXPORT_HISTORY.with(None))),
+        )
         .with_child(Icon::close().button(|_, data: &mut State, _| data.clear()))
 }
 
@@ -89,6 +144,140 @@ impl State {
             items: im::Vector::new(),
             response_count: 0,
             request_count: 0,
+            calls: im::Vector::new(),
+            stored: None,
+        }
+    }
+
+    /// Restores a history from its summary and its items, read as JSON Lines.
+    pub fn from_parts(summary: Summary, items: impl BufRead) -> Result<Self> {
+        let mut state = State {
+            items: im::Vector::new(),
+            response_count: summary.response_count,
+            request_count: summary.request_count,
+            calls: summary.calls,
+            stored: None,
+        };
+        for (index, line) in items.lines().enumerate() {
+            let mut item: ItemExpanderState = serde_json::from_str(&line?)?;
+            item.expanded = summary.expanded.contains(&index);
+            state.items.push_back(item);
+        }
+        Ok(state)
+    }
+
+    /// Gets the state other than the items.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            response_count: self.response_count,
+            request_count: self.request_count,
+            calls: self.calls.clone(),
+            expanded: self
+                .items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.expanded)
+                .map(|(index, _)| index)
+                .collect(),
+        }
+    }
+
+    /// Writes the items after the first `start` as JSON Lines.
+    pub fn write_items(&self, start: usize, mut writer: impl Write) -> Result<()> {
+        for item in self.items.iter().skip(start) {
+            serde_json::to_writer(&mut writer, item)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub fn collapse_all(&mut self) {
+        for item in self.items.iter_mut() {
+            item.expanded = false;
+        }
+    }
+
+    pub fn item_count(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the history begins with all the items of `prev`, ignoring whether they are
+    /// expanded, so that it can be stored by appending the rest.
+    pub fn starts_with(&self, prev: &State) -> bool {
+        prev.items.len() <= self.items.len()
+            && self
+                .items
+                .iter()
+                .zip(&prev.items)
+                .all(|(item, prev)| item.same_contents(prev))
+    }
+
+    /// Gets the file the items were loaded from, if any.
+    pub fn stored(&self) -> Option<&Arc<StoredHistory>> {
+        self.stored.as_ref()
+    }
+
+    pub fn set_stored(&mut self, stored: Arc<StoredHistory>) {
+        self.stored = Some(stored);
+    }
+
+    /// Starts recording a new call. Requests and responses added afterwards belong to it.
+    pub fn start_call(&mut self, call: Call) {
+        self.calls.push_back(call);
+    }
+
+    /// Records that the current call completed successfully.
+    pub fn finish_call(&mut self) {
+        if let Some(call) = self.calls.back_mut() {
+            call.finish(None);
+        }
+    }
+
+    /// Writes the call at the given index, or all calls if `None`, to a JSON Lines file.
+    pub fn export(&self, path: &Path, index: Option<usize>) -> Result<()> {
+        let records = self.records();
+        match index {
+            Some(index) => match records.get(index) {
+                Some(record) => export::write(path, [record]),
+                None => bail!("the call is no longer in the request history"),
+            },
+            None if records.is_empty() => bail!("the request history is empty"),
+            None => export::write(path, &records),
+        }
+    }
+
+    /// Combines each call with the requests, responses and metadata recorded for it.
+    fn records(&self) -> Vec<export::Record<'_>> {
+        let mut records: Vec<_> = self.calls.iter().map(export::Record::new).collect();
+        for item in &self.items {
+            let Some(record) = item.call.and_then(|call| records.get_mut(call)) else {
+                continue;
+            };
+
+            match (&item.kind, item.data.payload(), item.data.metadata()) {
+                (ItemKind::Request, Some(json), _) => record.add_request(json, item.timestamp),
+                (ItemKind::Response, Some(json), _) => {
+                    record.add_response(json, item.timestamp, item.duration_ms)
+                }
+                (ItemKind::Metadata, _, Some(metadata)) => record.add_metadata(metadata),
+                _ => (),
+            }
+        }
+        records
+    }
+
+    /// Gets the metadata sent with the call at the given index, without authorization headers.
+    pub fn request_metadata(&self, index: usize) -> Option<MetadataMap> {
+        self.calls.get(index).map(Call::request_metadata)
+    }
+
+    /// Clears the values of metadata whose names suggest they hold secrets.
+    pub fn redact_secrets(&mut self) {
+        for call in self.calls.iter_mut() {
+            call.redact_secrets();
+        }
+        for item in self.items.iter_mut() {
+            item.data.redact_secrets();
         }
     }
 
@@ -105,6 +294,9 @@ impl State {
             data: item::State::from_request(request),
             kind: ItemKind::Request,
             duration: ArcStr::from(""),
+            call: self.calls.len().checked_sub(1),
+            timestamp: Some(OffsetDateTime::now_utc()),
+            duration_ms: None,
         });
     }
 
@@ -118,6 +310,10 @@ impl State {
             item.expanded = false;
         }
 
+        if let (Some(call), Err(err)) = (self.calls.back_mut(), &result) {
+            call.finish(Some(err));
+        }
+
         self.response_count += 1;
         let name = ArcStr::from(format!("Response {}", self.response_count));
         self.items.push_back(ItemExpanderState {
@@ -126,6 +322,9 @@ impl State {
             data: item::State::from_response(pool, result),
             kind: ItemKind::Response,
             duration: duration.map(format_duration).unwrap_or_default().into(),
+            call: self.calls.len().checked_sub(1),
+            timestamp: Some(OffsetDateTime::now_utc()),
+            duration_ms: duration.map(|duration| duration.as_secs_f64() * 1000.0),
         });
     }
 
@@ -137,6 +336,31 @@ impl State {
             data: item::State::from_metadata(metadata),
             kind: ItemKind::Metadata,
             duration: ArcStr::from(""),
+            call: self.calls.len().checked_sub(1),
+            timestamp: None,
+            duration_ms: None,
+        });
+    }
+
+    pub fn add_assertions(&mut self, results: Vec<AssertionResult>) {
+        let failed = results
+            .iter()
+            .filter(|result| result.error.is_some())
+            .count();
+        let name = if failed == 0 {
+            ArcStr::from("Assertions passed")
+        } else {
+            ArcStr::from(format!("{} of {} assertions failed", failed, results.len()))
+        };
+        self.items.push_back(ItemExpanderState {
+            label: name,
+            expanded: failed != 0,
+            data: item::State::from_assertions(results),
+            kind: ItemKind::Assertions,
+            duration: ArcStr::from(""),
+            call: None,
+            timestamp: None,
+            duration_ms: None,
         });
     }
 
@@ -144,6 +368,7 @@ impl State {
         self.items.clear();
         self.request_count = 0;
         self.response_count = 0;
+        self.calls.clear();
     }
 }
 
@@ -153,18 +378,61 @@ impl Default for State {
     }
 }
 
+impl ItemExpanderState {
+    fn same_contents(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.label.same(&other.label)
+            && self.data.same(&other.data)
+            && self.duration.same(&other.duration)
+            && self.call == other.call
+            && self.timestamp == other.timestamp
+            && self.duration_ms == other.duration_ms
+    }
+}
+
 impl ExpanderData for ItemExpanderState {
     fn buttons(&self) -> Vec<(Icon, Box<dyn FnMut(&mut EventCtx, &mut Self, &Env)>)> {
+        let mut buttons: Vec<(Icon, Box<dyn FnMut(&mut EventCtx, &mut Self, &Env)>)> = vec![];
+
+        if self.kind == ItemKind::Response && self.data.payload().is_some() {
+            let save_snapshot: Box<dyn FnMut(&mut EventCtx, &mut ItemExpanderState, &Env)> =
+                Box::new(move |ctx, data, _| {
+                    if let Some(json) = data.data.payload() {
+                        ctx.submit_notification(SAVE_SNAPSHOT.with(json.to_owned()));
+                    }
+                });
+            buttons.push((Icon::star().with_size((18.0, 18.0)), save_snapshot));
+        }
+
+        if self.kind == ItemKind::Request && self.data.payload().is_some() {
+            let copy_as_grpcurl: Box<dyn FnMut(&mut EventCtx, &mut ItemExpanderState, &Env)> =
+                Box::new(move |ctx, data, _| {
+                    if let Some(json) = data.data.payload() {
+                        ctx.submit_notification(
+                            COPY_AS_GRPCURL.with(Some((json.to_owned(), data.call))),
+                        );
+                    }
+                });
+            buttons.push((Icon::terminal().with_size((18.0, 18.0)), copy_as_grpcurl));
+        }
+
+        if let (ItemKind::Request, Some(call)) = (&self.kind, self.call) {
+            let export_call: Box<dyn FnMut(&mut EventCtx, &mut ItemExpanderState, &Env)> =
+                Box::new(move |ctx, _, _| {
+                    ctx.submit_notification(EXPORT_HISTORY.with(Some(call)));
+                });
+            buttons.push((Icon::download().with_size((18.0, 18.0)), export_call));
+        }
+
         if self.data.can_copy() {
             let copy_item: Box<dyn FnMut(&mut EventCtx, &mut ItemExpanderState, &Env)> =
                 Box::new(move |_, data, _| {
                     data.data.set_clipboard();
                 });
-
-            vec![(Icon::copy().with_size((18.0, 18.0)), copy_item)]
-        } else {
-            vec![]
+            buttons.push((Icon::copy().with_size((18.0, 18.0)), copy_item));
         }
+
+        buttons
     }
 
     fn expanded(&self, _: &Env) -> bool {
@@ -176,7 +444,7 @@ impl ExpanderData for ItemExpanderState {
     }
 }
 
-fn format_duration(duration: Duration) -> String {
+pub(in crate::app) fn format_duration(duration: Duration) -> String {
     fn precision(f: f64) -> usize {
         2 - f.log10().floor().min(2.0) as usize
     }
@@ -189,3 +457,180 @@ fn format_duration(duration: Duration) -> String {
         format!("{:.*} ms", precision(millis), millis)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use prost_reflect::MethodDescriptor;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn history(requests: &[&str]) -> State {
+        let mut state = State::new();
+        for request in requests {
+            state.add_request(JsonText::short(request.to_string()));
+        }
+        state
+    }
+
+    #[test]
+    fn items_round_trip() {
+        let state = history(&[r#"{"a":1}"#, r#"{"b":2}"#]);
+        let mut items = Vec::new();
+        state.write_items(0, &mut items).unwrap();
+
+        let loaded = State::from_parts(state.summary(), items.as_slice()).unwrap();
+        assert_eq!(loaded.item_count(), 2);
+        assert_eq!(loaded.request_count, 2);
+        assert_eq!(&*loaded.items[1].label, "Request 2");
+        assert!(!loaded.items[0].expanded);
+        assert!(loaded.items[1].expanded);
+    }
+
+    #[test]
+    fn write_items_after_start() {
+        let state = history(&[r#"{"a":1}"#, r#"{"b":2}"#]);
+        let mut items = Vec::new();
+        state.write_items(1, &mut items).unwrap();
+
+        assert_eq!(items.iter().filter(|&&byte| byte == b'\n').count(), 1);
+    }
+
+    #[test]
+    fn starts_with_previous_items() {
+        let prev = history(&[r#"{"a":1}"#]);
+        let mut next = prev.clone();
+        next.add_request(JsonText::short(r#"{"b":2}"#.to_owned()));
+        assert!(next.starts_with(&prev));
+        assert!(!prev.starts_with(&next));
+
+        next.clear();
+        next.add_request(JsonText::short(r#"{"c":3}"#.to_owned()));
+        assert!(!next.starts_with(&prev));
+    }
+
+    fn method() -> MethodDescriptor {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/snippet/testdata/shop.proto");
+        let file = crate::protoc::load_file(&path, &[]).unwrap();
+        let service = file.services().next().unwrap();
+        service.methods().next().unwrap()
+    }
+
+    #[test]
+    fn export_call() {
+        let method = method();
+        let pool = method.parent_pool().clone();
+
+        let mut state = State::new();
+        let mut request_metadata = MetadataMap::new();
+        request_metadata.insert("x-call", "first".parse().unwrap());
+        state.start_call(Call::new(
+            &method,
+            "http://localhost:50051".to_owned(),
+            &request_metadata,
+        ));
+        state.add_request(JsonText::short(r#"{"id":"o-1"}"#.to_owned()));
+        state.add_response(
+            &pool,
+            Ok(JsonText::short(r#"{"id":"o-1"}"#.to_owned())),
+            Some(Duration::from_millis(5)),
+        );
+        state.finish_call();
+
+        let mut request_metadata = MetadataMap::new();
+        request_metadata.insert("x-call", "second".parse().unwrap());
+        state.start_call(Call::new(
+            &method,
+            "http://localhost:50051".to_owned(),
+            &request_metadata,
+        ));
+        state.add_request(JsonText::short(r#"{"id":"o-2"}"#.to_owned()));
+        let mut response_metadata = MetadataMap::new();
+        response_metadata.insert("x-trace-id", "abc".parse().unwrap());
+        state.add_metadata(response_metadata);
+        state.add_response(
+            &pool,
+            Err(tonic::Status::not_found("no order 'o-2'").into()),
+            Some(Duration::from_millis(7)),
+        );
+
+        let path = env::temp_dir().join(format!("export-test-{}.jsonl", std::process::id()));
+        state.export(&path, Some(1)).unwrap();
+        let text = fs_err::read_to_string(&path).unwrap();
+        fs_err::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1, "{}", text);
+        let record: Value = serde_json::from_str(lines[0]).unwrap();
+
+        assert_eq!(record["method"], "shop.Orders/Create");
+        assert_eq!(record["address"], "http://localhost:50051");
+        assert_eq!(
+            record["request_metadata"],
+            json!([{ "name": "x-call", "value": "second" }])
+        );
+        assert_eq!(record["requests"].as_array().unwrap().len(), 1);
+        assert_eq!(record["requests"][0]["body"], json!({ "id": "o-2" }));
+        assert!(record["requests"][0]["timestamp"].is_string());
+        // The error is recorded as the call's status rather than as a response.
+        assert_eq!(record["responses"], json!([]));
+        assert_eq!(
+            record["response_metadata"],
+            json!([{ "name": "x-trace-id", "value": "abc" }])
+        );
+        assert_eq!(
+            record["status"],
+            json!({ "code": 5, "name": "NotFound", "message": "no order 'o-2'" })
+        );
+        assert!(record["started_at"].is_string());
+        assert!(record["finished_at"].is_string());
+        assert!(record["duration_ms"].is_number());
+    }
+
+    #[test]
+    fn export_all_calls() {
+        let method = method();
+        let pool = method.parent_pool().clone();
+
+        let mut state = State::new();
+        for id in ["o-1", "o-2"] {
+            let body = format!(r#"{{"id":"{}"}}"#, id);
+            state.start_call(Call::new(
+                &method,
+                "http://localhost:50051".to_owned(),
+                &MetadataMap::new(),
+            ));
+            state.add_request(JsonText::short(body.clone()));
+            state.add_response(
+                &pool,
+                Ok(JsonText::short(body)),
+                Some(Duration::from_millis(5)),
+            );
+            state.finish_call();
+        }
+
+        let path = env::temp_dir().join(format!("export-all-test-{}.jsonl", std::process::id()));
+        state.export(&path, None).unwrap();
+        let text = fs_err::read_to_string(&path).unwrap();
+        fs_err::remove_file(&path).unwrap();
+
+        let records: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        for (record, id) in records.iter().zip(["o-1", "o-2"]) {
+            assert_eq!(record["requests"].as_array().unwrap().len(), 1);
+            assert_eq!(record["requests"][0]["body"], json!({ "id": id }));
+            assert_eq!(record["responses"].as_array().unwrap().len(), 1);
+            assert_eq!(record["responses"][0]["body"], json!({ "id": id }));
+            let duration_ms = record["responses"][0]["duration_ms"].as_f64().unwrap();
+            assert!((duration_ms - 5.0).abs() < 1e-6, "{}", duration_ms);
+            assert_eq!(record["status"]["name"], "Ok");
+        }
+
+        assert!(state.export(&path, Some(2)).is_err());
+    }
+}
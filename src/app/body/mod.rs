@@ -1,24 +1,47 @@
 mod address;
+mod collection;
 mod compile;
+mod environment;
 mod method;
 mod options;
 mod reflection;
 
-pub(in crate::app) use self::{compile::CompileOptions, method::StreamState};
+pub(in crate::app) use self::{
+    address::Address,
+    collection::{runner, Collection, Collections, RunId, START_RUN as START_COLLECTION_RUN},
+    compile::CompileOptions,
+    environment::{Environment, Environments},
+    method::{
+        format_duration, AssertionState, CaptureState, OptionsOverride, SavedRequest, StreamState,
+        StreamSummary,
+    },
+};
 
-use std::{collections::BTreeMap, mem, ops::Bound, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    mem,
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use druid::{lens::Field, widget::ViewSwitcher, ArcStr, Data, Lens, Widget, WidgetExt as _};
 use iter_set::Inclusion;
 use prost_reflect::{MethodDescriptor, ServiceDescriptor};
 
 use self::{
-    compile::CompileTabState, method::MethodTabState, options::OptionsTabState,
-    reflection::ReflectionTabState,
+    collection::CollectionTabState,
+    compile::CompileTabState,
+    environment::EnvironmentTabState,
+    method::MethodTabState,
+    options::OptionsTabState,
+    reflection::{ReflectionTabState, ServicePreset},
 };
 use crate::{
-    app::{command, metadata, sidebar::service::ServiceOptions},
-    json::JsonText,
+    app::{
+        command,
+        sidebar::service::{ServiceOptions, ServiceState},
+    },
     widget::{tabs, TabId, TabLabelState, TabsData, TabsDataChange},
 };
 
@@ -26,6 +49,8 @@ use crate::{
 pub(in crate::app) struct State {
     tabs: Arc<BTreeMap<TabId, TabState>>,
     selected: Option<TabId>,
+    /// The open workspace file, which is passed to new method tabs.
+    workspace: Option<Arc<PathBuf>>,
 }
 
 #[derive(Debug, Clone, Data)]
@@ -35,6 +60,7 @@ pub enum RequestState {
     Connected,
     ConnectFailed(ArcStr),
     AuthorizationHookFailed(ArcStr),
+    SendFailed(ArcStr),
     SendInProgress,
     AuthorizationHookInProgress,
 }
@@ -45,6 +71,8 @@ pub enum TabState {
     Method(MethodTabState),
     Options(OptionsTabState),
     Compile(CompileTabState),
+    Environment(EnvironmentTabState),
+    Collection(CollectionTabState),
     Reflection(ReflectionTabState),
 }
 
@@ -60,6 +88,12 @@ pub(in crate::app) fn build() -> impl Widget<State> {
                 TabState::Compile(_) => {
                     compile::build_body().lens(TabState::compile_lens()).boxed()
                 }
+                TabState::Environment(_) => environment::build_body()
+                    .lens(TabState::environment_lens())
+                    .boxed(),
+                TabState::Collection(_) => collection::build_body()
+                    .lens(TabState::collection_lens())
+                    .boxed(),
                 TabState::Reflection(_) => reflection::build_body()
                     .lens(TabState::reflection_lens())
                     .boxed(),
@@ -73,6 +107,7 @@ impl State {
         State {
             tabs: Arc::new(tabs),
             selected,
+            workspace: None,
         }
     }
 
@@ -89,6 +124,32 @@ impl State {
         Arc::make_mut(&mut self.tabs).insert(id, TabState::new_compile(compile_options));
     }
 
+    pub fn select_or_create_environment_tab(&mut self, environments: &Environments) {
+        for (&id, tab) in self.tabs.iter() {
+            if matches!(tab, TabState::Environment(_)) {
+                self.selected = Some(id);
+                return;
+            }
+        }
+
+        let id = TabId::next();
+        self.selected = Some(id);
+        Arc::make_mut(&mut self.tabs).insert(id, TabState::new_environment(environments));
+    }
+
+    pub fn select_or_create_collection_tab(&mut self, collections: &Collections) {
+        for (&id, tab) in self.tabs.iter() {
+            if matches!(tab, TabState::Collection(_)) {
+                self.selected = Some(id);
+                return;
+            }
+        }
+
+        let id = TabId::next();
+        self.selected = Some(id);
+        Arc::make_mut(&mut self.tabs).insert(id, TabState::new_collection(collections));
+    }
+
     pub fn select_or_create_reflection_tab(&mut self) {
         for (&id, tab) in self.tabs.iter() {
             if matches!(tab, TabState::Reflection(_)) {
@@ -124,6 +185,29 @@ impl State {
         self.create_method_tab(method, options)
     }
 
+    pub fn select_or_create_saved_request_tab(
+        &mut self,
+        method: &MethodDescriptor,
+        request: &SavedRequest,
+        options: ServiceOptions,
+    ) {
+        for (&id, tab) in self.tabs.iter() {
+            if let TabState::Method(data) = tab {
+                if data.method() == method && data.name() == request.name.as_str() {
+                    self.selected = Some(id);
+                    return;
+                }
+            }
+        }
+
+        self.insert_method_tab(MethodTabState::new(
+            method.clone(),
+            request.clone(),
+            StreamState::new(),
+            options,
+        ));
+    }
+
     pub fn select_or_create_options_tab(
         &mut self,
         service: &ServiceDescriptor,
@@ -149,9 +233,29 @@ impl State {
     }
 
     pub fn create_method_tab(&mut self, method: &MethodDescriptor, options: ServiceOptions) {
+        self.insert_method_tab(MethodTabState::empty(method.clone(), options));
+    }
+
+    /// Creates a new tab with the given request, even if it is already open.
+    pub fn create_request_tab(
+        &mut self,
+        method: &MethodDescriptor,
+        request: &SavedRequest,
+        options: ServiceOptions,
+    ) {
+        self.insert_method_tab(MethodTabState::new(
+            method.clone(),
+            request.clone(),
+            StreamState::new(),
+            options,
+        ));
+    }
+
+    fn insert_method_tab(&mut self, mut tab: MethodTabState) {
+        tab.set_workspace(self.workspace.clone());
         let id = TabId::next();
         self.selected = Some(id);
-        Arc::make_mut(&mut self.tabs).insert(id, TabState::empty_method(method.clone(), options));
+        Arc::make_mut(&mut self.tabs).insert(id, TabState::Method(tab));
     }
 
     pub fn create_options_tab(&mut self, service: &ServiceDescriptor, options: &ServiceOptions) {
@@ -174,6 +278,8 @@ impl State {
             TabState::Method(method) => method.method().parent_service() != service,
             TabState::Options(options) => options.service() != service,
             TabState::Compile(_) => true,
+            TabState::Environment(_) => true,
+            TabState::Collection(_) => true,
             TabState::Reflection(_) => true,
         });
         self.update_selected_after_remove();
@@ -301,25 +407,86 @@ impl State {
                 }
             }
             TabState::Compile(_) => (),
+            TabState::Environment(_) => (),
+            TabState::Collection(_) => (),
             TabState::Reflection(_) => (),
         })
     }
 
+    pub fn set_environment(&mut self, environment: &Environment) {
+        self.for_each_mut(|_, tab| match tab {
+            TabState::Method(tab) => tab.set_environment(environment.clone()),
+            TabState::Options(tab) => tab.set_environment(environment.clone()),
+            TabState::Reflection(tab) => tab.set_environment(environment.clone()),
+            TabState::Compile(_) => (),
+            TabState::Environment(_) => (),
+            TabState::Collection(_) => (),
+        })
+    }
+
+    /// Sets the open workspace file, which snapshots are stored next to.
+    pub fn set_workspace(&mut self, workspace: Option<&Path>) {
+        self.workspace = workspace.map(|path| Arc::new(path.to_owned()));
+        let workspace = self.workspace.clone();
+        self.for_each_mut(|_, tab| {
+            if let TabState::Method(tab) = tab {
+                tab.set_workspace(workspace.clone());
+            }
+        })
+    }
+
+    pub fn set_captured_variables(&mut self, environments: &Environments) {
+        self.for_each_mut(|_, tab| {
+            if let TabState::Environment(tab) = tab {
+                tab.set_captured(environments);
+            }
+        })
+    }
+
+    pub fn set_service_presets(&mut self, services: &im::Vector<ServiceState>) {
+        let presets: Arc<Vec<_>> = Arc::new(services.iter().map(ServicePreset::new).collect());
+        self.for_each_mut(|_, tab| {
+            if let TabState::Reflection(tab) = tab {
+                tab.set_presets(presets.clone());
+            }
+        })
+    }
+
     pub fn can_connect(&self) -> bool {
         self.with_selected(|_, tab| match tab {
             TabState::Method(tab) => tab.can_connect(),
             TabState::Options(tab) => tab.can_connect(),
             TabState::Compile(_) => false,
+            TabState::Environment(_) => false,
+            TabState::Collection(_) => false,
             TabState::Reflection(_) => false,
         })
         .unwrap_or(false)
     }
 
+    pub fn selected_saved_request(&self) -> Option<(MethodDescriptor, SavedRequest)> {
+        self.with_selected_method(|_, tab| {
+            if tab.can_save() {
+                Some((tab.method().clone(), tab.saved_request()))
+            } else {
+                None
+            }
+        })
+        .flatten()
+    }
+
+    pub fn can_save(&self) -> bool {
+        self.with_selected_method(|_, tab| tab.can_save())
+            .unwrap_or(false)
+    }
+
     pub fn can_send(&self) -> bool {
         self.with_selected(|_, tab| match tab {
             TabState::Method(tab) => tab.can_send(),
             TabState::Options(_) => false,
             TabState::Compile(_) => false,
+            TabState::Environment(_) => false,
+            TabState::Collection(_) => false,
             TabState::Reflection(tab) => tab.can_send(),
         })
         .unwrap_or(false)
@@ -330,6 +497,8 @@ impl State {
             TabState::Method(tab) => tab.can_finish(),
             TabState::Options(_) => false,
             TabState::Compile(_) => false,
+            TabState::Environment(_) => false,
+            TabState::Collection(_) => false,
             TabState::Reflection(_) => false,
         })
         .unwrap_or(false)
@@ -340,6 +509,8 @@ impl State {
             TabState::Method(tab) => tab.can_disconnect(),
             TabState::Options(tab) => tab.can_disconnect(),
             TabState::Compile(_) => false,
+            TabState::Environment(_) => false,
+            TabState::Collection(_) => false,
             TabState::Reflection(_) => false,
         })
         .unwrap_or(false)
@@ -347,26 +518,13 @@ impl State {
 }
 
 impl TabState {
-    fn empty_method(method: prost_reflect::MethodDescriptor, options: ServiceOptions) -> TabState {
-        TabState::Method(MethodTabState::empty(method, options))
-    }
-
     pub fn new_method(
         method: MethodDescriptor,
-        address: String,
-        request: JsonText,
-        request_metadata: metadata::State,
+        saved: SavedRequest,
         stream: StreamState,
         service_options: ServiceOptions,
     ) -> Self {
-        TabState::Method(MethodTabState::new(
-            method,
-            address,
-            request,
-            request_metadata,
-            stream,
-            service_options,
-        ))
+        TabState::Method(MethodTabState::new(method, saved, stream, service_options))
     }
 
     pub fn new_options(service: ServiceDescriptor, options: ServiceOptions) -> TabState {
@@ -377,6 +535,14 @@ impl TabState {
         TabState::Compile(CompileTabState::new(options))
     }
 
+    pub fn new_environment(environments: &Environments) -> TabState {
+        TabState::Environment(EnvironmentTabState::new(environments))
+    }
+
+    pub fn new_collection(collections: &Collections) -> TabState {
+        TabState::Collection(CollectionTabState::new(collections))
+    }
+
     pub fn new_reflection(options: ServiceOptions) -> TabState {
         TabState::Reflection(ReflectionTabState::new(options))
     }
@@ -387,9 +553,11 @@ impl TabState {
 
     pub fn label(&self) -> ArcStr {
         match self {
-            TabState::Method(method) => method.method().name().into(),
+            TabState::Method(method) => method.label(),
             TabState::Options(options) => options.label(),
             TabState::Compile(_) => ArcStr::from("Compiler options"),
+            TabState::Environment(_) => ArcStr::from("Environments"),
+            TabState::Collection(_) => ArcStr::from("Collections"),
             TabState::Reflection(_) => ArcStr::from("Server reflection"),
         }
     }
@@ -433,6 +601,32 @@ impl TabState {
         )
     }
 
+    fn environment_lens() -> impl Lens<TabState, EnvironmentTabState> {
+        Field::new(
+            |data| match data {
+                TabState::Environment(environment) => environment,
+                _ => panic!("expected environment data"),
+            },
+            |data| match data {
+                TabState::Environment(environment) => environment,
+                _ => panic!("expected environment data"),
+            },
+        )
+    }
+
+    fn collection_lens() -> impl Lens<TabState, CollectionTabState> {
+        Field::new(
+            |data| match data {
+                TabState::Collection(collection) => collection,
+                _ => panic!("expected collection data"),
+            },
+            |data| match data {
+                TabState::Collection(collection) => collection,
+                _ => panic!("expected collection data"),
+            },
+        )
+    }
+
     fn reflection_lens() -> impl Lens<TabState, ReflectionTabState> {
         Field::new(
             |data| match data {
@@ -560,5 +754,9 @@ impl TabsData for State {
             && !cmd.is(command::DISCONNECT)
             && !cmd.is(command::SEND)
             && !cmd.is(command::FINISH)
+            && !cmd.is(command::CANCEL_AUTHORIZATION)
+            && !cmd.is(command::COPY_SNIPPET)
+            && !cmd.is(command::EXPORT_HISTORY)
+            && !cmd.is(command::EXPORT_HISTORY_ACCEPT)
     }
 }
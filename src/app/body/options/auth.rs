@@ -1,19 +1,27 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
 use druid::{
-    widget::{prelude::*, Button, Controller, CrossAxisAlignment, Flex},
+    lens::Map,
+    widget::{
+        prelude::*, Button, Checkbox, Controller, CrossAxisAlignment, Either, Flex, Label,
+        LineBreaking, RadioGroup, ViewSwitcher,
+    },
     ArcStr, Command, Handled, Insets, Lens, Selector, WidgetExt,
 };
 use once_cell::sync::Lazy;
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
 
 use crate::{
-    auth::AuthorizationHook,
+    auth::{
+        AuthProvider, AuthorizationHook, BuiltinAuth, CallContext, HookMode, HookOptions,
+        JwtConfig, OAuth2Config, OAuth2Grant, OAuth2Provider,
+    },
     error::fmt_err,
     lens,
-    theme::{self, BODY_PADDING},
+    theme::{self, BODY_PADDING, GRID_NARROW_SPACER},
     widget::{
-        error_label, input, state_icon,
+        empty, error_label, input, state_icon,
         update_queue::{self, UpdateQueue},
         FormField, StateIcon, ValidationFn, ValidationState,
     },
@@ -21,8 +29,53 @@ use crate::{
 
 #[derive(Debug, Data, Clone, Lens)]
 pub struct State {
-    command: CommandValidationState,
+    form: FormValidationState,
     execute_state: ExecuteState,
+    /// Output written to stderr by the hook while it is being tested.
+    stderr: Arc<String>,
+}
+
+#[derive(Debug, Default, Data, Clone, PartialEq, Lens)]
+pub struct AuthForm {
+    kind: AuthKind,
+    shell: String,
+    persistent: bool,
+    timeout: String,
+    refresh_margin: String,
+    token: String,
+    username: String,
+    password: String,
+    header: String,
+    key: String,
+    key_file: String,
+    claims: String,
+    lifetime: String,
+    key_id: String,
+    token_url: String,
+    grant: GrantKind,
+    client_id: String,
+    client_secret: String,
+    scope: String,
+    refresh_token: String,
+}
+
+#[derive(Debug, Default, Copy, Clone, Data, PartialEq, Eq)]
+pub enum AuthKind {
+    #[default]
+    None,
+    Hook,
+    Bearer,
+    Basic,
+    ApiKey,
+    Jwt,
+    OAuth2,
+}
+
+#[derive(Debug, Default, Copy, Clone, Data, PartialEq, Eq)]
+pub enum GrantKind {
+    #[default]
+    ClientCredentials,
+    RefreshToken,
 }
 
 #[derive(Debug, Data, Clone)]
@@ -36,39 +89,103 @@ pub enum ExecuteState {
 const START_TEST: Selector = Selector::new("app.body.options.auth.start-test");
 const CANCEL_TEST: Selector = Selector::new("app.body.options.auth.cancel-test");
 
+const FIELD_LABEL_WIDTH: f64 = 120.0;
+const DEFAULT_JWT_LIFETIME: Duration = Duration::from_secs(3600);
+
 struct AuthOptionsController {
     updates: UpdateQueue<AuthOptionsController, State>,
+    test_task: Option<JoinHandle<()>>,
 }
 
-type CommandValidationState = ValidationState<String, Option<Arc<AuthorizationHook>>>;
+/// A lens to a field of the form, which marks the form as dirty when it is edited.
+struct FormLens<L>(L);
+
+type FormValidationState = ValidationState<AuthForm, Option<Arc<AuthProvider>>>;
 
 pub fn build() -> impl Widget<State> {
     let id = WidgetId::next();
 
-    let command_textbox = FormField::text_box(input(command_placeholder())).lens(State::command);
+    let kind = RadioGroup::row(vec![
+        ("None", AuthKind::None),
+        ("Hook", AuthKind::Hook),
+        ("Bearer token", AuthKind::Bearer),
+        ("Basic", AuthKind::Basic),
+        ("API key", AuthKind::ApiKey),
+        ("JWT", AuthKind::Jwt),
+        ("OAuth2", AuthKind::OAuth2),
+    ])
+    .lens(Map::new(
+        |data: &State| data.form.text().kind,
+        |data: &mut State, kind| data.form.with_text_mut(|form| form.kind = kind),
+    ));
+
+    let fields = ViewSwitcher::new(
+        |data: &State, _| data.form.text().kind,
+        |&kind, _, _| match kind {
+            AuthKind::None => empty().boxed(),
+            AuthKind::Hook => build_hook_fields().boxed(),
+            AuthKind::Bearer => text_field("Token", "", AuthForm::token).boxed(),
+            AuthKind::Basic => Flex::column()
+                .with_child(text_field("Username", "", AuthForm::username))
+                .with_spacer(GRID_NARROW_SPACER)
+                .with_child(text_field("Password", "", AuthForm::password))
+                .boxed(),
+            AuthKind::ApiKey => Flex::column()
+                .with_child(text_field("Header", "x-api-key", AuthForm::header))
+                .with_spacer(GRID_NARROW_SPACER)
+                .with_child(text_field("Key", "", AuthForm::key))
+                .boxed(),
+            AuthKind::Jwt => build_jwt_fields().boxed(),
+            AuthKind::OAuth2 => build_oauth2_fields().boxed(),
+        },
+    );
 
     let error = error_label(Insets::ZERO)
         .expand_width()
         .lens(lens::Project::new(|data: &State| data.error()));
 
-    let command_form_field = Flex::column().with_child(command_textbox).with_child(error);
+    let stderr = Either::new(
+        |data: &State, _| data.stderr.is_empty(),
+        empty(),
+        Label::dynamic(|data: &State, _| data.stderr.trim_end().to_owned())
+            .with_font(theme::EDITOR_FONT)
+            .with_line_break_mode(LineBreaking::WordWrap)
+            .padding(GRID_NARROW_SPACER)
+            .expand_width(),
+    );
+
+    let form = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(kind)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(fields)
+        .with_child(error)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(stderr);
 
     let spinner = state_icon((0.0, 0.0, BODY_PADDING, 0.0))
         .lens(lens::Project::new(|data: &State| data.state_icon()));
 
-    let test_button = theme::button_scope(Button::new("Test").on_click(
-        move |ctx: &mut EventCtx, _: &mut State, _: &Env| {
-            ctx.submit_command(START_TEST.to(id));
-        },
-    ))
+    let test_button = theme::button_scope(
+        Button::dynamic(|data: &State, _| match data.execute_state {
+            ExecuteState::InProgress => "Cancel".to_owned(),
+            _ => "Test".to_owned(),
+        })
+        .on_click(move |ctx: &mut EventCtx, data: &mut State, _: &Env| {
+            match data.execute_state {
+                ExecuteState::InProgress => ctx.submit_command(CANCEL_TEST.to(id)),
+                _ => ctx.submit_command(START_TEST.to(id)),
+            }
+        }),
+    )
     .disabled_if(|data: &State, _| {
-        matches!(data.command.result(), Ok(None) | Err(_))
-            || matches!(data.execute_state, ExecuteState::InProgress)
+        matches!(data.form.result(), Ok(None) | Err(_))
+            && !matches!(data.execute_state, ExecuteState::InProgress)
     });
 
     Flex::row()
         .cross_axis_alignment(CrossAxisAlignment::Start)
-        .with_flex_child(command_form_field, 1.0)
+        .with_flex_child(form, 1.0)
         .with_spacer(theme::BODY_SPACER)
         .with_child(spinner)
         .with_child(test_button.fix_width(100.0))
@@ -76,25 +193,185 @@ pub fn build() -> impl Widget<State> {
         .with_id(id)
 }
 
+fn build_hook_fields() -> impl Widget<State> {
+    let command_textbox =
+        FormField::text_box(input(command_placeholder()).lens(AuthForm::shell)).lens(State::form);
+
+    let persistent_checkbox = theme::check_box_scope(Checkbox::new(
+        "Keep the hook process running between requests",
+    ))
+    .lens(FormLens(AuthForm::persistent));
+
+    let timeout_textbox = input("None").lens(FormLens(AuthForm::timeout));
+
+    let refresh_margin_textbox = input(HookOptions::default().refresh_margin.as_secs().to_string())
+        .lens(FormLens(AuthForm::refresh_margin));
+
+    let durations = Flex::row()
+        .with_child(Label::new("Timeout (seconds)"))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(timeout_textbox.fix_width(80.0))
+        .with_spacer(theme::BODY_SPACER)
+        .with_child(Label::new("Refresh before expiry (seconds)"))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(refresh_margin_textbox.fix_width(80.0));
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(command_textbox)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(persistent_checkbox)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(durations)
+}
+
+fn build_jwt_fields() -> impl Widget<State> {
+    Flex::column()
+        .with_child(text_field(
+            "Private key file",
+            "key.pem",
+            AuthForm::key_file,
+        ))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(text_field(
+            "Claims",
+            r#"{ "iss": "me@example.com", "aud": "https://example.com" }"#,
+            AuthForm::claims,
+        ))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(text_field(
+            "Lifetime (seconds)",
+            DEFAULT_JWT_LIFETIME.as_secs().to_string(),
+            AuthForm::lifetime,
+        ))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(text_field("Key ID", "None", AuthForm::key_id))
+}
+
+fn build_oauth2_fields() -> impl Widget<State> {
+    let grant = RadioGroup::row(vec![
+        ("Client credentials", GrantKind::ClientCredentials),
+        ("Refresh token", GrantKind::RefreshToken),
+    ])
+    .lens(FormLens(AuthForm::grant));
+
+    let refresh_token = Either::new(
+        |data: &State, _| data.form.text().grant == GrantKind::RefreshToken,
+        Flex::column()
+            .with_spacer(GRID_NARROW_SPACER)
+            .with_child(text_field("Refresh token", "", AuthForm::refresh_token)),
+        empty(),
+    );
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(text_field(
+            "Token URL",
+            "https://example.com/oauth2/token",
+            AuthForm::token_url,
+        ))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(grant)
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(text_field("Client ID", "", AuthForm::client_id))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(text_field("Client secret", "", AuthForm::client_secret))
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(text_field("Scope", "None", AuthForm::scope))
+        .with_child(refresh_token)
+}
+
+fn text_field(
+    label: &str,
+    placeholder: impl Into<String>,
+    lens: impl Lens<AuthForm, String> + 'static,
+) -> impl Widget<State> {
+    Flex::row()
+        .with_child(Label::new(label).fix_width(FIELD_LABEL_WIDTH))
+        .with_flex_child(input(placeholder).lens(FormLens(lens)), 1.0)
+}
+
 impl State {
-    pub fn new(auth_hook: &Option<Arc<AuthorizationHook>>) -> State {
-        let shell = match auth_hook {
-            Some(hook) => hook.shell(),
-            None => "",
+    pub fn new(auth: &Option<Arc<AuthProvider>>) -> State {
+        let form = match auth.as_deref() {
+            None => AuthForm::default(),
+            Some(AuthProvider::Hook(hook)) => {
+                let options = hook.options();
+                AuthForm {
+                    kind: AuthKind::Hook,
+                    shell: hook.shell().to_owned(),
+                    persistent: options.mode == HookMode::Persistent,
+                    timeout: options
+                        .timeout
+                        .map(|timeout| timeout.as_secs().to_string())
+                        .unwrap_or_default(),
+                    refresh_margin: options.refresh_margin.as_secs().to_string(),
+                    ..Default::default()
+                }
+            }
+            Some(AuthProvider::Builtin(BuiltinAuth::Bearer { token })) => AuthForm {
+                kind: AuthKind::Bearer,
+                token: token.clone(),
+                ..Default::default()
+            },
+            Some(AuthProvider::Builtin(BuiltinAuth::Basic { username, password })) => AuthForm {
+                kind: AuthKind::Basic,
+                username: username.clone(),
+                password: password.clone(),
+                ..Default::default()
+            },
+            Some(AuthProvider::Builtin(BuiltinAuth::ApiKey { header, key })) => AuthForm {
+                kind: AuthKind::ApiKey,
+                header: header.clone(),
+                key: key.clone(),
+                ..Default::default()
+            },
+            Some(AuthProvider::Builtin(BuiltinAuth::Jwt(config))) => AuthForm {
+                kind: AuthKind::Jwt,
+                key_file: config.key_file.display().to_string(),
+                claims: if config.claims.is_empty() {
+                    String::new()
+                } else {
+                    serde_json::to_string(&config.claims).unwrap_or_default()
+                },
+                lifetime: config.lifetime_secs.to_string(),
+                key_id: config.key_id.clone().unwrap_or_default(),
+                ..Default::default()
+            },
+            Some(AuthProvider::OAuth2(provider)) => {
+                let config = provider.config();
+                let (grant, refresh_token) = match &config.grant {
+                    OAuth2Grant::ClientCredentials => (GrantKind::ClientCredentials, String::new()),
+                    OAuth2Grant::RefreshToken { refresh_token } => {
+                        (GrantKind::RefreshToken, refresh_token.clone())
+                    }
+                };
+                AuthForm {
+                    kind: AuthKind::OAuth2,
+                    token_url: config.token_url.clone(),
+                    grant,
+                    client_id: config.client_id.clone(),
+                    client_secret: config.client_secret.clone(),
+                    scope: config.scope.clone(),
+                    refresh_token,
+                    ..Default::default()
+                }
+            }
         };
 
         State {
-            command: ValidationState::new(shell.to_owned(), VALIDATE_COMMAND.clone()),
+            form: ValidationState::new(form, VALIDATE_FORM.clone()),
             execute_state: ExecuteState::NotStarted,
+            stderr: Arc::default(),
         }
     }
 
-    pub fn hook(&self) -> Option<Arc<AuthorizationHook>> {
-        self.command.result().ok().and_then(|h| h.clone())
+    pub fn provider(&self) -> Option<Arc<AuthProvider>> {
+        self.form.result().ok().and_then(|p| p.clone())
     }
 
     pub fn error(&self) -> Option<Arc<str>> {
-        if let Some(err) = self.command.display_error() {
+        if let Some(err) = self.form.display_error() {
             Some(err)
         } else if let ExecuteState::Failed(err) = &self.execute_state {
             Some(err.clone())
@@ -113,10 +390,36 @@ impl State {
     }
 }
 
+impl Default for State {
+    fn default() -> Self {
+        State::new(&None)
+    }
+}
+
+impl<L, U> Lens<State, U> for FormLens<L>
+where
+    L: Lens<AuthForm, U>,
+    U: Data,
+{
+    fn with<V, F: FnOnce(&U) -> V>(&self, data: &State, f: F) -> V {
+        self.0.with(data.form.text(), f)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut U) -> V>(&self, data: &mut State, f: F) -> V {
+        let old = data.form.text().clone();
+        let value = data.form.with_text_mut(|form| self.0.with_mut(form, f));
+        if !data.form.text().same(&old) {
+            data.form.set_dirty();
+        }
+        value
+    }
+}
+
 impl AuthOptionsController {
     fn new() -> Self {
         AuthOptionsController {
             updates: UpdateQueue::new(),
+            test_task: None,
         }
     }
 
@@ -127,25 +430,54 @@ impl AuthOptionsController {
         data: &mut State,
     ) -> Handled {
         if command.is(START_TEST) {
-            let Ok(Some(hook)) = data.command.result() else {
-                tracing::warn!("start-test called without authorization hook");
+            let Ok(Some(provider)) = data.form.result() else {
+                tracing::warn!("start-test called without authorization provider");
                 return Handled::Yes;
             };
 
             let writer = self.updates.writer(ctx);
-            let hook = hook.clone();
+            let provider = provider.clone();
+            let mut stderr = provider.subscribe_stderr();
 
             data.execute_state = ExecuteState::InProgress;
-            tokio::spawn(async move {
-                let result = hook.get_headers_force().await.map(drop);
-                writer.write(|_, _, data| match result {
-                    Ok(_) => data.execute_state = ExecuteState::Succeeded,
-                    Err(err) => data.execute_state = ExecuteState::Failed(fmt_err(&err)),
+            data.stderr = Arc::default();
+            self.test_task = Some(tokio::spawn(async move {
+                let context = CallContext::default();
+                let headers = provider.get_headers_force(&context);
+                tokio::pin!(headers);
+
+                let result = loop {
+                    tokio::select! {
+                        result = &mut headers => break result.map(drop),
+                        line = async { stderr.as_mut().unwrap().recv().await },
+                            if stderr.is_some() => match line {
+                            Ok(line) => writer.write(move |_, _, data| {
+                                Arc::make_mut(&mut data.stderr).push_str(&line);
+                                Arc::make_mut(&mut data.stderr).push('\n');
+                            }),
+                            Err(RecvError::Lagged(count)) => {
+                                tracing::warn!("Skipped {} lines of hook output", count)
+                            }
+                            Err(RecvError::Closed) => stderr = None,
+                        },
+                    }
+                };
+
+                writer.write(|controller, _, data| {
+                    controller.test_task = None;
+                    match result {
+                        Ok(()) => data.execute_state = ExecuteState::Succeeded,
+                        Err(err) => data.execute_state = ExecuteState::Failed(fmt_err(&err)),
+                    }
                 });
-            });
+            }));
 
             Handled::Yes
         } else if command.is(CANCEL_TEST) {
+            // Aborting the task drops the hook future, which kills the hook process.
+            if let Some(task) = self.test_task.take() {
+                task.abort();
+            }
             data.execute_state = ExecuteState::NotStarted;
             self.updates.disconnect();
             Handled::Yes
@@ -186,7 +518,7 @@ where
         data: &State,
         env: &Env,
     ) {
-        if old_data.command.text() != data.command.text() {
+        if old_data.form.text() != data.form.text() {
             ctx.submit_command(CANCEL_TEST.to(ctx.widget_id()));
         }
 
@@ -194,17 +526,111 @@ where
     }
 }
 
-static VALIDATE_COMMAND: Lazy<ValidationFn<String, Option<Arc<AuthorizationHook>>>> =
-    Lazy::new(|| Arc::new(validate_hook));
+static VALIDATE_FORM: Lazy<ValidationFn<AuthForm, Option<Arc<AuthProvider>>>> =
+    Lazy::new(|| Arc::new(validate_form));
+
+fn validate_form(form: &AuthForm) -> Result<Option<Arc<AuthProvider>>, ArcStr> {
+    let builtin = match form.kind {
+        AuthKind::None => return Ok(None),
+        AuthKind::Hook => {
+            return Ok(validate_hook(form)?.map(|hook| Arc::new(AuthProvider::Hook(hook))))
+        }
+        AuthKind::Bearer => BuiltinAuth::Bearer {
+            token: form.token.trim().to_owned(),
+        },
+        AuthKind::Basic => BuiltinAuth::Basic {
+            username: form.username.clone(),
+            password: form.password.clone(),
+        },
+        AuthKind::ApiKey => BuiltinAuth::ApiKey {
+            header: form.header.trim().to_owned(),
+            key: form.key.trim().to_owned(),
+        },
+        AuthKind::Jwt => BuiltinAuth::Jwt(validate_jwt(form)?),
+        AuthKind::OAuth2 => {
+            let provider = OAuth2Provider::new(oauth2_config(form)).map_err(|err| fmt_err(&err))?;
+            return Ok(Some(Arc::new(AuthProvider::OAuth2(provider))));
+        }
+    };
+
+    builtin.validate().map_err(|err| fmt_err(&err))?;
+    Ok(Some(Arc::new(AuthProvider::Builtin(builtin))))
+}
 
-fn validate_hook(s: &String) -> Result<Option<Arc<AuthorizationHook>>, ArcStr> {
-    if s.is_empty() {
+fn validate_hook(form: &AuthForm) -> Result<Option<AuthorizationHook>, ArcStr> {
+    if form.shell.is_empty() {
         return Ok(None);
     }
 
-    let hook = AuthorizationHook::new(s.clone()).map_err(|err| fmt_err(&err))?;
+    let mode = if form.persistent {
+        HookMode::Persistent
+    } else {
+        HookMode::OneShot
+    };
+    let timeout = match parse_seconds(&form.timeout, "timeout")? {
+        Some(Duration::ZERO) => return Err("timeout must be greater than zero".into()),
+        timeout => timeout,
+    };
+    let refresh_margin = parse_seconds(&form.refresh_margin, "refresh margin")?
+        .unwrap_or(HookOptions::default().refresh_margin);
+
+    let options = HookOptions {
+        mode,
+        timeout,
+        refresh_margin,
+    };
+    let hook = AuthorizationHook::new(form.shell.clone(), options).map_err(|err| fmt_err(&err))?;
+
+    Ok(Some(hook))
+}
+
+fn validate_jwt(form: &AuthForm) -> Result<JwtConfig, ArcStr> {
+    let claims = if form.claims.trim().is_empty() {
+        serde_json::Map::new()
+    } else {
+        serde_json::from_str(&form.claims)
+            .map_err(|err| ArcStr::from(format!("invalid claims: {}", err)))?
+    };
+    let lifetime = parse_seconds(&form.lifetime, "lifetime")?.unwrap_or(DEFAULT_JWT_LIFETIME);
+    let key_id = Some(form.key_id.trim())
+        .filter(|key_id| !key_id.is_empty())
+        .map(ToOwned::to_owned);
+
+    Ok(JwtConfig {
+        key_file: form.key_file.trim().into(),
+        claims,
+        lifetime_secs: lifetime.as_secs(),
+        key_id,
+    })
+}
 
-    Ok(Some(Arc::new(hook)))
+fn oauth2_config(form: &AuthForm) -> OAuth2Config {
+    let grant = match form.grant {
+        GrantKind::ClientCredentials => OAuth2Grant::ClientCredentials,
+        GrantKind::RefreshToken => OAuth2Grant::RefreshToken {
+            refresh_token: form.refresh_token.trim().to_owned(),
+        },
+    };
+
+    OAuth2Config {
+        token_url: form.token_url.trim().to_owned(),
+        grant,
+        client_id: form.client_id.trim().to_owned(),
+        client_secret: form.client_secret.trim().to_owned(),
+        scope: form.scope.trim().to_owned(),
+    }
+}
+
+fn parse_seconds(text: &str, name: &str) -> Result<Option<Duration>, ArcStr> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    match text.parse() {
+        Ok(secs) => Ok(Some(Duration::from_secs(secs))),
+        Err(_) => Err(format!("{} must be a whole number of seconds", name).into()),
+    }
 }
 
 fn command_placeholder() -> String {
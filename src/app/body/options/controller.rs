@@ -8,7 +8,7 @@ use crate::{
         body::{options::OptionsTabState, RequestState},
         command,
     },
-    error::fmt_connect_err,
+    error::{fmt_connect_err, fmt_err},
     grpc,
     widget::update_queue::{self, UpdateQueue},
 };
@@ -53,8 +53,9 @@ where
         data: &OptionsTabState,
         env: &Env,
     ) {
-        if old_data.default_address.uri() != data.default_address.uri()
+        if old_data.default_address.address() != data.default_address.address()
             || old_data.verify_certs != data.verify_certs
+            || !old_data.timeout.same(&data.timeout)
             || !old_data.default_metadata.same(&data.default_metadata)
             || !old_data.auth.same(&data.auth)
         {
@@ -63,6 +64,12 @@ where
             );
         }
 
+        if old_data.default_address.resolved_uri(&old_data.environment)
+            != data.default_address.resolved_uri(&data.environment)
+        {
+            ctx.submit_command(command::DISCONNECT.to(ctx.widget_id()));
+        }
+
         child.update(ctx, old_data, data, env);
     }
 }
@@ -93,8 +100,13 @@ impl OptionsTabController {
     }
 
     fn start_connect(&mut self, ctx: &mut EventCtx, data: &mut OptionsTabState) {
-        let uri = match data.default_address.uri() {
-            Some(uri) => uri.clone(),
+        let uri = match data.default_address.resolve(&data.environment) {
+            Some(Ok(uri)) => uri,
+            Some(Err(err)) => {
+                data.default_address
+                    .set_request_state(RequestState::ConnectFailed(fmt_err(&err)));
+                return;
+            }
             None => {
                 tracing::error!("Connect called with no address");
                 return;
@@ -102,9 +114,9 @@ impl OptionsTabController {
         };
 
         let update_writer = self.updates.writer(ctx);
-        let verify_certs = data.service_options().verify_certs;
+        let client_options = data.service_options().client_options();
         tokio::spawn(async move {
-            let result = grpc::Client::new(&uri, verify_certs).await;
+            let result = grpc::Client::new(&uri, &client_options).await;
             update_writer.write(|controller, _, data| controller.finish_connect(data, result));
         });
 
@@ -1,19 +1,25 @@
-mod auth;
+pub(in crate::app::body) mod auth;
 mod controller;
 
+use std::sync::Arc;
+
 use druid::{
     widget::{prelude::*, Button, Checkbox, CrossAxisAlignment, Flex, Label, Scroll},
-    ArcStr, Data, Lens, WidgetExt,
+    ArcStr, Data, Insets, Lens, WidgetExt,
 };
 use prost_reflect::ServiceDescriptor;
 
 use crate::{
     app::{
-        body::address::{self, AddressState},
+        body::{
+            address::{self, AddressState},
+            Environment,
+        },
         command, metadata,
-        sidebar::service::ServiceOptions,
+        sidebar::service::{parse_timeout_secs, ServiceOptions},
     },
-    theme,
+    lens, theme,
+    widget::{error_label, input},
 };
 
 use self::controller::OptionsTabController;
@@ -27,8 +33,12 @@ pub struct OptionsTabState {
     service: ServiceDescriptor,
     default_address: AddressState,
     verify_certs: bool,
+    /// The number of seconds a call may take, or empty for no limit.
+    timeout: Arc<String>,
     default_metadata: metadata::EditableState,
     auth: auth::State,
+    #[lens(ignore)]
+    environment: Environment,
 }
 
 pub fn build_body() -> impl Widget<OptionsTabState> {
@@ -36,6 +46,18 @@ pub fn build_body() -> impl Widget<OptionsTabState> {
 
     let tls_checkbox = theme::check_box_scope(Checkbox::new("Enable certificate verification"));
 
+    let timeout = Flex::row()
+        .with_child(Label::new("Timeout (seconds)"))
+        .with_spacer(theme::BODY_SPACER)
+        .with_child(input("None").lens(OptionsTabState::timeout).fix_width(80.0))
+        .with_spacer(theme::BODY_SPACER)
+        .with_flex_child(
+            error_label(Insets::ZERO).lens(lens::Project::new(|data: &OptionsTabState| {
+                parse_timeout_secs(&data.timeout).err()
+            })),
+            1.0,
+        );
+
     let default_metadata = metadata::build_editable();
 
     Scroll::new(
@@ -50,11 +72,13 @@ pub fn build_body() -> impl Widget<OptionsTabState> {
             .with_spacer(theme::BODY_SPACER)
             .with_child(tls_checkbox.lens(OptionsTabState::verify_certs))
             .with_spacer(theme::BODY_SPACER)
+            .with_child(timeout)
+            .with_spacer(theme::BODY_SPACER)
             .with_child(Label::new("Default metadata").with_font(theme::font::HEADER_TWO))
             .with_spacer(theme::BODY_SPACER)
             .with_child(default_metadata.lens(OptionsTabState::default_metadata))
             .with_spacer(theme::BODY_SPACER)
-            .with_child(Label::new("Authorization hook").with_font(theme::font::HEADER_TWO))
+            .with_child(Label::new("Authorization").with_font(theme::font::HEADER_TWO))
             .with_spacer(theme::BODY_SPACER)
             .with_child(auth::build().lens(OptionsTabState::auth))
             .must_fill_main_axis(true)
@@ -104,8 +128,15 @@ impl OptionsTabState {
                 None => AddressState::default(),
             },
             verify_certs: options.verify_certs,
+            timeout: Arc::new(
+                options
+                    .timeout_secs
+                    .map(|secs| secs.to_string())
+                    .unwrap_or_default(),
+            ),
             default_metadata: metadata::EditableState::new(options.default_metadata),
-            auth: auth::State::new(&options.auth_hook),
+            auth: auth::State::new(&options.auth),
+            environment: Environment::default(),
         }
     }
 
@@ -119,20 +150,25 @@ impl OptionsTabState {
 
     pub fn service_options(&self) -> ServiceOptions {
         ServiceOptions {
-            default_address: self.default_address.uri().cloned(),
+            default_address: self.default_address.address().cloned(),
             verify_certs: self.verify_certs,
             default_metadata: self.default_metadata.to_state(),
-            auth_hook: self.auth.hook(),
+            auth: self.auth.provider(),
+            timeout_secs: parse_timeout_secs(&self.timeout).ok().flatten(),
         }
     }
 
     pub fn set_service_options(&mut self, options: ServiceOptions) {
         if let Some(default_address) = options.default_address {
-            self.default_address.set_uri(&default_address);
+            self.default_address.set_address(&default_address);
         }
         self.verify_certs = options.verify_certs;
     }
 
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
     pub fn can_connect(&self) -> bool {
         self.default_address.is_valid()
             && match self.default_address.request_state() {
@@ -141,6 +177,7 @@ impl OptionsTabState {
                 | RequestState::ConnectInProgress
                 | RequestState::AuthorizationHookInProgress
                 | RequestState::AuthorizationHookFailed(_)
+                | RequestState::SendFailed(_)
                 | RequestState::SendInProgress => false,
             }
     }
@@ -1,30 +1,25 @@
 use std::sync::Arc;
 
-use anyhow::{bail, Context, Result};
 use druid::{
     widget::{prelude::*, Controller},
     Command, Handled, Target,
 };
-use http::Uri;
-use prost_reflect::{DescriptorPool, ServiceDescriptor};
-use tokio::sync::{mpsc, Mutex};
-use tokio_stream::wrappers::UnboundedReceiverStream;
-use tonic::{metadata::MetadataMap, Code, Extensions, Request, Status, Streaming};
-use tonic_reflection::pb::{
-    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
-    server_reflection_response::MessageResponse, ServerReflectionRequest, ServerReflectionResponse,
-};
+use tokio::sync::Mutex;
 
 use crate::{
     app::{
         body::{
-            reflection::{ReflectionTabState, IMPORT_SERVICE, LIST_SERVICES},
+            reflection::{ReflectionTabState, APPLY_PRESET, IMPORT_SERVICE, LIST_SERVICES},
             RequestState,
         },
         command,
     },
-    error::fmt_grpc_err,
-    grpc,
+    auth::CallContext,
+    error::{fmt_err, fmt_grpc_err},
+    grpc::{
+        self,
+        reflection::{self, ReflectionSession},
+    },
     widget::update_queue::{self, UpdateQueue},
 };
 
@@ -33,14 +28,6 @@ pub struct ReflectionController {
     session: Option<Arc<Mutex<ReflectionSession>>>,
 }
 
-struct ReflectionSession {
-    sender: mpsc::UnboundedSender<ServerReflectionRequest>,
-    receiver: Streaming<ServerReflectionResponse>,
-    host: String,
-    services: Arc<Vec<String>>,
-    pool: DescriptorPool,
-}
-
 impl<W> Controller<ReflectionTabState, W> for ReflectionController
 where
     W: Widget<ReflectionTabState>,
@@ -82,6 +69,9 @@ impl ReflectionController {
         } else if let Some(service) = command.get(IMPORT_SERVICE) {
             self.import_service(ctx, data, service.clone());
             Handled::Yes
+        } else if let Some(options) = command.get(APPLY_PRESET) {
+            data.set_service_options(options);
+            Handled::Yes
         } else if command.is(update_queue::UPDATE) {
             while let Some(update) = self.updates.pop() {
                 (update)(self, ctx, data)
@@ -96,20 +86,64 @@ impl ReflectionController {
         data.address
             .set_request_state(RequestState::ConnectInProgress);
 
-        let Some(address) = data.address.uri().cloned() else {
-            tracing::warn!("list-services called with invalid uri");
-            return;
+        let address = match data.address.resolve(&data.environment) {
+            Some(Ok(address)) => address,
+            Some(Err(err)) => {
+                data.address
+                    .set_request_state(RequestState::ConnectFailed(fmt_err(&err)));
+                return;
+            }
+            None => {
+                tracing::warn!("list-services called with invalid uri");
+                return;
+            }
         };
-        let verify_certs = data.verify_certs;
-        let metadata = data.metadata.metadata();
+        let metadata = match data.metadata.expand(&data.environment) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                data.address
+                    .set_request_state(RequestState::ConnectFailed(fmt_err(&err)));
+                return;
+            }
+        };
+        let client_options = grpc::ClientOptions::new().verify_certs(data.verify_certs);
+        let auth = data.auth.provider();
+        let auth_context = CallContext::new(
+            address.to_string(),
+            reflection::SERVICE_NAME,
+            reflection::METHOD_NAME,
+        );
 
         let writer = self.updates.writer(ctx);
         tokio::spawn(async move {
-            let result = ReflectionSession::connect(address, verify_certs, metadata).await;
+            let authorization = match &auth {
+                Some(auth) => match auth.get_headers(&auth_context).await {
+                    Ok(authorization) => authorization,
+                    Err(err) => {
+                        let err = fmt_err(&err);
+                        writer.write(move |_, _, data| {
+                            data.address
+                                .set_request_state(RequestState::AuthorizationHookFailed(err))
+                        });
+                        return;
+                    }
+                },
+                None => http::HeaderMap::new(),
+            };
+
+            let result = ReflectionSession::connect(
+                address,
+                &client_options,
+                metadata,
+                auth,
+                auth_context,
+                authorization,
+            )
+            .await;
             writer.write(|controller, _, data| match result {
                 Ok(session) => {
                     data.address.set_request_state(RequestState::Connected);
-                    data.services = Some(session.services.clone());
+                    data.services = Some(session.services().clone());
                     controller.session = Some(Arc::new(Mutex::new(session)));
                 }
                 Err(err) => data
@@ -134,10 +168,11 @@ impl ReflectionController {
 
         let writer = self.updates.writer(ctx);
         tokio::spawn(async move {
-            match ReflectionSession::load_service(session, name).await {
+            let result = session.lock().await.load_service(&name).await;
+            match result {
                 Ok(service) => writer.submit_command(
                     command::ADD_SERVICE,
-                    (service, service_options),
+                    (service, service_options, None),
                     Target::Auto,
                 ),
                 Err(err) => writer.write(move |_, _, data| {
@@ -148,79 +183,3 @@ impl ReflectionController {
         });
     }
 }
-
-impl ReflectionSession {
-    async fn connect(address: Uri, verify_certs: bool, metadata: MetadataMap) -> Result<Self> {
-        let channel = grpc::channel::get(&address, verify_certs).await?;
-        let mut client = ServerReflectionClient::new(channel);
-
-        let (sender, request_receiver) = mpsc::unbounded_channel::<ServerReflectionRequest>();
-        let mut receiver = client
-            .server_reflection_info(Request::from_parts(
-                metadata,
-                Extensions::default(),
-                UnboundedReceiverStream::new(request_receiver),
-            ))
-            .await?
-            .into_inner();
-
-        let host = address.host().unwrap_or_default().to_owned();
-        sender.send(ServerReflectionRequest {
-            host: host.clone(),
-            message_request: Some(MessageRequest::ListServices(String::default())),
-        })?;
-        let Some(response) = receiver.message().await? else {
-            bail!("unexpected end of response stream");
-        };
-        let service_list = match response.message_response {
-            Some(MessageResponse::ListServicesResponse(service_list)) => service_list,
-            Some(MessageResponse::ErrorResponse(error)) => {
-                return Err(
-                    Status::new(Code::from_i32(error.error_code), error.error_message).into(),
-                )
-            }
-            _ => bail!("unexpected response type"),
-        };
-
-        Ok(ReflectionSession {
-            sender,
-            receiver,
-            host,
-            services: Arc::new(service_list.service.into_iter().map(|s| s.name).collect()),
-            pool: DescriptorPool::new(),
-        })
-    }
-
-    async fn load_service(this: Arc<Mutex<Self>>, name: String) -> Result<ServiceDescriptor> {
-        let mut this = this.lock().await;
-
-        this.sender.send(ServerReflectionRequest {
-            host: this.host.clone(),
-            message_request: Some(MessageRequest::FileContainingSymbol(name.clone())),
-        })?;
-        let Some(response) = this.receiver.message().await? else {
-            bail!("unexpected end of response stream");
-        };
-        let file_response = match response.message_response {
-            Some(MessageResponse::FileDescriptorResponse(file_response)) => file_response,
-            Some(MessageResponse::ErrorResponse(error)) => {
-                return Err(
-                    Status::new(Code::from_i32(error.error_code), error.error_message).into(),
-                )
-            }
-            _ => bail!("unexpected response type"),
-        };
-
-        for file in file_response.file_descriptor_proto {
-            this.pool
-                .decode_file_descriptor_proto(file.as_ref())
-                .context("failed to load file descriptor from server")?;
-        }
-
-        let Some(service) = this.pool.get_service_by_name(&name) else {
-            bail!("service '{}' not found in file descriptor from server", name)
-        };
-
-        Ok(service)
-    }
-}
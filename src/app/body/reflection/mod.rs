@@ -3,18 +3,25 @@ mod controller;
 use std::sync::Arc;
 
 use druid::{
-    widget::{prelude::*, Button, Checkbox, CrossAxisAlignment, Flex, Label, List, Maybe, Scroll},
-    Lens, Selector, WidgetExt,
+    widget::{
+        prelude::*, Button, Checkbox, CrossAxisAlignment, Either, Flex, Label, LineBreaking, List,
+        Maybe, Scroll,
+    },
+    ArcStr, Lens, Selector, WidgetExt,
 };
 
 use crate::{
     app::{
-        body::address::{self, AddressState},
+        body::{
+            address::{self, AddressState},
+            options::auth,
+            Environment,
+        },
         metadata,
-        sidebar::service::ServiceOptions,
+        sidebar::service::{ServiceOptions, ServiceState},
     },
     theme::{self, BODY_SPACER, GRID_NARROW_SPACER},
-    widget::{empty, readonly_input, Icon},
+    widget::{empty, readonly_input, Empty, Icon},
 };
 
 use self::controller::ReflectionController;
@@ -22,13 +29,26 @@ use self::controller::ReflectionController;
 /// Connect
 pub const LIST_SERVICES: Selector = Selector::new("app.body.reflection.list-services");
 pub const IMPORT_SERVICE: Selector<String> = Selector::new("app.body.reflection.import-service");
+/// Copy the options of an existing service into the reflection tab
+pub const APPLY_PRESET: Selector<ServiceOptions> =
+    Selector::new("app.body.reflection.apply-preset");
 
 #[derive(Default, Debug, Clone, Data, Lens)]
 pub struct ReflectionTabState {
     address: AddressState,
     verify_certs: bool,
     metadata: metadata::EditableState,
+    auth: auth::State,
+    presets: Arc<Vec<ServicePreset>>,
     services: Option<Arc<Vec<String>>>,
+    #[lens(ignore)]
+    environment: Environment,
+}
+
+#[derive(Debug, Clone, Data, Lens)]
+pub struct ServicePreset {
+    name: ArcStr,
+    options: ServiceOptions,
 }
 
 pub fn build_body() -> impl Widget<ReflectionTabState> {
@@ -47,6 +67,18 @@ pub fn build_body() -> impl Widget<ReflectionTabState> {
             .with_child(Label::new("Metadata").with_font(theme::font::HEADER_TWO))
             .with_spacer(theme::BODY_SPACER)
             .with_child(metadata::build_editable().lens(ReflectionTabState::metadata))
+            .with_spacer(theme::BODY_SPACER)
+            .with_child(Label::new("Authorization").with_font(theme::font::HEADER_TWO))
+            .with_spacer(theme::BODY_SPACER)
+            .with_child(auth::build().lens(ReflectionTabState::auth))
+            .with_child(
+                Either::new(
+                    |data: &Arc<Vec<ServicePreset>>, _| !data.is_empty(),
+                    build_preset_list(id),
+                    Empty,
+                )
+                .lens(ReflectionTabState::presets),
+            )
             .with_child(
                 Maybe::new(move || build_service_list(id), empty)
                     .lens(ReflectionTabState::services),
@@ -76,6 +108,32 @@ fn build_address_bar(parent: WidgetId) -> impl Widget<ReflectionTabState> {
         .cross_axis_alignment(CrossAxisAlignment::Start)
 }
 
+fn build_preset_list(parent: WidgetId) -> impl Widget<Arc<Vec<ServicePreset>>> {
+    Flex::column()
+        .with_spacer(BODY_SPACER)
+        .with_child(Label::new("Use options from service").with_font(theme::font::HEADER_TWO))
+        .with_spacer(BODY_SPACER)
+        .with_child(List::new(move || build_preset_row(parent)).with_spacing(GRID_NARROW_SPACER))
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+}
+
+fn build_preset_row(parent: WidgetId) -> impl Widget<ServicePreset> {
+    Flex::row()
+        .with_flex_child(
+            Label::raw()
+                .with_line_break_mode(LineBreaking::Clip)
+                .expand_width()
+                .lens(ServicePreset::name),
+            1.0,
+        )
+        .with_spacer(GRID_NARROW_SPACER)
+        .with_child(Icon::settings().button(
+            move |ctx: &mut EventCtx, data: &mut ServicePreset, _| {
+                ctx.submit_command(APPLY_PRESET.with(data.options.clone()).to(parent));
+            },
+        ))
+}
+
 fn build_service_list(parent: WidgetId) -> impl Widget<Arc<Vec<String>>> {
     Flex::column()
         .with_spacer(BODY_SPACER)
@@ -105,20 +163,50 @@ impl ReflectionTabState {
             },
             verify_certs: options.verify_certs,
             metadata: metadata::EditableState::new(options.default_metadata),
+            auth: auth::State::new(&options.auth),
+            presets: Arc::default(),
             services: None,
+            environment: Environment::default(),
         }
     }
 
     pub fn service_options(&self) -> ServiceOptions {
         ServiceOptions {
-            default_address: self.address.uri().cloned(),
+            default_address: self.address.address().cloned(),
             verify_certs: self.verify_certs,
             default_metadata: self.metadata.to_state(),
-            auth_hook: None,
+            auth: self.auth.provider(),
+            timeout_secs: None,
         }
     }
 
+    pub fn set_service_options(&mut self, options: &ServiceOptions) {
+        if let Some(default_address) = &options.default_address {
+            self.address.set_address(default_address);
+        }
+        self.verify_certs = options.verify_certs;
+        self.metadata = metadata::EditableState::new(options.default_metadata.clone());
+        self.auth = auth::State::new(&options.auth);
+    }
+
+    pub fn set_presets(&mut self, presets: Arc<Vec<ServicePreset>>) {
+        self.presets = presets;
+    }
+
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
     pub fn can_send(&self) -> bool {
         self.address.is_valid() && self.metadata.is_valid()
     }
 }
+
+impl ServicePreset {
+    pub(in crate::app) fn new(service: &ServiceState) -> Self {
+        ServicePreset {
+            name: service.service().full_name().into(),
+            options: service.options().clone(),
+        }
+    }
+}
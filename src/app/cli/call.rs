@@ -0,0 +1,284 @@
+use std::{
+    io::{self, Write},
+    str::FromStr,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use futures::StreamExt;
+use pico_args::Arguments;
+use prost_reflect::{prost::Message, MethodDescriptor};
+use serde_json::Value;
+use tonic::{metadata::MetadataMap, Code, Status};
+
+use crate::{
+    app::{
+        body::SavedRequest,
+        cli::{read_argument, read_stdin, Source, Workspace},
+        metadata,
+        sidebar::service::ServiceOptions,
+    },
+    auth::CallContext,
+    error::is_unauthenticated,
+    grpc,
+};
+
+pub(super) struct CallArgs {
+    source: Source,
+    method: String,
+    data: Option<String>,
+    request: Option<String>,
+    headers: Vec<String>,
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Json,
+    JsonLines,
+}
+
+impl CallArgs {
+    pub fn parse(args: &mut Arguments) -> Result<Self> {
+        let source = Source::parse(args)?;
+        let data = args.opt_value_from_str(["-d", "--data"])?;
+        let request = args.opt_value_from_str(["-r", "--request"])?;
+        let headers = args.values_from_str(["-H", "--header"])?;
+        let format = args.opt_value_from_str("--format")?.unwrap_or(Format::Json);
+        let method = args
+            .opt_free_from_str()?
+            .context("expected a method in the form 'package.Service/Method'")?;
+
+        Ok(CallArgs {
+            source,
+            method,
+            data,
+            request,
+            headers,
+            format,
+        })
+    }
+}
+
+/// Calls a method, printing each response to stdout, and returns the status code of the call.
+pub(super) async fn call(workspace: &Workspace, args: CallArgs) -> Result<Code> {
+    let (service_name, method_name) = args
+        .method
+        .trim()
+        .rsplit_once('/')
+        .context("expected a method in the form 'package.Service/Method'")?;
+
+    let environment = workspace.environment(args.source.environment.as_deref())?;
+    let saved = match &args.request {
+        Some(name) => Some(find_saved_request(
+            workspace,
+            service_name,
+            method_name,
+            name,
+        )?),
+        None => None,
+    };
+    let options = args.source.options(workspace, service_name, saved.as_ref());
+
+    let saved_address = saved
+        .as_ref()
+        .map(|saved| saved.address.trim())
+        .filter(|address| !address.is_empty());
+    let uri = args
+        .source
+        .resolve_address(saved_address, &options, &environment)?;
+
+    let service = args
+        .source
+        .service(workspace, service_name, &uri, &options, &environment)
+        .await?;
+    let method = service
+        .methods()
+        .find(|method| method.name() == method_name)
+        .with_context(|| format!("method '{}' not found", method_name))?;
+
+    let json = match (&args.data, &saved) {
+        (Some(data), _) => read_argument(data)?,
+        (None, Some(saved)) => saved.request.as_str().to_owned(),
+        (None, None) => read_stdin()?,
+    };
+    let requests = parse_requests(&method, &environment.expand_json(&json)?)?;
+
+    let mut metadata = match &saved {
+        Some(saved) => saved.metadata.clone(),
+        None => options.default_metadata.clone(),
+    };
+    for header in &args.headers {
+        let (key, value) = header
+            .split_once(':')
+            .with_context(|| format!("invalid header '{}', expected 'key: value'", header))?;
+        Arc::make_mut(&mut metadata).push(metadata::Entry::new(
+            key.trim().to_owned(),
+            value.trim().to_owned(),
+        ));
+    }
+    let metadata = metadata::EditableState::new(metadata);
+    if !metadata.is_valid() {
+        bail!("invalid request metadata")
+    }
+    let metadata = metadata.expand(&environment)?;
+
+    let client = grpc::Client::new(&uri, &options.client_options()).await?;
+    let auth_context = CallContext::new(uri.to_string(), service.full_name(), method.name())
+        .with_request(&requests[0].message.encode_to_vec());
+
+    let mut force_authorization = false;
+    loop {
+        let authorization = match &options.auth {
+            Some(auth) if force_authorization => auth.get_headers_force(&auth_context).await,
+            Some(auth) => auth.get_headers(&auth_context).await,
+            None => Ok(http::HeaderMap::new()),
+        };
+        let authorization = authorization.context("authorization failed")?;
+
+        let mut headers = metadata.clone().into_headers();
+        headers.extend(authorization);
+        let mut headers = MetadataMap::from_headers(headers);
+        if let Some(timeout) = options.timeout() {
+            grpc::set_timeout(&mut headers, timeout);
+        }
+
+        let result = send(
+            client.clone(),
+            &method,
+            requests.clone(),
+            headers,
+            args.format,
+        )
+        .await?;
+
+        match result {
+            // Retry once with fresh credentials, as the method tab does.
+            Outcome::Failed(err, false)
+                if !force_authorization && is_unauthenticated(&err) && can_refresh(&options) =>
+            {
+                tracing::info!("Request was unauthenticated, refreshing authorization");
+                force_authorization = true;
+            }
+            Outcome::Failed(err, _) => return Err(err),
+            Outcome::Finished => return Ok(Code::Ok),
+        }
+    }
+}
+
+enum Outcome {
+    Finished,
+    /// The call failed, and whether any responses were received before the error.
+    Failed(Error, bool),
+}
+
+async fn send(
+    client: grpc::Client,
+    method: &MethodDescriptor,
+    requests: Vec<grpc::Request>,
+    metadata: MetadataMap,
+    format: Format,
+) -> Result<Outcome> {
+    let mut requests = requests.into_iter();
+    let first = requests.next().context("no request was given")?;
+
+    let (mut call, mut responses) = client.call_stream(method.clone(), first, metadata);
+    if method.is_client_streaming() {
+        for request in requests {
+            call.send(request);
+        }
+        call.finish();
+    }
+
+    let mut received = false;
+    while let Some(response) = responses.next().await {
+        match response {
+            grpc::ResponseResult::Response(response) => {
+                received = true;
+                let json = response.to_json();
+                let mut stdout = io::stdout().lock();
+                match format {
+                    Format::Json => {
+                        let value: Value = serde_json::from_str(&json)?;
+                        serde_json::to_writer_pretty(&mut stdout, &value)?;
+                        writeln!(stdout)?;
+                    }
+                    Format::JsonLines => writeln!(stdout, "{}", json)?,
+                }
+                stdout.flush()?;
+            }
+            grpc::ResponseResult::Metadata(_) => (),
+            grpc::ResponseResult::Error(err, _) => return Ok(Outcome::Failed(err, received)),
+            grpc::ResponseResult::Finished => return Ok(Outcome::Finished),
+        }
+    }
+
+    Ok(Outcome::Failed(
+        Status::unknown("the call ended unexpectedly").into(),
+        received,
+    ))
+}
+
+/// Parses one request, or a sequence of requests for client streaming methods.
+fn parse_requests(method: &MethodDescriptor, json: &str) -> Result<Vec<grpc::Request>> {
+    let requests = serde_json::Deserializer::from_str(json)
+        .into_iter::<Value>()
+        .map(|value| {
+            let value = value.context("invalid request")?;
+            grpc::Request::from_json(method.input(), &value.to_string()).context("invalid request")
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    match requests.len() {
+        0 => bail!("no request was given"),
+        1 => Ok(requests),
+        _ if method.is_client_streaming() => Ok(requests),
+        count => bail!(
+            "expected a single request for method '{}', got {}",
+            method.name(),
+            count
+        ),
+    }
+}
+
+fn find_saved_request(
+    workspace: &Workspace,
+    service_name: &str,
+    method_name: &str,
+    name: &str,
+) -> Result<SavedRequest> {
+    workspace
+        .service(service_name)
+        .and_then(|service| {
+            service
+                .methods()
+                .find(|method| method.method().name() == method_name)
+        })
+        .and_then(|method| {
+            method
+                .saved()
+                .iter()
+                .find(|saved| saved.name.as_str() == name.trim())
+        })
+        .cloned()
+        .with_context(|| format!("saved request '{}' not found", name.trim()))
+}
+
+fn can_refresh(options: &ServiceOptions) -> bool {
+    options
+        .auth
+        .as_ref()
+        .map_or(false, |auth| auth.can_refresh())
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            _ => Err(anyhow!("expected 'json' or 'jsonl'")),
+        }
+    }
+}
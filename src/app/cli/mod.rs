@@ -0,0 +1,367 @@
+mod call;
+mod test;
+
+use std::{
+    convert::Infallible,
+    ffi::OsString,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use http::Uri;
+use pico_args::Arguments;
+use prost_reflect::ServiceDescriptor;
+use tonic::{Code, Status};
+
+use crate::{
+    app::{
+        body::{Address, Collections, Environment, Environments, SavedRequest},
+        config::Config,
+        metadata,
+        sidebar::service::{ServiceOptions, ServiceState},
+        workspace,
+    },
+    auth::CallContext,
+    error::fmt_grpc_err,
+    grpc::reflection::{self, ReflectionSession},
+    protoc,
+};
+
+const USAGE: &str = "\
+Usage: lanquetta [COMMAND]
+
+Without a command, the application window is opened.
+
+Commands:
+  call <package.Service/Method>   Call a method and print the responses
+  list                            List the methods of the available services
+  test                            Run saved requests and check their assertions
+  help                            Print this message
+
+Options:
+      --workspace <FILE>          Read services and settings from a workspace file
+                                  [default: the application's workspace]
+
+Options for finding services:
+      --proto <PATH>              Load services from a .proto file or encoded file descriptor set
+  -I, --import-path <DIR>         Directory to search for imports of --proto, may be repeated
+      --reflection                Load services from the server using server reflection
+  -a, --address <URI>             The address of the server [default: the service's default address]
+      --insecure                  Don't verify the server's TLS certificate
+  -e, --env <NAME>                Environment used to expand {{variables}} [default: the active environment]
+
+Options for call:
+  -d, --data <JSON>               The request, or @FILE to read it from a file, or @- for stdin
+                                  [default: the saved request, or stdin]. Client streaming methods
+                                  accept a sequence of JSON objects.
+  -r, --request <NAME>            Use the address, request and metadata of a saved request
+  -H, --header <KEY: VALUE>       Add request metadata, may be repeated
+      --format <json|jsonl>       Print responses as pretty JSON, or one per line [default: json]
+
+Options for test:
+  -c, --collection <NAME>         Run a collection instead of every saved request, may be repeated
+      --filter <TEXT>             Only run saved requests whose name contains the text
+      --junit <FILE>              Write a JUnit XML report to the file
+
+Services, options, saved requests and environments are read from the workspace. The call command
+exits with the gRPC status code of the call, and the test command exits with 1 if any test fails.
+";
+
+/// Returns true if the arguments name a headless command, rather than opening the application.
+pub fn is_command(args: &[OsString]) -> bool {
+    matches!(
+        args.first().and_then(|arg| arg.to_str()),
+        Some("call" | "list" | "test" | "help" | "-h" | "--help")
+    )
+}
+
+/// Runs a headless command, returning the process exit code.
+pub async fn run(args: Vec<OsString>) -> ExitCode {
+    match try_run(args).await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", fmt_grpc_err(&err));
+            match err.downcast_ref::<Status>() {
+                Some(status) => exit_code(status.code()),
+                None => exit_code(Code::Unknown),
+            }
+        }
+    }
+}
+
+async fn try_run(args: Vec<OsString>) -> Result<ExitCode> {
+    let mut args = Arguments::from_vec(args);
+
+    let subcommand = args.subcommand()?;
+    let workspace: Option<PathBuf> = args.opt_value_from_os_str("--workspace", path)?;
+    let workspace = workspace.as_deref();
+
+    match subcommand.as_deref() {
+        Some("call") => {
+            let call_args = call::CallArgs::parse(&mut args)?;
+            finish(args)?;
+            let code = call::call(&Workspace::load(workspace)?, call_args).await?;
+            Ok(exit_code(code))
+        }
+        Some("list") => {
+            let source = Source::parse(&mut args)?;
+            finish(args)?;
+            list(&Workspace::load(workspace)?, source).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some("test") => {
+            let test_args = test::TestArgs::parse(&mut args)?;
+            finish(args)?;
+            test::test(&Workspace::load(workspace)?, test_args).await
+        }
+        _ => {
+            print!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn exit_code(code: Code) -> ExitCode {
+    ExitCode::from(code as u8)
+}
+
+/// The services, options and environments saved by the application.
+struct Workspace {
+    /// The workspace file, or `None` for the application's own services and settings.
+    path: Option<PathBuf>,
+    services: im::Vector<ServiceState>,
+    environments: Environments,
+    collections: Collections,
+}
+
+/// Where to find the descriptors of services.
+struct Source {
+    proto: Option<PathBuf>,
+    import_paths: Vec<PathBuf>,
+    reflection: bool,
+    address: Option<String>,
+    insecure: bool,
+    environment: Option<String>,
+}
+
+impl Workspace {
+    /// Loads a workspace file, or else the workspace open in the application, or else the
+    /// application's own services and settings.
+    fn load(path: Option<&Path>) -> Result<Self> {
+        // Secrets left out of workspace files are kept in the application's config.
+        let config = Config::load().data;
+        let path = path.or_else(|| config.workspace.path()).map(Path::to_owned);
+        let data = match &path {
+            Some(path) => workspace::load(path, &config.workspace)?,
+            None => config,
+        };
+        Ok(Workspace {
+            path,
+            services: data.sidebar.services().clone(),
+            environments: data.sidebar.environments().clone(),
+            collections: data.sidebar.collections().clone(),
+        })
+    }
+
+    fn service(&self, name: &str) -> Option<&ServiceState> {
+        self.services
+            .iter()
+            .find(|service| service.service().full_name() == name)
+    }
+
+    fn environment(&self, name: Option<&str>) -> Result<Environment> {
+        let name = name.unwrap_or_default();
+        self.environments
+            .named(name)
+            .with_context(|| format!("environment '{}' not found", name))
+    }
+}
+
+impl Source {
+    fn parse(args: &mut Arguments) -> Result<Self> {
+        Ok(Source {
+            proto: args.opt_value_from_os_str("--proto", path)?,
+            import_paths: args.values_from_os_str(["-I", "--import-path"], path)?,
+            reflection: args.contains("--reflection"),
+            address: args.opt_value_from_str(["-a", "--address"])?,
+            insecure: args.contains("--insecure"),
+            environment: args.opt_value_from_str(["-e", "--env"])?,
+        })
+    }
+
+    /// Gets the options for a service from the workspace, or those saved with a request, with any
+    /// overrides from the command line.
+    fn options(
+        &self,
+        workspace: &Workspace,
+        service_name: &str,
+        saved: Option<&SavedRequest>,
+    ) -> ServiceOptions {
+        let options = workspace
+            .service(service_name)
+            .map(|service| service.options().clone())
+            .unwrap_or_default();
+        let mut options = match saved {
+            Some(saved) => saved.service_options(&options),
+            None => options,
+        };
+        if self.insecure {
+            options.verify_certs = false;
+        }
+        options
+    }
+
+    /// Resolves the address given on the command line, falling back to `address`, and then
+    /// the service's default address.
+    fn resolve_address(
+        &self,
+        address: Option<&str>,
+        options: &ServiceOptions,
+        environment: &Environment,
+    ) -> Result<Uri> {
+        let address = match self.address.as_deref().or(address) {
+            Some(address) => address
+                .parse::<Address>()
+                .map_err(|err| anyhow!("invalid address: {}", err))?,
+            None => options
+                .default_address
+                .clone()
+                .context("no address is set, use --address to choose one")?,
+        };
+        address.resolve(environment)
+    }
+
+    /// Loads the descriptors of all services available from this source.
+    async fn services(
+        &self,
+        workspace: &Workspace,
+        environment: &Environment,
+    ) -> Result<Vec<ServiceDescriptor>> {
+        if let Some(path) = &self.proto {
+            let file = protoc::load_file(path, &self.import_paths)?;
+            Ok(file.parent_pool().services().collect())
+        } else if self.reflection {
+            let options = self.options(workspace, "", None);
+            let uri = self.resolve_address(None, &options, environment)?;
+            let mut session = connect_reflection(uri, &options, environment).await?;
+
+            let mut services = Vec::new();
+            for name in session.services().clone().iter() {
+                services.push(session.load_service(name).await?);
+            }
+            Ok(services)
+        } else {
+            Ok(workspace
+                .services
+                .iter()
+                .map(|service| service.service().clone())
+                .collect())
+        }
+    }
+
+    /// Loads the descriptor of a single service from this source.
+    async fn service(
+        &self,
+        workspace: &Workspace,
+        name: &str,
+        uri: &Uri,
+        options: &ServiceOptions,
+        environment: &Environment,
+    ) -> Result<ServiceDescriptor> {
+        if let Some(path) = &self.proto {
+            let file = protoc::load_file(path, &self.import_paths)?;
+            file.parent_pool()
+                .get_service_by_name(name)
+                .with_context(|| format!("service '{}' not found in '{}'", name, path.display()))
+        } else if self.reflection {
+            let mut session = connect_reflection(uri.clone(), options, environment).await?;
+            session.load_service(name).await
+        } else {
+            workspace
+                .service(name)
+                .map(|service| service.service().clone())
+                .with_context(|| {
+                    format!(
+                        "service '{}' not found in the workspace, use --proto or --reflection to load it",
+                        name
+                    )
+                })
+        }
+    }
+}
+
+async fn connect_reflection(
+    uri: Uri,
+    options: &ServiceOptions,
+    environment: &Environment,
+) -> Result<ReflectionSession> {
+    let metadata =
+        metadata::EditableState::new(options.default_metadata.clone()).expand(environment)?;
+    let auth_context = CallContext::new(
+        uri.to_string(),
+        reflection::SERVICE_NAME,
+        reflection::METHOD_NAME,
+    );
+    let authorization = match &options.auth {
+        Some(auth) => auth
+            .get_headers(&auth_context)
+            .await
+            .context("authorization failed")?,
+        None => http::HeaderMap::new(),
+    };
+
+    ReflectionSession::connect(
+        uri,
+        &options.client_options(),
+        metadata,
+        options.auth.clone(),
+        auth_context,
+        authorization,
+    )
+    .await
+}
+
+async fn list(workspace: &Workspace, source: Source) -> Result<()> {
+    let environment = workspace.environment(source.environment.as_deref())?;
+    for service in source.services(workspace, &environment).await? {
+        for method in service.methods() {
+            println!("{}/{}", service.full_name(), method.name());
+        }
+    }
+    Ok(())
+}
+
+/// Reads the text of an argument which may be given inline, as `@FILE`, or as `@-` for stdin.
+fn read_argument(value: &str) -> Result<String> {
+    match value.strip_prefix('@') {
+        Some("-") => read_stdin(),
+        Some(path) => Ok(fs_err::read_to_string(path)?),
+        None => Ok(value.to_owned()),
+    }
+}
+
+fn read_stdin() -> Result<String> {
+    let mut text = String::new();
+    io::stdin()
+        .read_to_string(&mut text)
+        .context("failed to read from stdin")?;
+    Ok(text)
+}
+
+/// Fails if any arguments were not recognized.
+fn finish(args: Arguments) -> Result<()> {
+    let remaining = args.finish();
+    if !remaining.is_empty() {
+        bail!(
+            "unexpected arguments {:?}, see 'lanquetta help' for usage",
+            remaining
+        )
+    }
+    Ok(())
+}
+
+fn path(s: &std::ffi::OsStr) -> Result<PathBuf, Infallible> {
+    Ok(PathBuf::from(s))
+}
@@ -0,0 +1,363 @@
+use std::{
+    fmt::Write,
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use pico_args::Arguments;
+
+use crate::{
+    app::{
+        body::{format_duration, runner, Environment, RunId},
+        cli::{path, Workspace},
+        config::Config,
+    },
+    error::fmt_err,
+};
+
+pub(super) struct TestArgs {
+    environment: Option<String>,
+    collections: Vec<String>,
+    filter: Option<String>,
+    junit: Option<PathBuf>,
+}
+
+/// A group of tests, either the saved requests of a service or the steps of a collection.
+struct Suite {
+    name: String,
+    cases: Vec<Case>,
+}
+
+struct Case {
+    name: String,
+    duration: Option<Duration>,
+    result: CaseResult,
+}
+
+enum CaseResult {
+    Passed,
+    Failed(String),
+    /// The step was not run, because an earlier step of the collection failed.
+    Skipped,
+}
+
+impl TestArgs {
+    pub fn parse(args: &mut Arguments) -> Result<Self> {
+        Ok(TestArgs {
+            environment: args.opt_value_from_str(["-e", "--env"])?,
+            collections: args.values_from_str(["-c", "--collection"])?,
+            filter: args.opt_value_from_str("--filter")?,
+            junit: args.opt_value_from_os_str("--junit", path)?,
+        })
+    }
+}
+
+/// Runs the saved requests or collections of a workspace, printing a summary and optionally
+/// writing a JUnit XML report. Fails if any test fails.
+pub(super) async fn test(workspace: &Workspace, args: TestArgs) -> Result<ExitCode> {
+    let start = Instant::now();
+
+    let suites = if args.collections.is_empty() {
+        let environment = workspace.environment(args.environment.as_deref())?;
+        run_saved_requests(workspace, &environment, args.filter.as_deref()).await?
+    } else {
+        let mut suites = Vec::with_capacity(args.collections.len());
+        for name in &args.collections {
+            suites.push(run_collection(workspace, name, args.environment.as_deref()).await?);
+        }
+        suites
+    };
+
+    print!("{}", summary(&suites, start.elapsed()));
+
+    if let Some(path) = &args.junit {
+        fs_err::write(path, junit(&suites))?;
+    }
+
+    if suites
+        .iter()
+        .any(|suite| suite.count(CaseResult::is_failed) != 0)
+    {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+async fn run_saved_requests(
+    workspace: &Workspace,
+    environment: &Environment,
+    filter: Option<&str>,
+) -> Result<Vec<Suite>> {
+    let snapshots = Config::snapshot_directory(workspace.path.as_deref())?;
+    let mut suites = Vec::new();
+    for service in &workspace.services {
+        let mut suite = Suite {
+            name: service.service().full_name().to_owned(),
+            cases: Vec::new(),
+        };
+
+        for method in service.methods() {
+            for saved in method.saved() {
+                let name = format!("{} ({})", method.method().name(), saved.name.trim());
+                if let Some(filter) = filter {
+                    if !format!("{}/{}", suite.name, name).contains(filter) {
+                        continue;
+                    }
+                }
+
+                let step = runner::Step::new(
+                    method.method().clone(),
+                    saved.clone(),
+                    service.options().clone(),
+                );
+                let mut result = None;
+                runner::run(
+                    vec![Ok(step)],
+                    environment.clone(),
+                    &snapshots,
+                    None,
+                    false,
+                    |_, step_result| result = Some(step_result),
+                )
+                .await;
+
+                suite.cases.push(Case::new(name, result));
+                suite.cases.last().unwrap().print();
+            }
+        }
+
+        if !suite.cases.is_empty() {
+            suites.push(suite);
+        }
+    }
+    Ok(suites)
+}
+
+async fn run_collection(
+    workspace: &Workspace,
+    name: &str,
+    environment: Option<&str>,
+) -> Result<Suite> {
+    let collection = workspace
+        .collections
+        .iter()
+        .find(|collection| collection.name() == name.trim())
+        .with_context(|| format!("collection '{}' not found", name.trim()))?;
+
+    let mut run = collection.resolve(
+        RunId::next(),
+        &workspace.services,
+        &workspace.environments,
+        workspace.path.as_deref(),
+    );
+    if environment.is_some() {
+        run.set_environment(workspace.environment(environment)?);
+    }
+
+    let mut results: Vec<Option<runner::StepResult>> = collection.steps().map(|_| None).collect();
+    run.run(|step_index, result| {
+        if let Some(slot) = results.get_mut(step_index) {
+            *slot = Some(result);
+        }
+    })
+    .await
+    .map_err(|err| anyhow!("{}", err))?;
+
+    let cases = collection
+        .steps()
+        .zip(results)
+        .map(|((method, request), result)| {
+            let case = Case::new(format!("{} ({})", method, request), result);
+            case.print();
+            case
+        })
+        .collect();
+
+    Ok(Suite {
+        name: collection.name().to_owned(),
+        cases,
+    })
+}
+
+impl Suite {
+    fn count(&self, f: impl Fn(&CaseResult) -> bool) -> usize {
+        self.cases.iter().filter(|case| f(&case.result)).count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.cases.iter().filter_map(|case| case.duration).sum()
+    }
+}
+
+impl Case {
+    fn new(name: String, result: Option<runner::StepResult>) -> Self {
+        match result {
+            Some(result) => Case {
+                name,
+                duration: result.duration,
+                result: match &result.error {
+                    None => CaseResult::Passed,
+                    Some(err) => CaseResult::Failed(fmt_err(err).to_string()),
+                },
+            },
+            None => Case {
+                name,
+                duration: None,
+                result: CaseResult::Skipped,
+            },
+        }
+    }
+
+    fn print(&self) {
+        let status = match self.result {
+            CaseResult::Passed => "ok",
+            CaseResult::Failed(_) => "FAILED",
+            CaseResult::Skipped => "skipped",
+        };
+        match self.duration {
+            Some(duration) => println!(
+                "test {} ... {} ({})",
+                self.name,
+                status,
+                format_duration(duration)
+            ),
+            None => println!("test {} ... {}", self.name, status),
+        }
+    }
+}
+
+impl CaseResult {
+    fn is_passed(&self) -> bool {
+        matches!(self, CaseResult::Passed)
+    }
+
+    fn is_failed(&self) -> bool {
+        matches!(self, CaseResult::Failed(_))
+    }
+
+    fn is_skipped(&self) -> bool {
+        matches!(self, CaseResult::Skipped)
+    }
+}
+
+fn summary(suites: &[Suite], elapsed: Duration) -> String {
+    let mut summary = String::new();
+
+    let failures: Vec<_> = suites
+        .iter()
+        .flat_map(|suite| suite.cases.iter().map(move |case| (suite, case)))
+        .filter_map(|(suite, case)| match &case.result {
+            CaseResult::Failed(message) => Some((suite, case, message)),
+            _ => None,
+        })
+        .collect();
+    if !failures.is_empty() {
+        summary.push_str("\nfailures:\n");
+        for (suite, case, message) in &failures {
+            writeln!(
+                summary,
+                "\n---- {}: {} ----\n{}",
+                suite.name, case.name, message
+            )
+            .unwrap();
+        }
+    }
+
+    let count =
+        |f: fn(&CaseResult) -> bool| -> usize { suites.iter().map(|suite| suite.count(f)).sum() };
+    writeln!(
+        summary,
+        "\ntest result: {}. {} passed; {} failed; {} skipped; finished in {}",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        count(CaseResult::is_passed),
+        count(CaseResult::is_failed),
+        count(CaseResult::is_skipped),
+        format_duration(elapsed),
+    )
+    .unwrap();
+
+    summary
+}
+
+fn junit(suites: &[Suite]) -> String {
+    let tests: usize = suites.iter().map(|suite| suite.cases.len()).sum();
+    let failures: usize = suites
+        .iter()
+        .map(|suite| suite.count(CaseResult::is_failed))
+        .sum();
+    let time: Duration = suites.iter().map(Suite::duration).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuites name=\"lanquetta\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        tests,
+        failures,
+        time.as_secs_f64()
+    )
+    .unwrap();
+
+    for suite in suites {
+        writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape(&suite.name),
+            suite.cases.len(),
+            suite.count(CaseResult::is_failed),
+            suite.count(CaseResult::is_skipped),
+            suite.duration().as_secs_f64()
+        )
+        .unwrap();
+
+        for case in &suite.cases {
+            write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape(&case.name),
+                escape(&suite.name),
+                case.duration.unwrap_or_default().as_secs_f64()
+            )
+            .unwrap();
+
+            match &case.result {
+                CaseResult::Passed => xml.push_str("/>\n"),
+                CaseResult::Failed(message) => {
+                    let summary = message.lines().next().unwrap_or_default();
+                    writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                        escape(summary),
+                        escape(message)
+                    )
+                    .unwrap();
+                }
+                CaseResult::Skipped => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+            }
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\t' => escaped.push(c),
+            c if c.is_control() => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -1,9 +1,14 @@
-use druid::{Command, FileDialogOptions, FileInfo, FileSpec, Selector};
+use std::{path::PathBuf, sync::Arc};
+
+use druid::{ArcStr, Command, FileDialogOptions, FileInfo, FileSpec, Selector, WidgetId};
 use prost_reflect::{MethodDescriptor, ServiceDescriptor};
 
-use crate::app::sidebar::service::ServiceOptions;
+use crate::{
+    app::{config::Config, sidebar::service::ServiceOptions},
+    grpcurl, snippet,
+};
 
-use super::body::CompileOptions;
+use super::body::{Collection, Collections, CompileOptions, Environments, RunId, SavedRequest};
 
 /// Open the source code in a browser
 pub const OPEN_GITHUB: Selector = Selector::new("app.open-github");
@@ -16,6 +21,10 @@ pub const SELECT_OR_CREATE_OPTIONS_TAB: Selector<(ServiceDescriptor, ServiceOpti
 pub const SELECT_OR_CREATE_METHOD_TAB: Selector<MethodDescriptor> =
     Selector::new("app.select-or-create-method-tab");
 
+/// Select the tab with the given saved request, or create a new one
+pub const SELECT_OR_CREATE_SAVED_REQUEST_TAB: Selector<(MethodDescriptor, SavedRequest)> =
+    Selector::new("app.select-or-create-saved-request-tab");
+
 /// Select or create a compiler options tab.
 pub const SELECT_OR_CREATE_COMPILE_TAB: Selector =
     Selector::new("app.select-or-create-compile-tab");
@@ -24,20 +33,71 @@ pub const SELECT_OR_CREATE_COMPILE_TAB: Selector =
 pub const SELECT_OR_CREATE_REFLECTION_TAB: Selector =
     Selector::new("app.select-or-create-reflection-tab");
 
+/// Select or create an environments tab.
+pub const SELECT_OR_CREATE_ENVIRONMENT_TAB: Selector =
+    Selector::new("app.select-or-create-environment-tab");
+
+/// Select or create a collections tab.
+pub const SELECT_OR_CREATE_COLLECTION_TAB: Selector =
+    Selector::new("app.select-or-create-collection-tab");
+
 /// Set compiler options
 pub const SET_COMPILE_OPTIONS: Selector<CompileOptions> = Selector::new("app.set-compile-options");
 
+/// Set the saved environments
+pub const SET_ENVIRONMENTS: Selector<Environments> = Selector::new("app.set-environments");
+
+/// Set the saved collections
+pub const SET_COLLECTIONS: Selector<Collections> = Selector::new("app.set-collections");
+
+/// Start the given run of a collection in the collections tab with the given id
+pub const RUN_COLLECTION: Selector<(WidgetId, RunId, Collection)> =
+    Selector::new("app.run-collection");
+
+/// Store values captured from a response in variables
+pub const CAPTURE_VARIABLES: Selector<Vec<(String, String)>> =
+    Selector::new("app.capture-variables");
+
 /// Set service options
 pub const SET_SERVICE_OPTIONS: Selector<(ServiceDescriptor, ServiceOptions)> =
     Selector::new("app.set-service-options");
 
-/// Add a service
-pub const ADD_SERVICE: Selector<(ServiceDescriptor, ServiceOptions)> =
+/// Add a service, with the `.proto` file it was compiled from, if any
+pub const ADD_SERVICE: Selector<(ServiceDescriptor, ServiceOptions, Option<PathBuf>)> =
     Selector::new("app.add-service");
 
 /// Remove a service
 pub const REMOVE_SERVICE: Selector<usize> = Selector::new("app.remove-service");
 
+/// Save the request in the selected tab under its name
+pub const SAVE_REQUEST: Selector = Selector::new("app.save-request");
+
+/// Copy a grpcurl command for the given method to the clipboard, loading the service from the
+/// include paths or a protoset file
+pub const COPY_AS_GRPCURL: Selector<(MethodDescriptor, Result<grpcurl::Command, ArcStr>)> =
+    Selector::new("app.copy-as-grpcurl");
+
+/// Copy a client code snippet for the request in the selected tab to the clipboard
+pub const COPY_SNIPPET: Selector<snippet::Language> = Selector::new("app.copy-snippet");
+
+/// Export the request history of the selected tab to a file
+pub const EXPORT_HISTORY: Selector = Selector::new("app.export-history");
+
+/// Open a tab for the request described by the grpcurl command line in the clipboard, loading its
+/// service if needed
+pub const IMPORT_GRPCURL: Selector = Selector::new("app.import-grpcurl");
+
+/// Create a new tab with the given request
+pub const CREATE_REQUEST_TAB: Selector<(MethodDescriptor, SavedRequest)> =
+    Selector::new("app.create-request-tab");
+
+/// Display an error at the top of the window
+pub const SHOW_ERROR: Selector<ArcStr> = Selector::new("app.show-error");
+
+/// Delete the saved request with the given name
+pub const DELETE_SAVED_REQUEST: Selector<(MethodDescriptor, Arc<String>)> =
+    Selector::new("app.delete-saved-request");
+
 /// Create a new tab with the given method
 pub const CREATE_TAB: Selector<MethodDescriptor> = Selector::new("app.create-tab");
 
@@ -59,6 +119,9 @@ pub const SEND: Selector = Selector::new("app.send");
 /// Finish sending a request
 pub const FINISH: Selector = Selector::new("app.finish");
 
+/// Cancel a running authorization hook
+pub const CANCEL_AUTHORIZATION: Selector = Selector::new("app.cancel-authorization");
+
 /// Disconnect from the server
 pub const DISCONNECT: Selector = Selector::new("app.disconnect");
 
@@ -87,3 +150,84 @@ pub fn add_file() -> Command {
 }
 
 pub const ADD_FILE_ACCEPT: Selector<FileInfo> = Selector::new("app.add-file-accept");
+
+/// Choose a file to export the request history of the selected tab to
+pub fn export_history(method_name: &str) -> Command {
+    const JSON_LINES_FILE: FileSpec = FileSpec {
+        name: "JSON Lines",
+        extensions: &["jsonl"],
+    };
+
+    druid::commands::SHOW_SAVE_PANEL.with(
+        FileDialogOptions::new()
+            .accept_command(EXPORT_HISTORY_ACCEPT)
+            .allowed_types(vec![JSON_LINES_FILE])
+            .default_type(JSON_LINES_FILE)
+            .default_name(format!("{}.jsonl", method_name))
+            .title("Export history")
+            .button_text("Export"),
+    )
+}
+
+pub const EXPORT_HISTORY_ACCEPT: Selector<FileInfo> = Selector::new("app.export-history-accept");
+
+const WORKSPACE_FILE: FileSpec = FileSpec {
+    name: "Workspace",
+    extensions: &["json"],
+};
+
+/// Choose a workspace file to open
+pub fn open_workspace() -> Command {
+    druid::commands::SHOW_OPEN_PANEL.with(
+        FileDialogOptions::new()
+            .accept_command(OPEN_WORKSPACE_ACCEPT)
+            .allowed_types(vec![WORKSPACE_FILE])
+            .default_type(WORKSPACE_FILE)
+            .title("Open workspace")
+            .button_text("Open"),
+    )
+}
+
+pub const OPEN_WORKSPACE_ACCEPT: Selector<FileInfo> = Selector::new("app.open-workspace-accept");
+
+/// Open the workspace file at the given path
+pub const OPEN_RECENT_WORKSPACE: Selector<PathBuf> = Selector::new("app.open-recent-workspace");
+
+/// Choose a file to save the services, settings and tabs to
+pub fn save_workspace() -> Command {
+    druid::commands::SHOW_SAVE_PANEL.with(
+        FileDialogOptions::new()
+            .accept_command(SAVE_WORKSPACE_ACCEPT)
+            .allowed_types(vec![WORKSPACE_FILE])
+            .default_type(WORKSPACE_FILE)
+            .default_name("workspace.json")
+            .title("Save workspace")
+            .button_text("Save"),
+    )
+}
+
+pub const SAVE_WORKSPACE_ACCEPT: Selector<FileInfo> = Selector::new("app.save-workspace-accept");
+
+/// Close the open workspace, restoring the services and tabs from the configuration
+pub const CLOSE_WORKSPACE: Selector = Selector::new("app.close-workspace");
+
+/// Choose a backup of the configuration to restore
+pub fn restore_backup() -> Command {
+    const CONFIG_FILE: FileSpec = FileSpec {
+        name: "Configuration backup",
+        extensions: &["json"],
+    };
+
+    let mut options = FileDialogOptions::new()
+        .accept_command(RESTORE_BACKUP_ACCEPT)
+        .allowed_types(vec![CONFIG_FILE])
+        .default_type(CONFIG_FILE)
+        .title("Restore from backup")
+        .button_text("Restore");
+    if let Ok(dir) = Config::backup_directory() {
+        options = options.force_starting_directory(dir);
+    }
+    druid::commands::SHOW_OPEN_PANEL.with(options)
+}
+
+pub const RESTORE_BACKUP_ACCEPT: Selector<FileInfo> = Selector::new("app.restore-backup-accept");
@@ -1,4 +1,10 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use dirs::config_dir;
@@ -11,14 +17,28 @@ use futures::{
     StreamExt,
 };
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use tokio::task;
 
-use crate::app::State;
+use crate::{
+    app::{
+        serde::{NewerVersionError, Store},
+        workspace, State,
+    },
+    error::fmt_err,
+};
 
+/// The contents of the config file. When it is saved, the data is first serialized by the
+/// [`Store`], which writes its larger parts to separate files.
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Data)]
-pub(in crate::app) struct Config {
+pub(in crate::app) struct Config<T = State> {
     pub window: WindowConfig,
-    pub data: State,
+    pub data: T,
+    /// Set if the config file was saved by a newer version of the application, so it must not be
+    /// overwritten.
+    #[serde(skip)]
+    #[data(ignore)]
+    pub read_only: bool,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize, Data)]
@@ -33,6 +53,8 @@ pub struct ConfigController {
     sender: mpsc::UnboundedSender<Config>,
     save_timer_token: TimerToken,
     save_task: task::JoinHandle<()>,
+    last_saved: Option<Config>,
+    read_only: bool,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, Data, PartialEq)]
@@ -43,53 +65,354 @@ enum WindowState {
 }
 
 impl Config {
+    const BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+    const MAX_BACKUPS: usize = 10;
+
     pub fn load() -> Config {
         Config::try_load().unwrap_or_else(|err| {
             tracing::warn!("Failed to load config: {:?}", err);
-            Config::default()
+            let mut config = Config::default();
+            if err.is::<NewerVersionError>() {
+                config.read_only = true;
+                config.data.error = Some(
+                    format!(
+                        "The configuration could not be loaded, and changes won't be saved until the application is updated: {}",
+                        fmt_err(&err)
+                    )
+                    .into(),
+                );
+            }
+            config
         })
     }
 
-    pub async fn store(config: &Config) {
-        if let Err(err) = Config::try_store(config).await {
+    pub fn store(config: &Config, store: &mut Store, workspace: &mut workspace::Saved) {
+        if let Err(err) = Config::try_store(config, store, workspace) {
             tracing::warn!("Failed to store config: {:?}", err);
         }
     }
 
+    /// Loads the config file, falling back to the newest valid backup if it can't be parsed.
     fn try_load() -> Result<Config> {
         let path = Config::path()?;
-        let text = fs_err::read_to_string(&path)?;
-        let config = serde_json::from_str(&text)?;
-        tracing::debug!("Loaded config from {}", path.display());
-        Ok(config)
+        let err = match Config::load_file(&path) {
+            Ok(config) => return Ok(config),
+            Err(err) if !path.exists() => return Err(err),
+            // Loading an older backup would overwrite the file when the config is next saved.
+            Err(err) if err.is::<NewerVersionError>() => return Err(err),
+            Err(err) => err,
+        };
+
+        tracing::warn!("Failed to load config, trying backups: {:?}", err);
+        for backup in Config::backups()?.iter().rev() {
+            match Config::load_file(backup) {
+                Ok(mut config) => {
+                    if config.data.error.is_none() {
+                        config.data.error = Some(
+                            format!(
+                                "The configuration could not be loaded, so it was restored from `{}`: {}",
+                                backup.display(),
+                                fmt_err(&err)
+                            )
+                            .into(),
+                        );
+                    }
+                    return Ok(config);
+                }
+                Err(err) => {
+                    tracing::warn!("Failed to load backup `{}`: {:?}", backup.display(), err)
+                }
+            }
+        }
+        Err(err)
+    }
+
+    /// Loads a config file, falling back to loading only its valid parts if it can't be loaded
+    /// in full. Fails with a [`NewerVersionError`] if the file was saved by a newer version of the
+    /// application.
+    pub fn load_file(path: &Path) -> Result<Config> {
+        let text = fs_err::read_to_string(path)?;
+        let value: serde_json::Value = serde_json::from_str(&text)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?;
+        match Config::deserialize(&value) {
+            Ok(config) => {
+                tracing::debug!("Loaded config from {}", path.display());
+                Ok(config)
+            }
+            Err(err) => {
+                tracing::warn!("Failed to load config, loading valid parts only: {:?}", err);
+                Config::load_partial(path, value)
+            }
+        }
     }
 
-    async fn try_store(config: &Config) -> Result<()> {
+    fn load_partial(path: &Path, mut value: serde_json::Value) -> Result<Config> {
+        let window = value
+            .get_mut("window")
+            .map(serde_json::Value::take)
+            .and_then(|window| serde_json::from_value(window).ok())
+            .unwrap_or_default();
+        let data = value
+            .get_mut("data")
+            .map(serde_json::Value::take)
+            .unwrap_or_default();
+        let (mut data, warnings) = crate::app::serde::load_partial(data)?;
+
+        if !warnings.is_empty() {
+            for warning in &warnings {
+                tracing::warn!("Config: {}", warning);
+            }
+
+            // The file is overwritten on the next save, so keep a copy of what was dropped which
+            // isn't removed with old backups.
+            let mut message = format!(
+                "Some settings could not be loaded: {}.",
+                warnings.join("; ")
+            );
+            let name = broken_name(OffsetDateTime::now_utc());
+            match Config::backup_file(path, &name) {
+                Ok(backup) => write!(
+                    message,
+                    " A copy of the original file was saved to `{}`.",
+                    backup.display()
+                )
+                .unwrap(),
+                Err(err) => tracing::warn!("Failed to copy config: {:?}", err),
+            }
+            data.error = Some(message.into());
+        }
+
+        Ok(Config {
+            window,
+            data,
+            read_only: false,
+        })
+    }
+
+    fn try_store(
+        config: &Config,
+        store: &mut Store,
+        saved_workspace: &mut workspace::Saved,
+    ) -> Result<()> {
         let dir = Config::directory()?;
         let path = Config::path()?;
-        let text = serde_json::to_string(config)?;
+        fs_err::create_dir_all(&dir)?;
+        // A newer version of the application may have saved the file since it was loaded.
+        Config::check_version(&path)?;
 
-        tokio::fs::create_dir_all(&dir)
-            .await
-            .with_context(|| format!("failed to create directory `{}`", dir.display()))?;
-        tokio::fs::write(&path, text)
-            .await
-            .with_context(|| format!("failed to write to file `{}`", dir.display()))?;
+        // The services and tabs of an open workspace are saved to its file, and the config file
+        // keeps its own, along with any secrets left out of the workspace file.
+        let mut data = workspace::config_state(&config.data);
+        if let Some(workspace) = config.data.workspace.path() {
+            match workspace::save_changed(workspace, &config.data, saved_workspace) {
+                Ok(secrets) => data.workspace.set_secrets(workspace, secrets),
+                Err(err) => tracing::warn!("Failed to store workspace: {:?}", err),
+            }
+        }
+        let stored = Config {
+            window: config.window,
+            data: store.serialize(&data)?,
+            read_only: false,
+        };
+        let text = serde_json::to_string(&stored)?;
+
+        match Config::backup(false) {
+            Ok(Some(backup)) => tracing::debug!("Backed up config to `{}`", backup.display()),
+            Ok(None) => (),
+            Err(err) => tracing::warn!("Failed to back up config: {:?}", err),
+        }
+        Config::write_file(&path, text.as_bytes())?;
         tracing::debug!("Stored config to `{}`", path.display());
+
+        if let Err(err) = store.remove_unused() {
+            tracing::warn!("Failed to remove unused files: {:?}", err);
+        }
+        Ok(())
+    }
+
+    /// Fails if a config file was saved by a newer version of the application, so that it isn't
+    /// overwritten.
+    fn check_version(path: &Path) -> Result<()> {
+        #[derive(Deserialize)]
+        struct Versioned {
+            data: Version,
+        }
+
+        #[derive(Deserialize)]
+        struct Version {
+            #[serde(default)]
+            version: u64,
+        }
+
+        let text = match fs_err::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        // A file which can't be parsed will be replaced, as it is when loading.
+        if let Ok(file) = serde_json::from_str::<Versioned>(&text) {
+            crate::app::serde::check_version(file.data.version)?;
+        }
         Ok(())
     }
 
+    /// Writes to a temporary file and then replaces the file with it, so the file is never left
+    /// partially written.
+    pub fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+
+        let mut file = fs_err::File::create(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        fs_err::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Copies the config file to the backup directory, unless the newest backup is more recent
+    /// than the backup interval and `force` is not set. The oldest backups are then removed.
+    /// Returns the path of the new backup, if one was made.
+    pub fn backup(force: bool) -> Result<Option<PathBuf>> {
+        let path = Config::path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        if !force {
+            let threshold = backup_name(OffsetDateTime::now_utc() - Config::BACKUP_INTERVAL);
+            if let Some(newest) = Config::backups()?.last() {
+                if newest.file_name() >= Some(OsStr::new(&threshold)) {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let backup = Config::backup_file(&path, &backup_name(OffsetDateTime::now_utc()))?;
+        let backups = Config::backups()?;
+        for old in &backups[..backups.len().saturating_sub(Config::MAX_BACKUPS)] {
+            fs_err::remove_file(old)?;
+        }
+        Ok(Some(backup))
+    }
+
+    /// Copies a file to the backup directory with the given name.
+    fn backup_file(path: &Path, name: &str) -> Result<PathBuf> {
+        let dir = Config::backup_directory()?;
+        fs_err::create_dir_all(&dir)?;
+        let backup = dir.join(name);
+        fs_err::copy(path, &backup)?;
+        Ok(backup)
+    }
+
+    /// Lists the backups of the config file, from oldest to newest.
+    pub fn backups() -> Result<Vec<PathBuf>> {
+        Config::list_backups(false)
+    }
+
+    /// Lists the copies of config files which could only be partly loaded, oldest first. These are
+    /// kept apart from the backups so they are never rotated out.
+    pub fn broken_copies() -> Result<Vec<PathBuf>> {
+        Config::list_backups(true)
+    }
+
+    fn list_backups(broken: bool) -> Result<Vec<PathBuf>> {
+        let entries = match fs_err::read_dir(Config::backup_directory()?) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let is_backup = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map_or(false, |name| {
+                    name.starts_with("config-")
+                        && name.ends_with(".json")
+                        && name.ends_with(BROKEN_SUFFIX) == broken
+                });
+            if is_backup {
+                backups.push(path);
+            }
+        }
+        // Names start with the time of the backup, so sorting them puts the newest last.
+        backups.sort();
+        Ok(backups)
+    }
+
     fn directory() -> Result<PathBuf> {
         let mut path = config_dir().context("no config directory found")?;
         path.push(env!("CARGO_CRATE_NAME"));
         Ok(path)
     }
 
-    fn path() -> Result<PathBuf> {
+    pub fn path() -> Result<PathBuf> {
         let mut path = Config::directory()?;
         path.push("config.json");
         Ok(path)
     }
+
+    /// The directory backups of the config file are stored in.
+    pub fn backup_directory() -> Result<PathBuf> {
+        let mut path = Config::directory()?;
+        path.push("backups");
+        Ok(path)
+    }
+
+    /// The directory descriptor pools are stored in, named by their hash.
+    pub fn descriptor_directory() -> Result<PathBuf> {
+        let mut path = Config::directory()?;
+        path.push("descriptors");
+        Ok(path)
+    }
+
+    /// The directory the request history of tabs is stored in, named by its hash.
+    pub fn history_directory() -> Result<PathBuf> {
+        let mut path = Config::directory()?;
+        path.push("history");
+        Ok(path)
+    }
+
+    /// The directory golden response snapshots are stored in. When a workspace file is used, this
+    /// is next to it, so that snapshots can be committed along with the workspace.
+    pub fn snapshot_directory(workspace: Option<&Path>) -> Result<PathBuf> {
+        let mut path = match workspace {
+            Some(workspace) => workspace.parent().map(Path::to_owned).unwrap_or_default(),
+            None => Config::directory()?,
+        };
+        path.push("snapshots");
+        Ok(path)
+    }
+
+    /// The directory protoset files for grpcurl commands are written to.
+    pub fn protoset_directory() -> Result<PathBuf> {
+        let mut path = Config::directory()?;
+        path.push("protosets");
+        Ok(path)
+    }
+}
+
+/// The suffix of copies of config files which could only be partly loaded.
+const BROKEN_SUFFIX: &str = ".broken.json";
+
+fn backup_name(time: OffsetDateTime) -> String {
+    format!(
+        "config-{:04}{:02}{:02}T{:02}{:02}{:02}Z.json",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+fn broken_name(time: OffsetDateTime) -> String {
+    backup_name(time).replace(".json", BROKEN_SUFFIX)
 }
 
 impl WindowConfig {
@@ -153,20 +476,45 @@ impl ConfigController {
             sender,
             save_timer_token: TimerToken::INVALID,
             save_task: task::spawn(Self::run_save(receiver)),
+            last_saved: None,
+            read_only: false,
         }
     }
 
+    /// Stops the config from being saved, because it was saved by a newer version of the
+    /// application.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
     fn save(&mut self, ctx: &mut EventCtx, data: &State) {
+        if self.read_only {
+            return;
+        }
+
+        // Cloning the state is cheap, since its contents are shared.
+        let config = Config {
+            window: WindowConfig::from_handle(ctx.window()),
+            data: data.clone(),
+            read_only: false,
+        };
+        if let Some(last_saved) = &self.last_saved {
+            if last_saved.same(&config) {
+                tracing::debug!("Skipping config save because it is unchanged");
+                return;
+            }
+        }
+
         self.sender
-            .unbounded_send(Config {
-                window: WindowConfig::from_handle(ctx.window()),
-                data: data.clone(),
-            })
+            .unbounded_send(config.clone())
             .expect("save task exited unexpectedly");
+        self.last_saved = Some(config);
     }
 
     async fn run_save(mut receiver: UnboundedReceiver<Config>) {
-        let mut prev: Option<Config> = None;
+        let mut store = Store::default();
+        let mut workspace = workspace::Saved::default();
 
         while let Some(mut config) = receiver.next().await {
             while let Ok(Some(buffered_config)) = receiver.try_next() {
@@ -174,13 +522,12 @@ impl ConfigController {
                 config = buffered_config;
             }
 
-            match prev {
-                Some(prev) if prev.same(&config) => {
-                    tracing::debug!("Skipping config save because it is unchanged")
-                }
-                _ => Config::store(&config).await,
-            }
-            prev = Some(config);
+            (store, workspace) = task::spawn_blocking(move || {
+                Config::store(&config, &mut store, &mut workspace);
+                (store, workspace)
+            })
+            .await
+            .expect("save task panicked");
         }
     }
 }
@@ -235,3 +582,33 @@ impl Drop for ConfigController {
         tracing::debug!("Config save task exited");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn newer_version_is_not_overwritten() {
+        let dir = env::temp_dir().join(format!("config-test-{}", std::process::id()));
+        fs_err::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        let text = json!({
+            "window": WindowConfig::default(),
+            "data": { "version": 1000000, "services": "a newer format" }
+        })
+        .to_string();
+        fs_err::write(&path, &text).unwrap();
+
+        let err = Config::load_file(&path).unwrap_err();
+        assert!(err.is::<NewerVersionError>(), "{:?}", err);
+        let err = Config::check_version(&path).unwrap_err();
+        assert!(err.is::<NewerVersionError>(), "{:?}", err);
+        assert_eq!(fs_err::read_to_string(&path).unwrap(), text);
+
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+}
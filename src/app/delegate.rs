@@ -1,9 +1,15 @@
+use std::path::Path;
+
 use anyhow::Result;
-use druid::{AppDelegate, Command, DelegateCtx, Env, Handled, Target, WindowHandle, WindowId};
+use druid::{
+    AppDelegate, Application, Command, DelegateCtx, Env, Handled, Target, WindowHandle, WindowId,
+};
+use prost_reflect::MethodDescriptor;
 
 use crate::{
-    app::{self, command},
+    app::{self, body, command, config::Config, import, workspace},
     error::fmt_err,
+    grpcurl,
 };
 
 pub(in crate::app) fn build() -> impl AppDelegate<app::State> {
@@ -28,7 +34,7 @@ impl AppDelegate<app::State> for Delegate {
 
     fn command(
         &mut self,
-        _ctx: &mut DelegateCtx,
+        ctx: &mut DelegateCtx,
         _target: Target,
         cmd: &Command,
         data: &mut app::State,
@@ -41,6 +47,30 @@ impl AppDelegate<app::State> for Delegate {
             } else {
                 data.error = None;
             }
+            data.body.set_service_presets(data.sidebar.services());
+            Handled::Yes
+        } else if let Some(file) = cmd.get(command::OPEN_WORKSPACE_ACCEPT) {
+            open_workspace(data, file.path());
+            Handled::Yes
+        } else if let Some(path) = cmd.get(command::OPEN_RECENT_WORKSPACE) {
+            open_workspace(data, path);
+            Handled::Yes
+        } else if let Some(file) = cmd.get(command::SAVE_WORKSPACE_ACCEPT) {
+            data.error = match workspace::save_as(data, file.path()) {
+                Ok(()) => None,
+                Err(err) => Some(format!("Error saving workspace: {}", fmt_err(&err)).into()),
+            };
+            Handled::Yes
+        } else if cmd.is(command::CLOSE_WORKSPACE) {
+            data.error = match workspace::close(data) {
+                Ok(()) => None,
+                Err(err) => Some(format!("Error saving workspace: {}", fmt_err(&err)).into()),
+            };
+            Handled::Yes
+        } else if let Some(file) = cmd.get(command::RESTORE_BACKUP_ACCEPT) {
+            if let Err(err) = restore_backup(data, file.path()) {
+                data.error = Some(format!("Error restoring backup: {}", fmt_err(&err)).into());
+            }
             Handled::Yes
         } else if cmd.is(command::OPEN_GITHUB) {
             let _ = open::that(concat!(
@@ -64,35 +94,128 @@ impl AppDelegate<app::State> for Delegate {
             data.body
                 .select_or_create_compiler_tab(data.sidebar.compile_options());
             Handled::Yes
+        } else if cmd.is(command::SELECT_OR_CREATE_ENVIRONMENT_TAB) {
+            data.body
+                .select_or_create_environment_tab(data.sidebar.environments());
+            Handled::Yes
+        } else if cmd.is(command::SELECT_OR_CREATE_COLLECTION_TAB) {
+            data.body
+                .select_or_create_collection_tab(data.sidebar.collections());
+            Handled::Yes
         } else if cmd.is(command::SELECT_OR_CREATE_REFLECTION_TAB) {
             data.body.select_or_create_reflection_tab();
+            data.body.set_service_presets(data.sidebar.services());
+            data.body
+                .set_environment(&data.sidebar.environments().active());
             Handled::Yes
         } else if let Some((service, options)) = cmd.get(command::SET_SERVICE_OPTIONS) {
             data.body.set_service_options(service, options);
             data.sidebar.set_service_options(service, options);
+            data.body.set_service_presets(data.sidebar.services());
             Handled::Yes
         } else if let Some(options) = cmd.get(command::SET_COMPILE_OPTIONS) {
             data.sidebar.set_compile_options(options.clone());
             Handled::Yes
+        } else if let Some(environments) = cmd.get(command::SET_ENVIRONMENTS) {
+            data.sidebar.set_environments(environments.clone());
+            data.body.set_environment(&environments.active());
+            Handled::Yes
+        } else if let Some(collections) = cmd.get(command::SET_COLLECTIONS) {
+            data.sidebar.set_collections(collections.clone());
+            Handled::Yes
+        } else if let Some((target, id, collection)) = cmd.get(command::RUN_COLLECTION) {
+            let run = collection.resolve(
+                *id,
+                data.sidebar.services(),
+                data.sidebar.environments(),
+                data.workspace.path(),
+            );
+            ctx.submit_command(body::START_COLLECTION_RUN.with(run).to(*target));
+            Handled::Yes
+        } else if let Some(values) = cmd.get(command::CAPTURE_VARIABLES) {
+            let mut environments = data.sidebar.environments().clone();
+            environments.capture(values);
+            data.body.set_environment(&environments.active());
+            data.body.set_captured_variables(&environments);
+            data.sidebar.set_environments(environments);
+            Handled::Yes
         } else if let Some((service, options)) = cmd.get(command::SELECT_OR_CREATE_OPTIONS_TAB) {
             data.body.select_or_create_options_tab(service, options);
+            data.body
+                .set_environment(&data.sidebar.environments().active());
             Handled::Yes
         } else if let Some(method) = cmd.get(command::SELECT_OR_CREATE_METHOD_TAB) {
             if let Some(options) = data.sidebar.service_options(method.parent_service()) {
                 data.body
                     .select_or_create_method_tab(method, options.clone());
+                data.body
+                    .set_environment(&data.sidebar.environments().active());
             }
             Handled::Yes
-        } else if let Some((service, options)) = cmd.get(command::ADD_SERVICE) {
-            data.sidebar.add_service(service.clone(), options.clone());
+        } else if let Some((service, options, proto)) = cmd.get(command::ADD_SERVICE) {
+            data.sidebar
+                .add_service(service.clone(), options.clone(), proto.clone());
+            data.body.set_service_presets(data.sidebar.services());
             Handled::Yes
         } else if let Some(service_index) = cmd.get(command::REMOVE_SERVICE) {
             let service = data.sidebar.remove_service(*service_index);
             data.body.remove_service(service.service());
+            data.body.set_service_presets(data.sidebar.services());
+            Handled::Yes
+        } else if let Some((method, request)) = cmd.get(command::SELECT_OR_CREATE_SAVED_REQUEST_TAB)
+        {
+            if let Some(options) = data.sidebar.service_options(method.parent_service()) {
+                data.body
+                    .select_or_create_saved_request_tab(method, request, options.clone());
+                data.body
+                    .set_environment(&data.sidebar.environments().active());
+            }
+            Handled::Yes
+        } else if cmd.is(command::SAVE_REQUEST) {
+            if let Some((method, request)) = data.body.selected_saved_request() {
+                data.sidebar.save_request(&method, request);
+            }
+            Handled::Yes
+        } else if let Some((method, grpcurl)) = cmd.get(command::COPY_AS_GRPCURL) {
+            let result = grpcurl.clone().and_then(|grpcurl| {
+                copy_as_grpcurl(data, method, grpcurl).map_err(|err| fmt_err(&err))
+            });
+            data.error = match result {
+                Ok(()) => None,
+                Err(err) => Some(format!("Error copying grpcurl command: {}", err).into()),
+            };
+            Handled::Yes
+        } else if cmd.is(command::IMPORT_GRPCURL) {
+            let text = Application::global()
+                .clipboard()
+                .get_string()
+                .unwrap_or_default();
+            data.error = match import::grpcurl(ctx, data, &text) {
+                Ok(()) => None,
+                Err(err) => {
+                    Some(format!("Error importing grpcurl command: {}", fmt_err(&err)).into())
+                }
+            };
+            Handled::Yes
+        } else if let Some((method, request)) = cmd.get(command::CREATE_REQUEST_TAB) {
+            if let Some(options) = data.sidebar.service_options(method.parent_service()) {
+                data.body
+                    .create_request_tab(method, request, options.clone());
+                data.body
+                    .set_environment(&data.sidebar.environments().active());
+            }
+            Handled::Yes
+        } else if let Some(error) = cmd.get(command::SHOW_ERROR) {
+            data.error = Some(error.clone());
+            Handled::Yes
+        } else if let Some((method, name)) = cmd.get(command::DELETE_SAVED_REQUEST) {
+            data.sidebar.delete_saved_request(method, name);
             Handled::Yes
         } else if let Some(method) = cmd.get(command::CREATE_TAB) {
             if let Some(options) = data.sidebar.service_options(method.parent_service()) {
                 data.body.create_method_tab(method, options.clone());
+                data.body
+                    .set_environment(&data.sidebar.environments().active());
             }
             Handled::Yes
         } else {
@@ -101,6 +224,59 @@ impl AppDelegate<app::State> for Delegate {
     }
 }
 
+/// Replaces the services, settings and tabs with those of a workspace file.
+fn open_workspace(data: &mut app::State, path: &Path) {
+    data.error = match workspace::open(data, path) {
+        Ok(()) => None,
+        Err(err) => Some(format!("Error opening workspace: {}", fmt_err(&err)).into()),
+    };
+}
+
+/// Replaces the state with that of a backup, after backing up the current config so the restore
+/// can be undone.
+fn restore_backup(data: &mut app::State, path: &Path) -> Result<()> {
+    let config = Config::load_file(path)?;
+    Config::backup(true)?;
+    *data = config.data;
+    workspace::reopen(data);
+    Ok(())
+}
+
+fn copy_as_grpcurl(
+    data: &app::State,
+    method: &MethodDescriptor,
+    mut grpcurl: grpcurl::Command,
+) -> Result<()> {
+    let proto = data
+        .sidebar
+        .services()
+        .iter()
+        .find(|service| service.service() == &method.parent_service())
+        .and_then(|service| service.proto());
+    grpcurl.source = grpcurl::Source::for_method(
+        method,
+        proto,
+        data.sidebar.compile_options().includes(),
+        &Config::protoset_directory()?,
+    )?;
+
+    let text = match &grpcurl.source {
+        // The protoset is only on this machine, so make it clear that it needs to be shared too.
+        grpcurl::Source::Protoset(paths) => format!(
+            "# Requires the protoset file {}, which must be copied along with this command\n{}",
+            paths
+                .iter()
+                .map(|path| format!("`{}`", path.display()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            grpcurl
+        ),
+        grpcurl::Source::Reflection | grpcurl::Source::Proto { .. } => grpcurl.to_string(),
+    };
+    Application::global().clipboard().put_string(text);
+    Ok(())
+}
+
 #[cfg(windows)]
 fn set_window_icon(handle: &WindowHandle) -> Result<()> {
     use druid::{HasRawWindowHandle, RawWindowHandle};
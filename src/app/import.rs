@@ -0,0 +1,242 @@
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+use anyhow::{anyhow, Context, Result};
+use druid::{DelegateCtx, ExtEventSink, Target};
+use prost_reflect::{MethodDescriptor, ServiceDescriptor};
+use tokio::task;
+
+use crate::{
+    app::{
+        self,
+        body::{Address, SavedRequest},
+        command, metadata,
+        sidebar::service::ServiceOptions,
+    },
+    auth::CallContext,
+    error::fmt_grpc_err,
+    grpc::{
+        self,
+        reflection::{self, ReflectionSession},
+    },
+    grpcurl, protoc,
+};
+
+/// Opens a tab for the request described by a grpcurl command line. If its service isn't loaded
+/// yet, it is loaded in the background from the command's proto files or protosets, or else using
+/// server reflection.
+pub(in crate::app) fn grpcurl(
+    ctx: &mut DelegateCtx,
+    data: &mut app::State,
+    text: &str,
+) -> Result<()> {
+    let grpcurl = grpcurl::Command::parse(text)?;
+    let (service_name, method_name) = split_method(&grpcurl);
+    let request = saved_request(&grpcurl);
+
+    if let Some(service) = find_service(data, service_name) {
+        let method = find_method(service.service(), method_name)?;
+        let mut options = service.options().clone();
+        options.verify_certs = grpcurl.verify_certs;
+
+        data.body.create_request_tab(&method, &request, options);
+        data.body
+            .set_environment(&data.sidebar.environments().active());
+        return Ok(());
+    }
+
+    let options = service_options(&grpcurl)?;
+    let loaded = data
+        .sidebar
+        .services()
+        .iter()
+        .map(|service| service.service().full_name().to_owned())
+        .collect();
+    tokio::spawn(load(
+        ctx.get_external_handle(),
+        grpcurl,
+        options,
+        request,
+        loaded,
+    ));
+    Ok(())
+}
+
+/// Loads the services of a grpcurl command, and submits commands to add those which aren't in
+/// `loaded` and open a tab for its request.
+async fn load(
+    sink: ExtEventSink,
+    grpcurl: grpcurl::Command,
+    options: ServiceOptions,
+    request: SavedRequest,
+    loaded: HashSet<String>,
+) {
+    let (service_name, method_name) = split_method(&grpcurl);
+    let result = async {
+        let mut services = load_files(&grpcurl, loaded).await?;
+        let found = services
+            .iter()
+            .map(|(service, _)| service)
+            .find(|service| service.full_name() == service_name)
+            .cloned();
+        let service = match found {
+            Some(service) => service,
+            None => {
+                let service = load_service(&grpcurl, &options, service_name).await;
+                let service = match grpcurl.source {
+                    grpcurl::Source::Reflection => service?,
+                    // The files may only define the service's messages, so fall back to server
+                    // reflection.
+                    _ => service.with_context(|| {
+                        format!(
+                            "service '{}' not found in the command's proto files or using server \
+                             reflection",
+                            service_name
+                        )
+                    })?,
+                };
+                services.push((service.clone(), None));
+                service
+            }
+        };
+        let method = find_method(&service, method_name)?;
+        Ok::<_, anyhow::Error>((services, method))
+    }
+    .await;
+
+    let result = match result {
+        Ok((services, method)) => services
+            .into_iter()
+            .try_for_each(|(service, proto)| {
+                sink.submit_command(
+                    command::ADD_SERVICE,
+                    (service, options.clone(), proto),
+                    Target::Auto,
+                )
+            })
+            .and_then(|()| {
+                sink.submit_command(command::CREATE_REQUEST_TAB, (method, request), Target::Auto)
+            }),
+        Err(err) => sink.submit_command(
+            command::SHOW_ERROR,
+            format!("Error importing grpcurl command: {}", fmt_grpc_err(&err)).into(),
+            Target::Auto,
+        ),
+    };
+    if let Err(err) = result {
+        tracing::warn!("Failed to submit imported request: {}", err);
+    }
+}
+
+/// Loads the services defined in the command's proto files or protosets which aren't in `loaded`,
+/// along with the `.proto` file each was compiled from.
+async fn load_files(
+    grpcurl: &grpcurl::Command,
+    mut loaded: HashSet<String>,
+) -> Result<Vec<(ServiceDescriptor, Option<PathBuf>)>> {
+    let source = grpcurl.source.clone();
+    task::spawn_blocking(move || {
+        let mut services = Vec::new();
+        for path in source.paths() {
+            let file = protoc::load_file(&path, source.import_paths())?;
+            for service in file.services() {
+                if loaded.insert(service.full_name().to_owned()) {
+                    let proto = protoc::is_proto(&path).then(|| path.clone());
+                    services.push((service, proto));
+                }
+            }
+        }
+        Ok(services)
+    })
+    .await
+    .expect("load task panicked")
+}
+
+async fn load_service(
+    grpcurl: &grpcurl::Command,
+    options: &ServiceOptions,
+    name: &str,
+) -> Result<ServiceDescriptor> {
+    let auth_context = CallContext::new(
+        grpcurl.address.to_string(),
+        reflection::SERVICE_NAME,
+        reflection::METHOD_NAME,
+    );
+    let mut session = ReflectionSession::connect(
+        grpcurl.address.clone(),
+        &options.client_options(),
+        grpcurl.metadata()?,
+        None,
+        auth_context,
+        http::HeaderMap::new(),
+    )
+    .await?;
+    session.load_service(name).await
+}
+
+fn split_method(grpcurl: &grpcurl::Command) -> (&str, &str) {
+    grpcurl
+        .method
+        .rsplit_once('/')
+        .expect("parsed methods contain a '/'")
+}
+
+fn find_service(data: &app::State, name: &str) -> Option<app::sidebar::service::ServiceState> {
+    data.sidebar
+        .services()
+        .iter()
+        .find(|service| service.service().full_name() == name)
+        .cloned()
+}
+
+fn find_method(service: &ServiceDescriptor, name: &str) -> Result<MethodDescriptor> {
+    service
+        .methods()
+        .find(|method| method.name() == name)
+        .with_context(|| {
+            format!(
+                "method '{}' not found in service '{}'",
+                name,
+                service.full_name()
+            )
+        })
+}
+
+fn address(grpcurl: &grpcurl::Command) -> String {
+    format!(
+        "{}://{}",
+        grpcurl.address.scheme_str().unwrap_or("https"),
+        grpc::authority(&grpcurl.address)
+    )
+}
+
+fn service_options(grpcurl: &grpcurl::Command) -> Result<ServiceOptions> {
+    let address = address(grpcurl)
+        .parse::<Address>()
+        .map_err(|err| anyhow!("invalid address: {}", err))?;
+    Ok(ServiceOptions {
+        default_address: Some(address),
+        verify_certs: grpcurl.verify_certs,
+        ..Default::default()
+    })
+}
+
+fn saved_request(grpcurl: &grpcurl::Command) -> SavedRequest {
+    let request = if grpcurl.data.trim().is_empty() {
+        "{}".to_owned()
+    } else {
+        grpcurl.data.clone()
+    };
+
+    SavedRequest {
+        address: Arc::new(address(grpcurl)),
+        request: Arc::new(request),
+        metadata: Arc::new(
+            grpcurl
+                .headers
+                .iter()
+                .map(|(key, value)| metadata::Entry::new(key.clone(), value.clone()))
+                .collect(),
+        ),
+        ..Default::default()
+    }
+}
@@ -1,22 +1,50 @@
 use druid::{keyboard_types::Key, menu, Env, LocalizedString, Menu, MenuItem, SysMods, WindowId};
 
-use crate::app;
+use crate::{app, snippet::Language};
 
 pub(in crate::app) fn build(
     _window: Option<WindowId>,
-    _data: &app::State,
+    data: &app::State,
     _env: &Env,
 ) -> Menu<app::State> {
     Menu::empty()
-        .entry(file_menu())
+        .entry(file_menu(data))
         .entry(edit_menu())
         .entry(request_menu())
         .entry(view_menu())
         .entry(help_menu())
+        .rebuild_on(|old, data, _| old.workspace.recent() != data.workspace.recent())
 }
 
-fn file_menu() -> Menu<app::State> {
+fn file_menu(data: &app::State) -> Menu<app::State> {
     Menu::new(LocalizedString::new("common-menu-file-menu"))
+        .entry(MenuItem::new("Open Workspace").command(app::command::open_workspace()))
+        .entry(recent_workspaces_menu(data))
+        .entry(MenuItem::new("Save Workspace As").command(app::command::save_workspace()))
+        .entry(
+            MenuItem::new("Close Workspace")
+                .command(app::command::CLOSE_WORKSPACE)
+                .enabled_if(|data, _| data.workspace.path().is_some()),
+        )
+        .entry(
+            Menu::new("Save Workspace With")
+                .entry(
+                    MenuItem::new("Secrets")
+                        .on_activate(|_, data: &mut app::State, _| {
+                            data.workspace.toggle_include_secrets()
+                        })
+                        .selected_if(|data, _| data.workspace.include_secrets()),
+                )
+                .entry(
+                    MenuItem::new("Request history")
+                        .on_activate(|_, data: &mut app::State, _| {
+                            data.workspace.toggle_include_history()
+                        })
+                        .selected_if(|data, _| data.workspace.include_history()),
+                ),
+        )
+        .entry(MenuItem::new("Restore from Backup").command(app::command::restore_backup()))
+        .separator()
         .entry(
             Menu::new("Import services")
                 .entry(
@@ -31,8 +59,15 @@ fn file_menu() -> Menu<app::State> {
                 .entry(
                     MenuItem::new("Server reflection")
                         .command(app::command::SELECT_OR_CREATE_REFLECTION_TAB),
+                )
+                .entry(
+                    MenuItem::new("Paste grpcurl command").command(app::command::IMPORT_GRPCURL),
                 ),
         )
+        .entry(
+            MenuItem::new("Environments").command(app::command::SELECT_OR_CREATE_ENVIRONMENT_TAB),
+        )
+        .entry(MenuItem::new("Collections").command(app::command::SELECT_OR_CREATE_COLLECTION_TAB))
         .separator()
         .entry(
             MenuItem::new("Close Tab")
@@ -43,6 +78,23 @@ fn file_menu() -> Menu<app::State> {
         .entry(menu::sys::win::file::close())
 }
 
+fn recent_workspaces_menu(data: &app::State) -> Menu<app::State> {
+    let recent = data.workspace.recent();
+    if recent.is_empty() {
+        return Menu::new("Recent Workspaces")
+            .entry(MenuItem::new("No recent workspaces").enabled(false));
+    }
+
+    recent
+        .iter()
+        .fold(Menu::new("Recent Workspaces"), |menu, path| {
+            menu.entry(
+                MenuItem::new(path.display().to_string())
+                    .command(app::command::OPEN_RECENT_WORKSPACE.with(path.clone())),
+            )
+        })
+}
+
 fn edit_menu() -> Menu<app::State> {
     Menu::new(LocalizedString::new("common-menu-edit-menu"))
         .entry(menu::sys::common::cut())
@@ -63,6 +115,13 @@ fn request_menu() -> Menu<app::State> {
                 .hotkey(SysMods::Shift, Key::Enter)
                 .enabled_if(|data, _| can_send(data)),
         )
+        .entry(
+            MenuItem::new("Save")
+                .command(app::command::SAVE_REQUEST)
+                .hotkey(SysMods::Cmd, "s")
+                .enabled_if(|data, _| can_save(data)),
+        )
+        .entry(snippet_menu())
         .entry(
             MenuItem::new("Finish")
                 .command(app::command::FINISH)
@@ -75,6 +134,18 @@ fn request_menu() -> Menu<app::State> {
         )
 }
 
+fn snippet_menu() -> Menu<app::State> {
+    Language::ALL
+        .into_iter()
+        .fold(Menu::new("Copy code snippet"), |menu, language| {
+            menu.entry(
+                MenuItem::new(language.to_string())
+                    .command(app::command::COPY_SNIPPET.with(language))
+                    .enabled_if(|data, _| has_selected_method(data)),
+            )
+        })
+}
+
 fn view_menu() -> Menu<app::State> {
     Menu::new("View")
         .entry(
@@ -95,6 +166,11 @@ fn view_menu() -> Menu<app::State> {
                 .hotkey(SysMods::CmdShift, "X")
                 .enabled_if(|data, _| has_selected_tab(data)),
         )
+        .entry(
+            MenuItem::new("Export request history")
+                .command(app::command::EXPORT_HISTORY)
+                .enabled_if(|data, _| has_selected_method(data)),
+        )
 }
 
 fn help_menu() -> Menu<app::State> {
@@ -105,6 +181,10 @@ fn has_selected_tab(data: &app::State) -> bool {
     data.body.selected_tab().is_some()
 }
 
+fn has_selected_method(data: &app::State) -> bool {
+    data.body.selected_method().is_some()
+}
+
 fn can_select_next_tab(data: &app::State) -> bool {
     data.body.selected_tab() != data.body.last_tab()
 }
@@ -121,6 +201,10 @@ fn can_send(data: &app::State) -> bool {
     data.body.can_send()
 }
 
+fn can_save(data: &app::State) -> bool {
+    data.body.can_save()
+}
+
 fn can_finish(data: &app::State) -> bool {
     data.body.can_finish()
 }
@@ -1,5 +1,6 @@
 use std::{str::FromStr, sync::Arc};
 
+use anyhow::{anyhow, Result};
 use base64::{
     alphabet,
     engine::{DecodePaddingMode, Engine, GeneralPurpose, GeneralPurposeConfig},
@@ -14,9 +15,13 @@ use tonic::metadata::{
     AsciiMetadataKey, AsciiMetadataValue, BinaryMetadataKey, BinaryMetadataValue, MetadataMap,
 };
 
-use crate::widget::{
-    env_error_label, input, readonly_input, EditableList, FinishEditController, FormField,
-    ValidationFn, ValidationState, ERROR_MESSAGE,
+use crate::{
+    app::{body::Environment, workspace},
+    template,
+    widget::{
+        env_error_label, input, readonly_input, EditableList, FinishEditController, FormField,
+        ValidationFn, ValidationState, ERROR_MESSAGE,
+    },
 };
 
 pub type State = Arc<Vec<Entry>>;
@@ -51,6 +56,8 @@ pub enum ParsedEntry {
         key: AsciiMetadataKey,
         value: AsciiMetadataValue,
     },
+    /// An entry containing placeholders, which is parsed after they are expanded.
+    Template,
 }
 
 const GRID_NARROW_SPACER: f64 = 2.0;
@@ -112,6 +119,37 @@ struct EditableRowLayout {
     error: WidgetPod<(), Box<dyn Widget<()>>>,
 }
 
+impl Entry {
+    pub fn new(key: String, value: String) -> Self {
+        Entry {
+            key: Arc::new(key),
+            value: Arc::new(value),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Clears the values of entries whose names suggest they hold secrets.
+pub fn redact_secrets(metadata: &mut State) {
+    if metadata
+        .iter()
+        .any(|entry| workspace::is_secret_name(&entry.key))
+    {
+        for entry in Arc::make_mut(metadata) {
+            if workspace::is_secret_name(&entry.key) {
+                entry.value = Arc::default();
+            }
+        }
+    }
+}
+
 pub fn state_from_tonic(metadata: MetadataMap) -> State {
     Arc::new(
         metadata
@@ -150,21 +188,36 @@ impl EditableState {
         EditableState { entries }
     }
 
-    pub fn metadata(&self) -> MetadataMap {
+    /// Gets the metadata to send, with any placeholders expanded.
+    pub fn expand(&self, environment: &Environment) -> Result<MetadataMap> {
         let mut map = MetadataMap::new();
         for entry in self.entries.iter() {
-            if let Ok(parsed_entry) = entry.result() {
-                match parsed_entry.clone() {
-                    ParsedEntry::Ascii { key, value } => {
-                        map.append(key, value);
-                    }
-                    ParsedEntry::Binary { key, value } => {
-                        map.append_bin(key, value);
-                    }
+            let raw = entry.text();
+            let parsed = match entry.result() {
+                Ok(ParsedEntry::Template) => {
+                    let expanded = EditableEntry {
+                        key: Arc::new(environment.expand(&raw.key)?.into_owned()),
+                        value: Arc::new(environment.expand(&raw.value)?.into_owned()),
+                        deleted: false,
+                    };
+                    parse_entry(&expanded)
+                        .map_err(|err| anyhow!("invalid metadata '{}': {}", expanded.key, err))?
+                }
+                Ok(parsed) => parsed.clone(),
+                Err(_) => continue,
+            };
+
+            match parsed {
+                ParsedEntry::Ascii { key, value } => {
+                    map.append(key, value);
                 }
+                ParsedEntry::Binary { key, value } => {
+                    map.append_bin(key, value);
+                }
+                ParsedEntry::Template => unreachable!(),
             }
         }
-        map
+        Ok(map)
     }
 
     pub fn to_state(&self) -> State {
@@ -340,6 +393,16 @@ static VALIDATE_ENTRY: Lazy<ValidationFn<EditableEntry, ParsedEntry>> =
     Lazy::new(|| Arc::new(validate_entry));
 
 fn validate_entry(raw: &EditableEntry) -> Result<ParsedEntry, ArcStr> {
+    if template::has_placeholders(&raw.key) || template::has_placeholders(&raw.value) {
+        template::validate(&raw.key).map_err(|err| err.to_string())?;
+        template::validate(&raw.value).map_err(|err| err.to_string())?;
+        return Ok(ParsedEntry::Template);
+    }
+
+    parse_entry(raw)
+}
+
+fn parse_entry(raw: &EditableEntry) -> Result<ParsedEntry, ArcStr> {
     if let Ok(key) = BinaryMetadataKey::from_str(&raw.key) {
         const STANDARD: GeneralPurpose = GeneralPurpose::new(
             &alphabet::STANDARD,
@@ -1,11 +1,14 @@
 mod body;
+pub mod cli;
 mod command;
 mod config;
 mod delegate;
+mod import;
 mod menu;
 mod metadata;
 mod serde;
 mod sidebar;
+mod workspace;
 
 use druid::{
     widget::Painter,
@@ -21,11 +24,12 @@ use crate::{
 };
 
 pub fn launch() -> Result<(), PlatformError> {
-    let config = Config::load();
+    let mut config = Config::load();
+    workspace::reopen(&mut config.data);
 
     let main_window = config
         .window
-        .apply(WindowDesc::new(build()))
+        .apply(WindowDesc::new(build(config.read_only)))
         .title(TITLE)
         .menu(menu::build)
         .with_min_size((407.0, 322.0))
@@ -43,11 +47,12 @@ struct State {
     sidebar: sidebar::ServiceListState,
     body: body::State,
     error: Option<ArcStr>,
+    workspace: workspace::State,
 }
 
 const TITLE: &str = "Lanquetta";
 
-fn build() -> impl Widget<State> {
+fn build(read_only: bool) -> impl Widget<State> {
     let sidebar = sidebar::build().lens(State::sidebar_lens());
     let body = body::build().lens(State::body);
 
@@ -86,7 +91,7 @@ fn build() -> impl Widget<State> {
     Flex::column()
         .with_child(error.lens(State::error))
         .with_flex_child(split, 1.0)
-        .controller(ConfigController::new())
+        .controller(ConfigController::new().read_only(read_only))
 }
 
 impl State {
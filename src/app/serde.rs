@@ -1,6 +1,9 @@
 use std::{
     collections::BTreeMap,
     convert::{TryFrom, TryInto},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use anyhow::{Context, Error, Result};
@@ -8,18 +11,26 @@ use druid::piet::TextStorage;
 use prost_reflect::{prost::Message, prost_types::FileDescriptorSet};
 use prost_reflect::{DescriptorPool, DynamicMessage, ReflectMessage};
 use serde::{
-    de::{self, Deserializer},
+    de::{self, DeserializeOwned, Deserializer},
     ser::{self, Serializer},
     Deserialize, Serialize,
 };
 
 use crate::{
     app,
-    json::JsonText,
+    error::fmt_err,
     widget::{TabId, TabsData},
 };
 
-use super::body::CompileOptions;
+mod secrets;
+mod store;
+
+pub(in crate::app) use self::{
+    secrets::Secrets,
+    store::{Store, StoredHistory},
+};
+
+use super::body::{Collections, CompileOptions, Environments, SavedRequest};
 
 impl Serialize for app::State {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -37,18 +48,40 @@ impl<'de> Deserialize<'de> for app::State {
     where
         D: Deserializer<'de>,
     {
-        AppState::deserialize(deserializer)?
+        let mut object = serde_json::Map::deserialize(deserializer)?;
+        migrate(&mut object).map_err(de::Error::custom)?;
+        AppState::deserialize(serde_json::Value::Object(object))
+            .map_err(de::Error::custom)?
             .try_into()
             .map_err(de::Error::custom)
     }
 }
 
+/// The current version of the schema. Files without a version were saved before it was added.
+const VERSION: u32 = 3;
+
+/// Functions which upgrade the state from each version of the schema to the next, indexed by the
+/// version they upgrade from.
+const MIGRATIONS: [fn(&mut serde_json::Map<String, serde_json::Value>); VERSION as usize] = [
+    migrate_auth_hook,
+    migrate_stored_files,
+    migrate_appended_history,
+];
+
 #[derive(Debug, Serialize, Deserialize)]
 struct AppState {
+    #[serde(default)]
+    version: u32,
     file_descriptor_sets: Vec<DescriptorPoolSerde>,
     services: Vec<AppServiceState>,
     body: AppBodyState,
     compile_options: CompileOptions,
+    #[serde(default)]
+    environments: Environments,
+    #[serde(default)]
+    collections: Collections,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workspace: Option<app::workspace::State>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -63,6 +96,11 @@ struct AppServiceState {
     idx: AppServiceRef,
     expanded: bool,
     options: app::sidebar::service::ServiceOptions,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    saved_requests: BTreeMap<String, Vec<SavedRequest>>,
+    /// The `.proto` file the service was compiled from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proto: Option<PathBuf>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -88,21 +126,62 @@ enum AppBodyTabKind {
         address: String,
         request: String,
         request_metadata: app::metadata::State,
-        stream: app::body::StreamState,
+        #[serde(default)]
+        captures: app::body::CaptureState,
+        #[serde(default)]
+        assertions: app::body::AssertionState,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        name: String,
+        /// The request history, unless it is stored in a separate file.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stream: Option<app::body::StreamState>,
+        /// The file the request history is stored in.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        history: Option<HistoryRef>,
         options: app::sidebar::service::ServiceOptions,
+        /// Options saved with the tab's request, which override the service's options.
+        #[serde(default, skip_serializing_if = "app::body::OptionsOverride::is_empty")]
+        saved_options: app::body::OptionsOverride,
     },
     Options {
         #[serde(flatten)]
         idx: AppServiceRef,
     },
     Compile,
+    Environment,
+    Collection,
     Reflection {
         options: app::sidebar::service::ServiceOptions,
     },
 }
 
 #[derive(Debug)]
-struct DescriptorPoolSerde(DescriptorPool);
+struct DescriptorPoolSerde {
+    pool: DescriptorPool,
+    /// The hash of the file the pool is stored in, if it is stored separately from the state.
+    stored: Option<String>,
+}
+
+/// A reference to a request history stored by [`Store`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum HistoryRef {
+    /// A file the items are appended to, of which the first `size` bytes belong to this history.
+    Appended {
+        file: String,
+        size: u64,
+        #[serde(flatten)]
+        summary: app::body::StreamSummary,
+    },
+    /// The hash of a file containing the whole history, as saved by version 2.
+    Hashed(String),
+}
+
+/// A reference to a file stored by [`Store`].
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredFile {
+    hash: String,
+}
 
 impl<'a> TryFrom<&'a app::State> for AppState {
     type Error = Error;
@@ -126,6 +205,8 @@ impl<'a> TryFrom<&'a app::State> for AppState {
                     },
                     expanded: service.expanded(),
                     options: service.options().clone(),
+                    saved_requests: service.saved_requests(),
+                    proto: service.proto().map(Path::to_owned),
                 })
             })
             .collect::<Result<Vec<_>>>()?;
@@ -155,8 +236,13 @@ impl<'a> TryFrom<&'a app::State> for AppState {
                                 address: method.address().text().to_owned(),
                                 request: method.request().text().as_str().to_owned(),
                                 request_metadata: method.request().serde_metadata(),
-                                stream: method.stream().clone(),
+                                captures: method.request().captures().to_state(),
+                                assertions: method.request().assertions().to_state(),
+                                name: method.name().to_owned(),
+                                stream: Some(method.stream().clone()),
+                                history: None,
                                 options: method.service_options().clone(),
+                                saved_options: method.options_override(),
                             }
                         }
                         app::body::TabState::Options(options) => {
@@ -173,6 +259,8 @@ impl<'a> TryFrom<&'a app::State> for AppState {
                             }
                         }
                         app::body::TabState::Compile(_) => AppBodyTabKind::Compile,
+                        app::body::TabState::Environment(_) => AppBodyTabKind::Environment,
+                        app::body::TabState::Collection(_) => AppBodyTabKind::Collection,
                         app::body::TabState::Reflection(options) => AppBodyTabKind::Reflection {
                             options: options.service_options(),
                         },
@@ -189,14 +277,18 @@ impl<'a> TryFrom<&'a app::State> for AppState {
 
         let file_descriptor_sets = file_descriptor_sets
             .into_iter()
-            .map(DescriptorPoolSerde)
+            .map(|pool| DescriptorPoolSerde { pool, stored: None })
             .collect();
 
         Ok(AppState {
+            version: VERSION,
             file_descriptor_sets,
             services,
             body,
             compile_options: data.sidebar.compile_options().clone(),
+            environments: data.sidebar.environments().clone(),
+            collections: data.sidebar.collections().clone(),
+            workspace: Some(data.workspace.clone()),
         })
     }
 }
@@ -206,102 +298,443 @@ impl TryInto<app::State> for AppState {
 
     fn try_into(self) -> Result<app::State, Self::Error> {
         let AppState {
+            version: _,
             file_descriptor_sets,
             services,
             body,
             compile_options,
+            environments,
+            collections,
+            workspace,
         } = self;
 
         let file_descriptor_sets: Vec<_> = file_descriptor_sets
             .into_iter()
-            .map(|serde| serde.0)
+            .map(|serde| serde.pool)
             .collect();
 
         let service_states = services
             .iter()
-            .map(|service| {
-                Ok(app::sidebar::service::ServiceState::new(
-                    get_service(&file_descriptor_sets, &service.idx)?,
-                    service.expanded,
-                    service.options.clone(),
-                ))
+            .map(|service| service.to_state(&file_descriptor_sets))
+            .collect::<Result<Vec<_>>>()?;
+
+        let tabs = body
+            .tabs
+            .into_iter()
+            .map(|tab| {
+                tab.kind.into_state(
+                    &file_descriptor_sets,
+                    &services,
+                    &compile_options,
+                    &environments,
+                    &collections,
+                )
             })
             .collect::<Result<Vec<_>>>()?;
 
-        Ok(app::State {
-            body: body.into_state(&file_descriptor_sets, &services, &compile_options)?,
-            sidebar: app::sidebar::ServiceListState::new(service_states, compile_options),
-            error: None,
-        })
+        Ok(new_state(
+            service_states,
+            tabs,
+            body.selected,
+            compile_options,
+            environments,
+            collections,
+            workspace.unwrap_or_default(),
+        ))
     }
 }
 
-impl AppBodyState {
+/// Loads as much as possible of a serialized state which failed to load, skipping any services
+/// and tabs which are invalid. Returns a warning for each part that was dropped or reset, or a
+/// [`NewerVersionError`] if the state was saved by a newer version of the application.
+pub(in crate::app) fn load_partial(value: serde_json::Value) -> Result<(app::State, Vec<String>)> {
+    let mut warnings = Vec::new();
+
+    let mut object = match value {
+        serde_json::Value::Object(object) => object,
+        _ => {
+            let warning = "the file does not contain an object, all settings were reset";
+            return Ok((app::State::default(), vec![warning.to_owned()]));
+        }
+    };
+    if let Err(err) = migrate(&mut object) {
+        if err.is::<NewerVersionError>() {
+            return Err(err);
+        }
+        warnings.push(fmt_err(&err).to_string());
+    }
+
+    let file_descriptor_sets: Vec<DescriptorPool> =
+        take_field::<Vec<serde_json::Value>>(&mut object, "file_descriptor_sets", &mut warnings)
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, value)| match DescriptorPoolSerde::deserialize(value) {
+                    Ok(serde) => serde.pool,
+                    Err(err) => {
+                        // Services referring to this file set will be dropped too.
+                        warnings.push(format!(
+                            "file descriptor set {} was dropped: {}",
+                            index, err
+                        ));
+                        DescriptorPool::new()
+                    }
+                },
+            )
+            .collect();
+
+    let mut services = Vec::new();
+    let mut service_states = Vec::new();
+    for value in take_field::<Vec<serde_json::Value>>(&mut object, "services", &mut warnings) {
+        let name = value["service"].as_str().unwrap_or("unknown").to_owned();
+        let result = AppServiceState::deserialize(value)
+            .map_err(Error::from)
+            .and_then(|service| Ok((service.to_state(&file_descriptor_sets)?, service)));
+        match result {
+            Ok((state, service)) => {
+                service_states.push(state);
+                services.push(service);
+            }
+            Err(err) => warnings.push(format!("service '{}' was dropped: {}", name, fmt_err(&err))),
+        }
+    }
+
+    let compile_options: CompileOptions = take_field(&mut object, "compile_options", &mut warnings);
+    let environments: Environments = take_field(&mut object, "environments", &mut warnings);
+    let collections: Collections = take_field(&mut object, "collections", &mut warnings);
+    let workspace: Option<app::workspace::State> =
+        take_field(&mut object, "workspace", &mut warnings);
+
+    let mut body: serde_json::Map<String, serde_json::Value> =
+        take_field(&mut object, "body", &mut warnings);
+    let body_selected: Option<usize> = take_field(&mut body, "selected", &mut warnings);
+    let mut tabs = Vec::new();
+    let mut selected = None;
+    for (index, value) in take_field::<Vec<serde_json::Value>>(&mut body, "tabs", &mut warnings)
+        .into_iter()
+        .enumerate()
+    {
+        let result = AppBodyTabState::deserialize(value)
+            .map_err(Error::from)
+            .and_then(|tab| {
+                tab.kind.into_state(
+                    &file_descriptor_sets,
+                    &services,
+                    &compile_options,
+                    &environments,
+                    &collections,
+                )
+            });
+        match result {
+            Ok(tab) => {
+                if body_selected == Some(index) {
+                    selected = Some(tabs.len());
+                }
+                tabs.push(tab);
+            }
+            Err(err) => warnings.push(format!("tab {} was dropped: {}", index + 1, fmt_err(&err))),
+        }
+    }
+
+    let state = new_state(
+        service_states,
+        tabs,
+        selected,
+        compile_options,
+        environments,
+        collections,
+        workspace.unwrap_or_default(),
+    );
+    Ok((state, warnings))
+}
+
+/// Removes a field from a serialized object, or returns the default value if it is missing or
+/// invalid.
+fn take_field<T>(
+    object: &mut serde_json::Map<String, serde_json::Value>,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> T
+where
+    T: DeserializeOwned + Default,
+{
+    match object.remove(name) {
+        None | Some(serde_json::Value::Null) => T::default(),
+        Some(value) => T::deserialize(value).unwrap_or_else(|err| {
+            warnings.push(format!("{} was reset: {}", name, err));
+            T::default()
+        }),
+    }
+}
+
+fn new_state(
+    services: Vec<app::sidebar::service::ServiceState>,
+    tabs: Vec<app::body::TabState>,
+    selected: Option<usize>,
+    compile_options: CompileOptions,
+    environments: Environments,
+    collections: Collections,
+    workspace: app::workspace::State,
+) -> app::State {
+    let tabs: BTreeMap<_, _> = tabs.into_iter().map(|tab| (TabId::next(), tab)).collect();
+    let selected = selected.and_then(|selected| tabs.keys().nth(selected).copied());
+
+    let mut body = app::body::State::new(tabs, selected);
+    let sidebar =
+        app::sidebar::ServiceListState::new(services, compile_options, environments, collections);
+    body.set_service_presets(sidebar.services());
+    body.set_environment(&sidebar.environments().active());
+
+    app::State {
+        body,
+        sidebar,
+        error: None,
+        workspace,
+    }
+}
+
+/// Upgrades a serialized state from an older version of the schema.
+fn migrate(object: &mut serde_json::Map<String, serde_json::Value>) -> Result<()> {
+    let version = match object.get("version") {
+        None => 0,
+        Some(version) => version.as_u64().context("invalid version")?,
+    };
+    check_version(version)?;
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(object);
+    }
+    object.insert("version".to_owned(), VERSION.into());
+    Ok(())
+}
+
+/// Checks that a file with the given schema version can be loaded by this version of the
+/// application.
+pub(in crate::app) fn check_version(version: u64) -> Result<(), NewerVersionError> {
+    if version > VERSION as u64 {
+        return Err(NewerVersionError { version });
+    }
+    Ok(())
+}
+
+/// The error for a file saved by a newer version of the application. The file must not be
+/// overwritten, since that would lose settings this version doesn't know about.
+#[derive(Debug)]
+pub(in crate::app) struct NewerVersionError {
+    version: u64,
+}
+
+impl fmt::Display for NewerVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the file was saved by a newer version of the application (schema version {})",
+            self.version
+        )
+    }
+}
+
+impl std::error::Error for NewerVersionError {}
+
+/// Renames the `auth_hook` field of service options to `auth`, since they may now hold other kinds
+/// of authorization.
+fn migrate_auth_hook(object: &mut serde_json::Map<String, serde_json::Value>) {
+    fn rename(options: Option<&mut serde_json::Value>) {
+        if let Some(options) = options.and_then(|options| options.as_object_mut()) {
+            if let Some(hook) = options.remove("auth_hook") {
+                options.entry("auth").or_insert(hook);
+            }
+        }
+    }
+
+    if let Some(services) = object.get_mut("services").and_then(|v| v.as_array_mut()) {
+        for service in services {
+            rename(service.get_mut("options"));
+        }
+    }
+    if let Some(tabs) = object
+        .get_mut("body")
+        .and_then(|body| body.get_mut("tabs"))
+        .and_then(|tabs| tabs.as_array_mut())
+    {
+        for tab in tabs {
+            rename(tab.get_mut("options"));
+        }
+    }
+}
+
+/// Version 2 allows descriptor sets and request history to be stored in separate files, which
+/// doesn't change files saved by earlier versions.
+fn migrate_stored_files(_: &mut serde_json::Map<String, serde_json::Value>) {}
+
+/// Version 3 appends the items of request history to a file instead of rewriting it on every save.
+/// Files saved by version 2 are still read, and rewritten the first time they are saved.
+fn migrate_appended_history(_: &mut serde_json::Map<String, serde_json::Value>) {}
+
+/// Serializes the state as a workspace file, which doesn't include the application's own settings.
+/// Unless `include_secrets` is set, secrets are left out of the file and returned separately.
+pub(in crate::app) fn workspace_to_string(
+    data: &app::State,
+    include_secrets: bool,
+    include_history: bool,
+) -> Result<(String, Secrets)> {
+    let mut state = AppState::try_from(data)?;
+    state.workspace = None;
+
+    // Leave out state which only affects the view, so that it doesn't cause the file to change.
+    state.body.selected = None;
+    for service in &mut state.services {
+        service.expanded = false;
+    }
+    for tab in &mut state.body.tabs {
+        if let AppBodyTabKind::Method { stream, .. } = &mut tab.kind {
+            if include_history {
+                if let Some(stream) = stream {
+                    stream.collapse_all();
+                }
+            } else {
+                *stream = None;
+            }
+        }
+    }
+
+    let secrets = if include_secrets {
+        Secrets::default()
+    } else {
+        let full = serde_json::to_value(&state)?;
+        redact_secrets(&mut state);
+        Secrets::split(&full, &serde_json::to_value(&state)?)
+    };
+
+    Ok((serde_json::to_string_pretty(&state)?, secrets))
+}
+
+/// Removes authorization settings, captured variables and the values of metadata and variables
+/// with secret names.
+fn redact_secrets(state: &mut AppState) {
+    for service in &mut state.services {
+        redact_options(&mut service.options);
+        for saved in service.saved_requests.values_mut().flatten() {
+            app::metadata::redact_secrets(&mut saved.metadata);
+            saved.options.redact_secrets();
+        }
+    }
+    for tab in &mut state.body.tabs {
+        match &mut tab.kind {
+            AppBodyTabKind::Method {
+                request_metadata,
+                stream,
+                options,
+                saved_options,
+                ..
+            } => {
+                app::metadata::redact_secrets(request_metadata);
+                if let Some(stream) = stream {
+                    stream.redact_secrets();
+                }
+                redact_options(options);
+                saved_options.redact_secrets();
+            }
+            AppBodyTabKind::Reflection { options } => redact_options(options),
+            _ => (),
+        }
+    }
+    state.environments.redact_secrets();
+}
+
+/// Removes the authorization settings of a service, and the values of its default metadata with
+/// secret names.
+fn redact_options(options: &mut app::sidebar::service::ServiceOptions) {
+    options.auth = None;
+    app::metadata::redact_secrets(&mut options.default_metadata);
+}
+
+impl AppServiceState {
+    fn to_state(
+        &self,
+        file_sets: &[prost_reflect::DescriptorPool],
+    ) -> Result<app::sidebar::service::ServiceState> {
+        let mut state = app::sidebar::service::ServiceState::new(
+            get_service(file_sets, &self.idx)?,
+            self.expanded,
+            self.options.clone(),
+        )
+        .with_proto(self.proto.clone());
+        state.set_saved_requests(&self.saved_requests);
+        Ok(state)
+    }
+}
+
+impl AppBodyTabKind {
     fn into_state(
         self,
         file_sets: &[prost_reflect::DescriptorPool],
         services: &[AppServiceState],
         compile_options: &CompileOptions,
-    ) -> Result<app::body::State> {
-        let tabs = self
-            .tabs
-            .into_iter()
-            .map(|tab| match tab.kind {
-                AppBodyTabKind::Method {
-                    idx,
+        environments: &Environments,
+        collections: &Collections,
+    ) -> Result<app::body::TabState> {
+        match self {
+            AppBodyTabKind::Method {
+                idx,
+                method,
+                address,
+                request,
+                request_metadata,
+                captures,
+                assertions,
+                name,
+                stream,
+                history,
+                options,
+                saved_options,
+            } => {
+                let method = get_service(file_sets, &idx)?
+                    .methods()
+                    .nth(method)
+                    .context("invalid method index")?;
+                let stream = match (stream, history) {
+                    (Some(stream), _) => stream,
+                    (None, Some(history)) => store::read_history(history).unwrap_or_else(|err| {
+                        tracing::warn!("Failed to load request history: {:?}", err);
+                        app::body::StreamState::new()
+                    }),
+                    (None, None) => app::body::StreamState::new(),
+                };
+                Ok(app::body::TabState::new_method(
                     method,
-                    address,
-                    request,
-                    request_metadata,
+                    SavedRequest {
+                        name: Arc::new(name),
+                        address: Arc::new(address),
+                        request: Arc::new(request),
+                        metadata: request_metadata,
+                        captures,
+                        assertions,
+                        options: saved_options,
+                    },
                     stream,
                     options,
-                } => {
-                    let method = get_service(file_sets, &idx)?
-                        .methods()
-                        .nth(method)
-                        .context("invalid method index")?;
-                    Ok((
-                        TabId::next(),
-                        app::body::TabState::new_method(
-                            method,
-                            address,
-                            JsonText::pretty(request),
-                            request_metadata,
-                            stream,
-                            options,
-                        ),
-                    ))
-                }
-                AppBodyTabKind::Options { idx } => {
-                    let service = get_service(file_sets, &idx)?;
-
-                    let options = services
-                        .iter()
-                        .find(|s| s.idx == idx)
-                        .context("options tab has no associated service")?
-                        .options
-                        .clone();
-
-                    Ok((
-                        TabId::next(),
-                        app::body::TabState::new_options(service, options),
-                    ))
-                }
-                AppBodyTabKind::Compile => Ok((
-                    TabId::next(),
-                    app::body::TabState::new_compile(compile_options),
-                )),
-                AppBodyTabKind::Reflection { options } => {
-                    Ok((TabId::next(), app::body::TabState::new_reflection(options)))
-                }
-            })
-            .collect::<Result<BTreeMap<_, _>>>()?;
-
-        let selected = self
-            .selected
-            .and_then(|selected| tabs.iter().nth(selected).map(|(&id, _)| id));
-
-        Ok(app::body::State::new(tabs, selected))
+                ))
+            }
+            AppBodyTabKind::Options { idx } => {
+                let service = get_service(file_sets, &idx)?;
+
+                let options = services
+                    .iter()
+                    .find(|s| s.idx == idx)
+                    .context("options tab has no associated service")?
+                    .options
+                    .clone();
+
+                Ok(app::body::TabState::new_options(service, options))
+            }
+            AppBodyTabKind::Compile => Ok(app::body::TabState::new_compile(compile_options)),
+            AppBodyTabKind::Environment => Ok(app::body::TabState::new_environment(environments)),
+            AppBodyTabKind::Collection => Ok(app::body::TabState::new_collection(collections)),
+            AppBodyTabKind::Reflection { options } => {
+                Ok(app::body::TabState::new_reflection(options))
+            }
+        }
     }
 }
 
@@ -310,7 +743,11 @@ impl Serialize for DescriptorPoolSerde {
     where
         S: Serializer,
     {
-        let bytes = self.0.encode_to_vec();
+        if let Some(hash) = &self.stored {
+            return StoredFile { hash: hash.clone() }.serialize(serializer);
+        }
+
+        let bytes = self.pool.encode_to_vec();
         let mut dynamic =
             DynamicMessage::decode(FileDescriptorSet::default().descriptor(), bytes.as_slice())
                 .map_err(<S::Error as serde::ser::Error>::custom)?;
@@ -336,13 +773,26 @@ impl<'de> Deserialize<'de> for DescriptorPoolSerde {
     where
         D: Deserializer<'de>,
     {
-        let dynamic =
-            DynamicMessage::deserialize(FileDescriptorSet::default().descriptor(), deserializer)?;
-        let bytes = dynamic.encode_to_vec();
-        Ok(DescriptorPoolSerde(
-            DescriptorPool::decode(bytes.as_slice())
-                .map_err(<D::Error as serde::de::Error>::custom)?,
-        ))
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Stored(StoredFile),
+            Inline(serde_json::Value),
+        }
+
+        let pool = match Repr::deserialize(deserializer)? {
+            Repr::Stored(StoredFile { hash }) => {
+                store::read_descriptors(&hash).map_err(<D::Error as de::Error>::custom)?
+            }
+            Repr::Inline(value) => {
+                let dynamic =
+                    DynamicMessage::deserialize(FileDescriptorSet::default().descriptor(), value)
+                        .map_err(<D::Error as de::Error>::custom)?;
+                let bytes = dynamic.encode_to_vec();
+                DescriptorPool::decode(bytes.as_slice()).map_err(<D::Error as de::Error>::custom)?
+            }
+        };
+        Ok(DescriptorPoolSerde { pool, stored: None })
     }
 }
 
@@ -370,3 +820,123 @@ fn get_service(
         .find(|s| s.full_name() == idx.service)
         .context("invalid service index")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthProvider;
+
+    const CONFIG_V0: &str = include_str!("testdata/config-v0.json");
+    const CONFIG_PARTIAL: &str = include_str!("testdata/config-partial.json");
+
+    fn hook_shell(options: &app::sidebar::service::ServiceOptions) -> Option<&str> {
+        match options.auth.as_deref() {
+            Some(AuthProvider::Hook(hook)) => Some(hook.shell()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn migrate_renames_auth_hook() {
+        let mut object: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(CONFIG_V0).unwrap();
+        migrate(&mut object).unwrap();
+
+        assert_eq!(object["version"], VERSION);
+        let service_options = &object["services"][0]["options"];
+        let tab_options = &object["body"]["tabs"][0]["options"];
+        for options in [service_options, tab_options] {
+            assert!(options.get("auth_hook").is_none());
+            assert_eq!(options["auth"], "print-token --service echo");
+        }
+    }
+
+    #[test]
+    fn migrate_keeps_existing_auth() {
+        let mut object: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(CONFIG_V0).unwrap();
+        object["services"][0]["options"]["auth"] = "print-token --new".into();
+        migrate(&mut object).unwrap();
+
+        let options = &object["services"][0]["options"];
+        assert!(options.get("auth_hook").is_none());
+        assert_eq!(options["auth"], "print-token --new");
+    }
+
+    #[test]
+    fn load_v0_config() {
+        let state: app::State = serde_json::from_str(CONFIG_V0).unwrap();
+
+        let services = state.sidebar.services();
+        assert_eq!(services.len(), 1);
+        assert_eq!(
+            hook_shell(services[0].options()),
+            Some("print-token --service echo")
+        );
+
+        let tabs: Vec<_> = state.body.tabs().collect();
+        assert_eq!(tabs.len(), 1);
+        match tabs[0].1 {
+            app::body::TabState::Method(method) => assert_eq!(
+                hook_shell(method.service_options()),
+                Some("print-token --service echo")
+            ),
+            _ => panic!("expected a method tab"),
+        }
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let mut object: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(CONFIG_V0).unwrap();
+        object.insert("version".to_owned(), (VERSION + 1).into());
+
+        let err = migrate(&mut object.clone()).unwrap_err();
+        assert!(err.to_string().contains("newer version"));
+
+        let err = serde_json::from_value::<app::State>(object.into()).unwrap_err();
+        assert!(err.to_string().contains("newer version"));
+    }
+
+    #[test]
+    fn load_partial_drops_invalid_service_and_tab() {
+        let value: serde_json::Value = serde_json::from_str(CONFIG_PARTIAL).unwrap();
+        let (state, warnings) = load_partial(value).unwrap();
+
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[0].starts_with("service 'echo.Missing' was dropped"));
+        assert!(warnings[1].starts_with("tab 2 was dropped"));
+
+        let services = state.sidebar.services();
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].service().full_name(), "echo.Echo");
+
+        let tabs: Vec<_> = state.body.tabs().collect();
+        assert_eq!(tabs.len(), 2);
+        assert!(matches!(tabs[0].1, app::body::TabState::Method(_)));
+        assert!(matches!(tabs[1].1, app::body::TabState::Compile(_)));
+        assert_eq!(state.body.selected_tab(), Some(tabs[1].0));
+    }
+
+    #[test]
+    fn workspace_leaves_out_view_state() {
+        let state: app::State = serde_json::from_str(CONFIG_V0).unwrap();
+        assert!(state.sidebar.services()[0].expanded());
+        assert!(state.body.selected_tab().is_some());
+
+        let (text, _) = workspace_to_string(&state, true, true).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+
+        assert_eq!(value["services"][0]["expanded"], false);
+        assert!(value["body"]["selected"].is_null());
+    }
+
+    #[test]
+    fn load_partial_rejects_newer_version() {
+        let mut value: serde_json::Value = serde_json::from_str(CONFIG_PARTIAL).unwrap();
+        value["version"] = (VERSION + 1).into();
+        let err = load_partial(value).unwrap_err();
+
+        assert!(err.is::<NewerVersionError>(), "{:?}", err);
+    }
+}
@@ -0,0 +1,245 @@
+//! Secrets which are left out of workspace files. They are kept in the config file instead, and
+//! restored when the workspace is opened, so that leaving them out of a shared file doesn't lose
+//! them.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Values removed from a serialized state because they are secrets, with where each was found.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(in crate::app) struct Secrets(Vec<Secret>);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Secret {
+    /// The names of object fields, and the ids of array elements as given by [`element_id`].
+    path: Vec<String>,
+    value: Value,
+}
+
+/// Fields which identify an element of an array, so that a secret is restored to the same element
+/// even if others were added or moved.
+const ID_FIELDS: &[&str] = &["key", "name", "service"];
+
+impl Secrets {
+    /// Finds the values which differ between a serialized state and the same state with its
+    /// secrets redacted.
+    pub fn split(full: &Value, redacted: &Value) -> Self {
+        let mut secrets = Vec::new();
+        diff(full, redacted, &mut Vec::new(), &mut secrets);
+        Secrets(secrets)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Puts the secrets back into a serialized state. A secret is only restored where the value is
+    /// still empty, so values filled in since the secrets were split off are kept.
+    pub fn restore(&self, value: &mut Value) {
+        for secret in &self.0 {
+            if let Some(target) = resolve(value, &secret.path) {
+                if is_empty(target) {
+                    *target = secret.value.clone();
+                }
+            }
+        }
+    }
+}
+
+fn diff(full: &Value, redacted: &Value, path: &mut Vec<String>, secrets: &mut Vec<Secret>) {
+    if full == redacted {
+        return;
+    }
+
+    match (full, redacted) {
+        (Value::Object(full), Value::Object(redacted)) => {
+            for (key, value) in full {
+                path.push(key.clone());
+                match redacted.get(key) {
+                    Some(redacted) => diff(value, redacted, path, secrets),
+                    None => secrets.push(Secret {
+                        path: path.clone(),
+                        value: value.clone(),
+                    }),
+                }
+                path.pop();
+            }
+        }
+        (Value::Array(full), Value::Array(redacted)) if full.len() == redacted.len() => {
+            for (index, (value, redacted_value)) in full.iter().zip(redacted).enumerate() {
+                path.push(element_id(redacted, index));
+                diff(value, redacted_value, path, secrets);
+                path.pop();
+            }
+        }
+        _ => secrets.push(Secret {
+            path: path.clone(),
+            value: full.clone(),
+        }),
+    }
+}
+
+/// Gets the value at a path, adding a missing object field at the end of it.
+fn resolve<'a>(mut value: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    for (index, segment) in path.iter().enumerate() {
+        value = match value {
+            Value::Object(object) if index + 1 == path.len() => {
+                object.entry(segment.clone()).or_insert(Value::Null)
+            }
+            Value::Object(object) => object.get_mut(segment)?,
+            Value::Array(array) => find_element(array, segment)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Identifies an element of an array by the first of [`ID_FIELDS`] it has and the number of
+/// earlier elements with the same value, such as `key=authorization#0`, or else by its index, such
+/// as `#2`.
+fn element_id(array: &[Value], index: usize) -> String {
+    match id(&array[index]) {
+        Some(id) => {
+            let count = array[..index]
+                .iter()
+                .filter(|value| self::id(value).as_ref() == Some(&id))
+                .count();
+            format!("{}#{}", id, count)
+        }
+        None => format!("#{}", index),
+    }
+}
+
+fn find_element<'a>(array: &'a mut [Value], element_id: &str) -> Option<&'a mut Value> {
+    let (element_id, count) = element_id.rsplit_once('#')?;
+    let count: usize = count.parse().ok()?;
+    if element_id.is_empty() {
+        return array.get_mut(count);
+    }
+
+    array
+        .iter_mut()
+        .filter(|value| id(value).as_deref() == Some(element_id))
+        .nth(count)
+}
+
+fn id(value: &Value) -> Option<String> {
+    ID_FIELDS
+        .iter()
+        .find_map(|field| Some(format!("{}={}", field, value.get(field)?.as_str()?)))
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(string) => string.is_empty(),
+        Value::Array(array) => array.is_empty(),
+        Value::Object(object) => object.is_empty(),
+        Value::Bool(_) | Value::Number(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn full() -> Value {
+        json!({
+            "services": [{
+                "service": "shop.Orders",
+                "options": {
+                    "auth": "print-token",
+                    "default_metadata": [
+                        { "key": "x-request-source", "value": "app" },
+                        { "key": "authorization", "value": "Bearer abc" }
+                    ]
+                }
+            }],
+            "environments": {
+                "captured": [{ "name": "token", "value": "xyz" }]
+            }
+        })
+    }
+
+    fn redacted() -> Value {
+        json!({
+            "services": [{
+                "service": "shop.Orders",
+                "options": {
+                    "auth": null,
+                    "default_metadata": [
+                        { "key": "x-request-source", "value": "app" },
+                        { "key": "authorization", "value": "" }
+                    ]
+                }
+            }],
+            "environments": {
+                "captured": []
+            }
+        })
+    }
+
+    #[test]
+    fn split_and_restore() {
+        let secrets = Secrets::split(&full(), &redacted());
+        assert_eq!(secrets.0.len(), 3);
+
+        let mut value = redacted();
+        secrets.restore(&mut value);
+        assert_eq!(value, full());
+    }
+
+    #[test]
+    fn restore_after_reordering() {
+        let secrets = Secrets::split(&full(), &redacted());
+
+        let mut value = redacted();
+        let metadata = value["services"][0]["options"]["default_metadata"]
+            .as_array_mut()
+            .unwrap();
+        metadata.reverse();
+        metadata.push(json!({ "key": "x-trace", "value": "" }));
+        value["services"].as_array_mut().unwrap().insert(
+            0,
+            json!({ "service": "shop.Users", "options": { "auth": null } }),
+        );
+        secrets.restore(&mut value);
+
+        assert_eq!(value["services"][0]["options"]["auth"], Value::Null);
+        let options = &value["services"][1]["options"];
+        assert_eq!(options["auth"], "print-token");
+        assert_eq!(options["default_metadata"][0]["value"], "Bearer abc");
+        assert_eq!(options["default_metadata"][2]["value"], "");
+    }
+
+    #[test]
+    fn keep_values_filled_in() {
+        let secrets = Secrets::split(&full(), &redacted());
+
+        let mut value = redacted();
+        value["services"][0]["options"]["default_metadata"][1]["value"] = "Bearer new".into();
+        secrets.restore(&mut value);
+
+        assert_eq!(
+            value["services"][0]["options"]["default_metadata"][1]["value"],
+            "Bearer new"
+        );
+    }
+
+    #[test]
+    fn ignore_missing_paths() {
+        let secrets = Secrets::split(&full(), &redacted());
+
+        let mut value = json!({ "services": [] });
+        secrets.restore(&mut value);
+        assert_eq!(value, json!({ "services": [] }));
+    }
+
+    #[test]
+    fn nothing_to_split() {
+        assert!(Secrets::split(&full(), &full()).is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+//! Storage for the parts of the config which are large. Descriptor pools are written to separate
+//! files named by the hash of their contents, and the items of each tab's request history are
+//! appended to a file as they are added, so saving the config only writes what changed.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Result};
+use prost_reflect::{prost::Message, prost_types::FileDescriptorSet, DescriptorPool};
+use rand::Rng;
+use ring::digest;
+
+use crate::{
+    app::{self, config::Config},
+    widget::TabId,
+};
+
+use super::{AppBodyTabKind, AppState, HistoryRef};
+
+/// Serializes the state for the config file, remembering what was stored so that unchanged
+/// descriptor pools are neither serialized nor written again, and only new history is written.
+#[derive(Debug)]
+pub(in crate::app) struct Store {
+    pools: Vec<(DescriptorPool, String)>,
+    history: BTreeMap<TabId, Arc<StoredHistory>>,
+    /// Whether any stored files may have stopped being used since unused files were last removed.
+    has_unused: bool,
+    /// The stored files used by each backup, which never change once they are written.
+    backups: HashMap<PathBuf, Vec<String>>,
+}
+
+/// A request history whose items were written to a file.
+#[derive(Debug)]
+pub(in crate::app) struct StoredHistory {
+    file: String,
+    /// The length of the file when the history was written. If the file is longer now, the rest
+    /// belongs to another version of the history, such as the one in a newer backup, so the file
+    /// is left alone.
+    size: u64,
+    stream: app::body::StreamState,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store {
+            pools: Vec::new(),
+            history: BTreeMap::new(),
+            has_unused: true,
+            backups: HashMap::new(),
+        }
+    }
+}
+
+impl Store {
+    /// Serializes the state, with references to its descriptor pools and the history of its tabs,
+    /// which are written to separate files if they have changed.
+    pub fn serialize(&mut self, data: &app::State) -> Result<serde_json::Value> {
+        let mut state = AppState::try_from(data)?;
+
+        let mut pools = Vec::with_capacity(state.file_descriptor_sets.len());
+        for file_set in &mut state.file_descriptor_sets {
+            let hash = match self.pools.iter().find(|(pool, _)| pool == &file_set.pool) {
+                Some((_, hash)) => hash.clone(),
+                None => write_descriptors(&file_set.pool)?,
+            };
+            file_set.stored = Some(hash.clone());
+            pools.push((file_set.pool.clone(), hash));
+        }
+
+        let mut history = BTreeMap::new();
+        for ((id, _), tab) in data.body.tabs().zip(&mut state.body.tabs) {
+            if let AppBodyTabKind::Method {
+                stream,
+                history: stored,
+                ..
+            } = &mut tab.kind
+            {
+                let stream = stream.take().unwrap_or_default();
+                if stream.item_count() == 0 {
+                    continue;
+                }
+
+                // Tabs loaded from the config file haven't been stored by this store yet, but
+                // remember the file they were loaded from.
+                let prev = self.history.get(&id).or_else(|| stream.stored()).cloned();
+                let history_file = write_history(prev.as_deref(), stream)?;
+                *stored = Some(HistoryRef::Appended {
+                    file: history_file.file.clone(),
+                    size: history_file.size,
+                    summary: history_file.stream.summary(),
+                });
+                history.insert(id, history_file);
+            }
+        }
+
+        let names: HashSet<&str> = pools
+            .iter()
+            .map(|(_, hash)| hash.as_str())
+            .chain(history.values().map(|history| history.file.as_str()))
+            .collect();
+        self.has_unused |= self
+            .pools
+            .iter()
+            .map(|(_, hash)| hash)
+            .chain(self.history.values().map(|history| &history.file))
+            .any(|name| !names.contains(name.as_str()));
+
+        self.pools = pools;
+        self.history = history;
+        Ok(serde_json::to_value(&state)?)
+    }
+
+    /// Removes stored files which are not used by the last serialized state, any backup or any
+    /// copy of a config file which could only be partly loaded.
+    pub fn remove_unused(&mut self) -> Result<()> {
+        if !self.has_unused {
+            return Ok(());
+        }
+
+        let mut used: HashSet<String> = self
+            .pools
+            .iter()
+            .map(|(_, hash)| hash.clone())
+            .chain(self.history.values().map(|history| history.file.clone()))
+            .collect();
+        let backups: Vec<PathBuf> = Config::backups()?
+            .into_iter()
+            .chain(Config::broken_copies()?)
+            .collect();
+        self.backups.retain(|path, _| backups.contains(path));
+        for backup in backups {
+            if !self.backups.contains_key(&backup) {
+                let references = references(&backup)?;
+                self.backups.insert(backup.clone(), references);
+            }
+            used.extend(self.backups[&backup].iter().cloned());
+        }
+
+        for dir in [
+            Config::descriptor_directory()?,
+            Config::history_directory()?,
+        ] {
+            let entries = match fs_err::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            for entry in entries {
+                let path = entry?.path();
+                let is_used = path
+                    .file_stem()
+                    .and_then(OsStr::to_str)
+                    .map_or(true, |hash| used.contains(hash));
+                if !is_used {
+                    tracing::debug!("Removing unused file `{}`", path.display());
+                    fs_err::remove_file(&path)?;
+                }
+            }
+        }
+
+        self.has_unused = false;
+        Ok(())
+    }
+}
+
+pub(super) fn read_descriptors(hash: &str) -> Result<DescriptorPool> {
+    let bytes = fs_err::read(Config::descriptor_directory()?.join(file_name(hash, "bin")?))?;
+    Ok(DescriptorPool::decode(bytes.as_slice())?)
+}
+
+pub(super) fn read_history(history: HistoryRef) -> Result<app::body::StreamState> {
+    let dir = Config::history_directory()?;
+    match history {
+        HistoryRef::Appended {
+            file,
+            size,
+            summary,
+        } => {
+            let reader = fs_err::File::open(dir.join(file_name(&file, "jsonl")?))?.take(size);
+            let mut stream = app::body::StreamState::from_parts(summary, BufReader::new(reader))?;
+            stream.set_stored(Arc::new(StoredHistory {
+                file,
+                size,
+                stream: stream.clone(),
+            }));
+            Ok(stream)
+        }
+        HistoryRef::Hashed(hash) => {
+            let text = fs_err::read_to_string(dir.join(file_name(&hash, "json")?))?;
+            Ok(serde_json::from_str(&text)?)
+        }
+    }
+}
+
+fn write_descriptors(pool: &DescriptorPool) -> Result<String> {
+    let mut file_set = FileDescriptorSet::decode(pool.encode_to_vec().as_slice())?;
+    for file in &mut file_set.file {
+        // We don't use source code info and it bloats the stored file.
+        file.source_code_info = None;
+    }
+    write_file(
+        &Config::descriptor_directory()?,
+        "bin",
+        &file_set.encode_to_vec(),
+    )
+}
+
+/// Stores the items of a request history, appending them to the file it was previously stored in
+/// if it still begins with the same items, or writing them to a new file otherwise.
+fn write_history(
+    prev: Option<&StoredHistory>,
+    stream: app::body::StreamState,
+) -> Result<Arc<StoredHistory>> {
+    let dir = Config::history_directory()?;
+
+    if let Some(prev) = prev.filter(|prev| stream.starts_with(&prev.stream)) {
+        let start = prev.stream.item_count();
+        let mut items = Vec::new();
+        stream.write_items(start, &mut items)?;
+        if append_file(
+            &dir.join(file_name(&prev.file, "jsonl")?),
+            prev.size,
+            &items,
+        )? {
+            return Ok(Arc::new(StoredHistory {
+                file: prev.file.clone(),
+                size: prev.size + items.len() as u64,
+                stream,
+            }));
+        }
+    }
+
+    let mut items = Vec::new();
+    stream.write_items(0, &mut items)?;
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    let file: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    fs_err::create_dir_all(&dir)?;
+    Config::write_file(&dir.join(file_name(&file, "jsonl")?), &items)?;
+    Ok(Arc::new(StoredHistory {
+        file,
+        size: items.len() as u64,
+        stream,
+    }))
+}
+
+/// Appends to a file if it still has the given length. Returns `false` if it doesn't, or no
+/// longer exists, in which case it is left alone.
+fn append_file(path: &Path, size: u64, contents: &[u8]) -> Result<bool> {
+    let mut file = match fs_err::OpenOptions::new().append(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    if file.metadata()?.len() != size {
+        return Ok(false);
+    }
+    if !contents.is_empty() {
+        file.write_all(contents)?;
+        file.sync_data()?;
+    }
+    Ok(true)
+}
+
+/// Writes a file named by the hash of its contents, unless it already exists, and returns the
+/// hash.
+fn write_file(dir: &Path, extension: &str, contents: &[u8]) -> Result<String> {
+    let hash: String = digest::digest(&digest::SHA256, contents)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let path = dir.join(file_name(&hash, extension)?);
+    if !path.exists() {
+        fs_err::create_dir_all(dir)?;
+        Config::write_file(&path, contents)?;
+        tracing::debug!("Stored `{}`", path.display());
+    }
+    Ok(hash)
+}
+
+fn file_name(hash: &str, extension: &str) -> Result<String> {
+    // Hashes are read from config and workspace files, so make sure they can't refer to other
+    // files.
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("invalid hash '{}'", hash);
+    }
+    Ok(format!("{}.{}", hash, extension))
+}
+
+/// Gets the hashes of the stored files used by a config file.
+fn references(path: &Path) -> Result<Vec<String>> {
+    let value: serde_json::Value = serde_json::from_str(&fs_err::read_to_string(path)?)?;
+    let data = &value["data"];
+
+    let pools = data["file_descriptor_sets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|file_set| file_set["hash"].as_str());
+    let history = data["body"]["tabs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tab| tab["history"]["file"].as_str().or(tab["history"].as_str()));
+    Ok(pools.chain(history).map(str::to_owned).collect())
+}
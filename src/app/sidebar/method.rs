@@ -1,10 +1,17 @@
+use std::sync::Arc;
+
 use druid::{
     widget::LineBreaking,
-    widget::{Flex, Label, ViewSwitcher},
+    widget::{CrossAxisAlignment, Flex, Label, List, ViewSwitcher},
     ArcStr, Data, Lens, Widget, WidgetExt as _,
 };
 
-use crate::{app::command, grpc::MethodKind, lens, theme, widget::Icon};
+use crate::{
+    app::{body::SavedRequest, command},
+    grpc::MethodKind,
+    lens, theme,
+    widget::Icon,
+};
 
 #[derive(Debug, Clone, Data, Lens)]
 pub(in crate::app) struct State {
@@ -16,12 +23,17 @@ pub(in crate::app) struct State {
 pub(in crate::app) struct MethodState {
     #[data(same_fn = "PartialEq::eq")]
     method: prost_reflect::MethodDescriptor,
+    saved: Arc<Vec<SavedRequest>>,
 }
 
+/// The kind of a method, which picks the icon shown next to it.
+#[derive(Debug, Copy, Clone, Data)]
+struct Kind(#[data(same_fn = "PartialEq::eq")] MethodKind);
+
 pub(in crate::app) fn build() -> impl Widget<State> {
     let kind = ViewSwitcher::new(
-        |data: &MethodState, _| MethodKind::for_method(&data.method),
-        |&kind: &MethodKind, _, _| match kind {
+        |data: &MethodState, _| Kind(MethodKind::for_method(&data.method)),
+        |&Kind(kind): &Kind, _, _| match kind {
             MethodKind::Unary => Icon::unary().boxed(),
             MethodKind::ClientStreaming => Icon::client_streaming().boxed(),
             MethodKind::ServerStreaming => Icon::server_streaming().boxed(),
@@ -55,9 +67,18 @@ pub(in crate::app) fn build() -> impl Widget<State> {
         })
         .padding((6.0, 3.0));
 
-    Flex::row()
-        .with_flex_child(icon_and_label, 1.0)
-        .with_child(add)
+    let saved = List::new(build_saved_request).lens(lens::Project::new(|data: &State| {
+        (data.method.clone(), data.method.saved.clone())
+    }));
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(
+            Flex::row()
+                .with_flex_child(icon_and_label, 1.0)
+                .with_child(add),
+        )
+        .with_child(saved)
         .background(druid::theme::BACKGROUND_LIGHT)
         .env_scope(|env, data| {
             if data.selected {
@@ -72,6 +93,37 @@ pub(in crate::app) fn build() -> impl Widget<State> {
         })
 }
 
+fn build_saved_request() -> impl Widget<(MethodState, SavedRequest)> {
+    let label = Label::dynamic(|(_, request): &(MethodState, SavedRequest), _| {
+        request.name.as_ref().clone()
+    })
+    .with_line_break_mode(LineBreaking::Clip)
+    .padding((36.0, 3.0, 8.0, 3.0))
+    .expand_width()
+    .background(theme::hot_or_active_painter(0.0))
+    .on_click(
+        |ctx, (method, request): &mut (MethodState, SavedRequest), _| {
+            ctx.submit_command(
+                command::SELECT_OR_CREATE_SAVED_REQUEST_TAB
+                    .with((method.method.clone(), request.clone())),
+            );
+        },
+    );
+
+    let delete = Icon::close()
+        .button(
+            |ctx, (method, request): &mut (MethodState, SavedRequest), _| {
+                ctx.submit_command(
+                    command::DELETE_SAVED_REQUEST
+                        .with((method.method.clone(), request.name.clone())),
+                );
+            },
+        )
+        .padding((6.0, 3.0));
+
+    Flex::row().with_flex_child(label, 1.0).with_child(delete)
+}
+
 impl State {
     pub fn new(selected: bool, method: MethodState) -> Self {
         State { selected, method }
@@ -86,10 +138,34 @@ impl MethodState {
     pub fn method(&self) -> &prost_reflect::MethodDescriptor {
         &self.method
     }
+
+    pub fn saved(&self) -> &[SavedRequest] {
+        &self.saved
+    }
+
+    pub fn set_saved(&mut self, saved: Vec<SavedRequest>) {
+        self.saved = Arc::new(saved);
+    }
+
+    /// Saves a request, replacing any existing request with the same name.
+    pub fn save(&mut self, request: SavedRequest) {
+        let saved = Arc::make_mut(&mut self.saved);
+        match saved.iter_mut().find(|saved| saved.name == request.name) {
+            Some(existing) => *existing = request,
+            None => saved.push(request),
+        }
+    }
+
+    pub fn delete(&mut self, name: &str) {
+        Arc::make_mut(&mut self.saved).retain(|saved| saved.name.as_str() != name);
+    }
 }
 
 impl From<prost_reflect::MethodDescriptor> for MethodState {
     fn from(method: prost_reflect::MethodDescriptor) -> Self {
-        MethodState { method }
+        MethodState {
+            method,
+            saved: Arc::default(),
+        }
     }
 }
@@ -1,7 +1,10 @@
 mod method;
 pub(in crate::app) mod service;
 
-use std::{iter::FromIterator, path::Path};
+use std::{
+    iter::FromIterator,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 use druid::{
@@ -9,13 +12,13 @@ use druid::{
     widget::{Flex, Label, LineBreaking, List, ListIter, MainAxisAlignment},
     Data, Lens, Widget, WidgetExt as _,
 };
-use prost_reflect::ServiceDescriptor;
+use prost_reflect::{MethodDescriptor, ServiceDescriptor};
 
 use crate::{app::command, protoc, theme, widget::Icon};
 
 use self::service::ServiceOptions;
 
-use super::body::CompileOptions;
+use super::body::{Collections, CompileOptions, Environments, SavedRequest};
 
 #[derive(Debug, Default, Clone, Data, Lens)]
 pub(in crate::app) struct State {
@@ -28,6 +31,8 @@ pub(in crate::app) struct State {
 pub(in crate::app) struct ServiceListState {
     services: im::Vector<service::ServiceState>,
     compile_options: CompileOptions,
+    environments: Environments,
+    collections: Collections,
 }
 
 pub(in crate::app) fn build() -> impl Widget<State> {
@@ -45,10 +50,33 @@ pub(in crate::app) fn build() -> impl Widget<State> {
         })
         .background(theme::hot_or_active_painter(0.0));
 
+    let environment_button = Flex::row()
+        .with_child(Icon::settings().padding(8.0))
+        .with_child(
+            Label::dynamic(
+                |data: &State, _| match data.services.environments.active_name() {
+                    Some(name) => format!("Environment: {}", name),
+                    None => "No environment".to_owned(),
+                },
+            )
+            .with_font(theme::font::HEADER_TWO)
+            .with_line_break_mode(LineBreaking::Clip),
+        )
+        .must_fill_main_axis(true)
+        .main_axis_alignment(MainAxisAlignment::Start)
+        .on_click(|ctx, _: &mut State, _| {
+            ctx.submit_command(command::SELECT_OR_CREATE_ENVIRONMENT_TAB);
+        })
+        .background(theme::hot_or_active_painter(0.0));
+
     Scroll::new(
         Flex::column()
             .with_child(List::new(service::build))
-            .with_child(add_button)
+            .with_child(
+                Flex::column()
+                    .with_child(add_button)
+                    .with_child(environment_button),
+            )
             .main_axis_alignment(MainAxisAlignment::SpaceBetween),
     )
     .vertical()
@@ -80,21 +108,31 @@ impl State {
 }
 
 impl ServiceListState {
-    pub fn new<T>(services: T, compile_options: CompileOptions) -> Self
+    pub fn new<T>(
+        services: T,
+        compile_options: CompileOptions,
+        environments: Environments,
+        collections: Collections,
+    ) -> Self
     where
         T: IntoIterator<Item = service::ServiceState>,
     {
         ServiceListState {
             services: im::Vector::from_iter(services),
             compile_options,
+            environments,
+            collections,
         }
     }
 
     pub fn add_from_path(&mut self, path: &Path) -> Result<()> {
         let file = protoc::load_file(path, self.compile_options.includes())?;
 
-        self.services
-            .extend(file.services().map(service::ServiceState::from));
+        let proto = protoc::is_proto(path).then(|| path.to_owned());
+        self.services.extend(
+            file.services()
+                .map(|service| service::ServiceState::from(service).with_proto(proto.clone())),
+        );
         Ok(())
     }
 
@@ -102,9 +140,14 @@ impl ServiceListState {
         &self.services
     }
 
-    pub fn add_service(&mut self, service: ServiceDescriptor, options: ServiceOptions) {
+    pub fn add_service(
+        &mut self,
+        service: ServiceDescriptor,
+        options: ServiceOptions,
+        proto: Option<PathBuf>,
+    ) {
         self.services
-            .push_back(service::ServiceState::new(service, true, options));
+            .push_back(service::ServiceState::new(service, true, options).with_proto(proto));
     }
 
     pub fn remove_service(&mut self, index: usize) -> service::ServiceState {
@@ -133,6 +176,24 @@ impl ServiceListState {
         }
     }
 
+    pub fn save_request(&mut self, method: &MethodDescriptor, request: SavedRequest) {
+        for service_state in self.services.iter_mut() {
+            if service_state.service() == &method.parent_service() {
+                service_state.save_request(method, request);
+                return;
+            }
+        }
+    }
+
+    pub fn delete_saved_request(&mut self, method: &MethodDescriptor, name: &str) {
+        for service_state in self.services.iter_mut() {
+            if service_state.service() == &method.parent_service() {
+                service_state.delete_saved_request(method, name);
+                return;
+            }
+        }
+    }
+
     pub fn compile_options(&self) -> &CompileOptions {
         &self.compile_options
     }
@@ -140,6 +201,22 @@ impl ServiceListState {
     pub fn set_compile_options(&mut self, options: CompileOptions) {
         self.compile_options = options
     }
+
+    pub fn environments(&self) -> &Environments {
+        &self.environments
+    }
+
+    pub fn set_environments(&mut self, environments: Environments) {
+        self.environments = environments
+    }
+
+    pub fn collections(&self) -> &Collections {
+        &self.collections
+    }
+
+    pub fn set_collections(&mut self, collections: Collections) {
+        self.collections = collections
+    }
 }
 
 impl ListIter<service::State> for State {
@@ -1,19 +1,25 @@
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use druid::{
     widget::{prelude::*, Label, LineBreaking, List, ListIter},
     ArcStr, Data, Lens, Widget, WidgetExt,
 };
-use http::Uri;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app::{
+        body::{Address, SavedRequest},
         command::{REMOVE_SERVICE, SELECT_OR_CREATE_OPTIONS_TAB},
         metadata,
         sidebar::method,
     },
-    auth::AuthorizationHook,
+    auth::AuthProvider,
+    grpc::ClientOptions,
     theme,
     widget::expander,
     widget::{ExpanderData, Icon},
@@ -39,16 +45,22 @@ pub(in crate::app) struct ServiceState {
     service: prost_reflect::ServiceDescriptor,
     #[lens(ignore)]
     options: ServiceOptions,
+    /// The `.proto` file the service was compiled from, if any.
+    #[lens(ignore)]
+    #[data(same_fn = "PartialEq::eq")]
+    proto: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Data, Lens, Serialize, Deserialize)]
 pub struct ServiceOptions {
     #[data(same_fn = "PartialEq::eq")]
-    #[serde(with = "serde_opt_uri")]
-    pub default_address: Option<Uri>,
+    pub default_address: Option<Address>,
     pub verify_certs: bool,
     pub default_metadata: metadata::State,
-    pub auth_hook: Option<Arc<AuthorizationHook>>,
+    pub auth: Option<Arc<AuthProvider>>,
+    /// The number of seconds a call may take before it fails, or no limit if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
 }
 
 impl Default for ServiceOptions {
@@ -57,11 +69,34 @@ impl Default for ServiceOptions {
             default_address: Default::default(),
             verify_certs: true,
             default_metadata: metadata::State::default(),
-            auth_hook: None,
+            auth: None,
+            timeout_secs: None,
         }
     }
 }
 
+impl ServiceOptions {
+    pub fn client_options(&self) -> ClientOptions {
+        ClientOptions::new().verify_certs(self.verify_certs)
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs.map(Duration::from_secs)
+    }
+}
+
+/// Parses a timeout entered as a whole number of seconds, where an empty string means no timeout.
+pub(in crate::app) fn parse_timeout_secs(text: &str) -> Result<Option<u64>, ArcStr> {
+    match text.trim() {
+        "" => Ok(None),
+        text => match text.parse() {
+            Ok(0) => Err("timeout must be greater than zero".into()),
+            Ok(secs) => Ok(Some(secs)),
+            Err(_) => Err("timeout must be a whole number of seconds".into()),
+        },
+    }
+}
+
 pub(in crate::app) fn build() -> impl Widget<State> {
     let expander_label = Label::raw()
         .with_font(theme::font::HEADER_ONE)
@@ -118,13 +153,23 @@ impl ServiceState {
             expanded,
             service,
             options,
+            proto: None,
         }
     }
 
+    pub fn with_proto(mut self, proto: Option<PathBuf>) -> Self {
+        self.proto = proto;
+        self
+    }
+
     pub fn service(&self) -> &prost_reflect::ServiceDescriptor {
         &self.service
     }
 
+    pub fn methods(&self) -> impl Iterator<Item = &method::MethodState> {
+        self.methods.iter()
+    }
+
     pub fn expanded(&self) -> bool {
         self.expanded
     }
@@ -136,6 +181,53 @@ impl ServiceState {
     pub fn set_options(&mut self, options: ServiceOptions) {
         self.options = options;
     }
+
+    pub fn proto(&self) -> Option<&Path> {
+        self.proto.as_deref()
+    }
+
+    /// Gets the saved requests for each method, keyed by method name.
+    pub fn saved_requests(&self) -> BTreeMap<String, Vec<SavedRequest>> {
+        self.methods
+            .iter()
+            .filter(|method| !method.saved().is_empty())
+            .map(|method| (method.method().name().to_owned(), method.saved().to_vec()))
+            .collect()
+    }
+
+    pub fn set_saved_requests(&mut self, saved: &BTreeMap<String, Vec<SavedRequest>>) {
+        self.update_methods(|method| {
+            if let Some(requests) = saved.get(method.method().name()) {
+                method.set_saved(requests.clone());
+            }
+        });
+    }
+
+    pub fn save_request(
+        &mut self,
+        method: &prost_reflect::MethodDescriptor,
+        request: SavedRequest,
+    ) {
+        self.update_methods(|state| {
+            if state.method() == method {
+                state.save(request.clone());
+            }
+        });
+    }
+
+    pub fn delete_saved_request(&mut self, method: &prost_reflect::MethodDescriptor, name: &str) {
+        self.update_methods(|state| {
+            if state.method() == method {
+                state.delete(name);
+            }
+        });
+    }
+
+    fn update_methods(&mut self, mut f: impl FnMut(&mut method::MethodState)) {
+        let mut methods = self.methods.to_vec();
+        methods.iter_mut().for_each(&mut f);
+        self.methods = methods.into();
+    }
 }
 
 impl From<prost_reflect::ServiceDescriptor> for ServiceState {
@@ -204,27 +296,3 @@ impl ListIter<method::State> for State {
         self.service.methods.len()
     }
 }
-
-mod serde_opt_uri {
-    use std::str::FromStr;
-
-    use http::Uri;
-    use serde::{de, Deserialize, Deserializer, Serializer};
-
-    pub fn serialize<S: Serializer>(uri: &Option<Uri>, ser: S) -> Result<S::Ok, S::Error> {
-        if let Some(uri) = uri {
-            ser.collect_str(uri)
-        } else {
-            ser.serialize_none()
-        }
-    }
-
-    pub fn deserialize<'de, D>(de: D) -> Result<Option<Uri>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s: Option<String> = Deserialize::deserialize(de)?;
-        s.map(|s| Uri::from_str(&s).map_err(de::Error::custom))
-            .transpose()
-    }
-}
@@ -0,0 +1,373 @@
+//! Workspace files, which hold services and their settings separately from the application's
+//! configuration so they can be shared, for example by committing them to a repository.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{self, body, config::Config, serde::Secrets, sidebar},
+    error::fmt_err,
+};
+
+/// The open workspace, recently opened workspaces, and what to include when saving one.
+#[derive(Debug, Default, Clone, Data, Serialize, Deserialize)]
+pub(in crate::app) struct State {
+    #[data(same_fn = "PartialEq::eq")]
+    #[serde(default)]
+    recent: Arc<Vec<PathBuf>>,
+    #[serde(default)]
+    include_secrets: bool,
+    #[serde(default)]
+    include_history: bool,
+    /// The workspace file which is open, if any. Its services and tabs are saved to it rather than
+    /// the config file.
+    #[data(same_fn = "PartialEq::eq")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<Arc<PathBuf>>,
+    /// Secrets left out of each workspace file, which are restored when it is opened.
+    #[data(same_fn = "PartialEq::eq")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    secrets: Arc<BTreeMap<PathBuf, Secrets>>,
+    /// The services and tabs from the config file, which are kept while a workspace is open.
+    #[data(ignore)]
+    #[serde(skip)]
+    personal: Option<Arc<Personal>>,
+}
+
+#[derive(Debug)]
+struct Personal {
+    sidebar: sidebar::ServiceListState,
+    body: body::State,
+}
+
+/// What was last saved to the open workspace, so that it is only serialized again when its
+/// services or tabs change, and only written when the result differs.
+#[derive(Default)]
+pub(in crate::app) struct Saved(Option<SavedWorkspace>);
+
+struct SavedWorkspace {
+    path: PathBuf,
+    sidebar: sidebar::ServiceListState,
+    body: body::State,
+    include_secrets: bool,
+    include_history: bool,
+    text: String,
+    secrets: Secrets,
+}
+
+impl State {
+    const MAX_RECENT: usize = 10;
+
+    pub fn recent(&self) -> &[PathBuf] {
+        &self.recent
+    }
+
+    /// Moves a workspace to the front of the recent list.
+    pub fn add_recent(&mut self, path: &Path) {
+        let path = fs_err::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        let recent = Arc::make_mut(&mut self.recent);
+        recent.retain(|p| p != &path);
+        recent.insert(0, path);
+        recent.truncate(Self::MAX_RECENT);
+    }
+
+    /// The workspace file which is open, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref().map(PathBuf::as_path)
+    }
+
+    /// Whether authorization settings, captured variables and the values of metadata and
+    /// variables with secret names are saved in workspace files.
+    pub fn include_secrets(&self) -> bool {
+        self.include_secrets
+    }
+
+    pub fn toggle_include_secrets(&mut self) {
+        self.include_secrets = !self.include_secrets;
+    }
+
+    /// Whether the request history of open tabs is saved in workspace files.
+    pub fn include_history(&self) -> bool {
+        self.include_history
+    }
+
+    pub fn toggle_include_history(&mut self) {
+        self.include_history = !self.include_history;
+    }
+
+    /// Records the secrets left out of a workspace file when it was last saved.
+    pub fn set_secrets(&mut self, path: &Path, secrets: Secrets) {
+        if self.secrets.get(path) == Some(&secrets)
+            || (secrets.is_empty() && !self.secrets.contains_key(path))
+        {
+            return;
+        }
+
+        let all = Arc::make_mut(&mut self.secrets);
+        if secrets.is_empty() {
+            all.remove(path);
+        } else {
+            all.insert(path.to_owned(), secrets);
+        }
+    }
+}
+
+/// Whether the name of a metadata entry or environment variable suggests its value is a secret,
+/// such as `authorization`, `x-api-key` or `accessToken`. Names are split into words at `_`, `-`,
+/// `.` and lowercase to uppercase changes, so that names like `author` or `x-idempotency-key` don't
+/// match.
+pub(in crate::app) fn is_secret_name(name: &str) -> bool {
+    const WORDS: &[&str] = &[
+        "auth",
+        "authorization",
+        "token",
+        "secret",
+        "password",
+        "passwd",
+        "credential",
+        "credentials",
+        "cookie",
+        "session",
+        "apikey",
+    ];
+    /// Words which only name a secret when followed by `key`.
+    const KEY_PREFIXES: &[&str] = &["api", "access", "private"];
+
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lowercase = false;
+    for c in name.chars() {
+        if matches!(c, '_' | '-' | '.') || (prev_lowercase && c.is_uppercase()) {
+            words.push(std::mem::take(&mut word));
+        }
+        if !matches!(c, '_' | '-' | '.') {
+            word.extend(c.to_lowercase());
+        }
+        prev_lowercase = c.is_lowercase();
+    }
+    words.push(word);
+
+    words.iter().any(|word| WORDS.contains(&word.as_str()))
+        || words
+            .windows(2)
+            .any(|pair| KEY_PREFIXES.contains(&pair[0].as_str()) && pair[1] == "key")
+}
+
+/// Replaces the services and tabs with those of a workspace file, and saves changes back to it.
+/// The services and tabs from the config file are kept, and restored when the workspace is closed.
+pub(in crate::app) fn open(data: &mut app::State, path: &Path) -> Result<()> {
+    check_not_config(path)?;
+    let workspace = load(path, &data.workspace)?;
+    save_open(data)?;
+    set_open(data, path);
+    data.sidebar = workspace.sidebar;
+    data.body = workspace.body;
+    data.body.set_workspace(data.workspace.path());
+    Ok(())
+}
+
+/// Opens the workspace which was open when the config file was saved, if any.
+pub(in crate::app) fn reopen(data: &mut app::State) {
+    if let Some(path) = data.workspace.path.take() {
+        if let Err(err) = open(data, &path) {
+            data.error = Some(format!("Error opening workspace: {}", fmt_err(&err)).into());
+        }
+    }
+}
+
+/// Saves and closes the open workspace, restoring the services and tabs from the config file.
+pub(in crate::app) fn close(data: &mut app::State) -> Result<()> {
+    save_open(data)?;
+    if let Some(personal) = data.workspace.personal.take() {
+        data.sidebar = personal.sidebar.clone();
+        data.body = personal.body.clone();
+    }
+    data.workspace.path = None;
+    data.body.set_workspace(None);
+    Ok(())
+}
+
+/// Saves the services, settings and tabs to a new workspace file, which becomes the open
+/// workspace.
+pub(in crate::app) fn save_as(data: &mut app::State, path: &Path) -> Result<()> {
+    check_not_config(path)?;
+    let secrets = save(path, data)?;
+    set_open(data, path);
+    set_open_secrets(data, secrets);
+    data.body.set_workspace(data.workspace.path());
+    Ok(())
+}
+
+/// Gets the state to save to the config file. While a workspace is open, this has the services and
+/// tabs from the config file rather than those of the workspace.
+pub(in crate::app) fn config_state(data: &app::State) -> app::State {
+    let mut data = data.clone();
+    if let Some(personal) = &data.workspace.personal {
+        data.sidebar = personal.sidebar.clone();
+        data.body = personal.body.clone();
+    }
+    data
+}
+
+/// Saves the open workspace, since the last changes to it may not have been saved yet.
+fn save_open(data: &mut app::State) -> Result<()> {
+    if let Some(path) = data.workspace.path() {
+        let secrets = save(path, data)?;
+        set_open_secrets(data, secrets);
+    }
+    Ok(())
+}
+
+fn set_open_secrets(data: &mut app::State, secrets: Secrets) {
+    if let Some(path) = data.workspace.path.clone() {
+        data.workspace.set_secrets(&path, secrets);
+    }
+}
+
+/// The config file is saved separately, so it can't also be the open workspace.
+fn check_not_config(path: &Path) -> Result<()> {
+    let config = Config::path()?;
+    if path == config || fs_err::canonicalize(path).ok() == fs_err::canonicalize(&config).ok() {
+        bail!("the application's configuration file can't be used as a workspace");
+    }
+    Ok(())
+}
+
+fn set_open(data: &mut app::State, path: &Path) {
+    if data.workspace.personal.is_none() {
+        data.workspace.personal = Some(Arc::new(Personal {
+            sidebar: data.sidebar.clone(),
+            body: data.body.clone(),
+        }));
+    }
+    let path = fs_err::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    data.workspace.add_recent(&path);
+    data.workspace.path = Some(Arc::new(path));
+}
+
+/// Loads the services, settings and tabs from a workspace file, with the secrets which were left
+/// out of it when it was saved from this application. The application's own configuration file is
+/// also accepted.
+pub(in crate::app) fn load(path: &Path, workspace: &State) -> Result<app::State> {
+    let text = fs_err::read_to_string(path)?;
+    let mut value: serde_json::Value = serde_json::from_str(&text)
+        .with_context(|| format!("failed to parse `{}`", path.display()))?;
+    let mut value = match value.get_mut("data") {
+        Some(data) => data.take(),
+        None => value,
+    };
+    let path = fs_err::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    if let Some(secrets) = workspace.secrets.get(&path) {
+        secrets.restore(&mut value);
+    }
+    let data = serde_json::from_value(value)
+        .with_context(|| format!("failed to parse `{}`", path.display()))?;
+    tracing::debug!("Loaded workspace from {}", path.display());
+    Ok(data)
+}
+
+/// Saves the services, settings and tabs to a workspace file, leaving out secrets and request
+/// history unless they are enabled in the workspace options. Returns the secrets which were left
+/// out, which should be kept with [`State::set_secrets`].
+pub(in crate::app) fn save(path: &Path, data: &app::State) -> Result<Secrets> {
+    let (text, secrets) = app::serde::workspace_to_string(
+        data,
+        data.workspace.include_secrets,
+        data.workspace.include_history,
+    )?;
+    Config::write_file(path, text.as_bytes())?;
+    tracing::debug!("Saved workspace to {}", path.display());
+    Ok(secrets)
+}
+
+/// Saves the open workspace like [`save`], unless it is unchanged since it was last saved.
+pub(in crate::app) fn save_changed(
+    path: &Path,
+    data: &app::State,
+    saved: &mut Saved,
+) -> Result<Secrets> {
+    let include_secrets = data.workspace.include_secrets;
+    let include_history = data.workspace.include_history;
+    if let Some(last) = &saved.0 {
+        if last.path == path
+            && last.include_secrets == include_secrets
+            && last.include_history == include_history
+            && last.sidebar.same(&data.sidebar)
+            && last.body.same(&data.body)
+        {
+            return Ok(last.secrets.clone());
+        }
+    }
+
+    let (text, secrets) = app::serde::workspace_to_string(data, include_secrets, include_history)?;
+    if matches!(&saved.0, Some(last) if last.path == path && last.text == text) {
+        tracing::debug!("Skipping workspace save because it is unchanged");
+    } else {
+        Config::write_file(path, text.as_bytes())?;
+        tracing::debug!("Saved workspace to {}", path.display());
+    }
+
+    saved.0 = Some(SavedWorkspace {
+        path: path.to_owned(),
+        sidebar: data.sidebar.clone(),
+        body: data.body.clone(),
+        include_secrets,
+        include_history,
+        text,
+        secrets: secrets.clone(),
+    });
+    Ok(secrets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_names() {
+        for name in [
+            "authorization",
+            "Authorization",
+            "x-auth-token",
+            "x-api-key",
+            "api_key",
+            "apiKey",
+            "APIKEY",
+            "accessToken",
+            "access_key",
+            "client_secret",
+            "password",
+            "cookie",
+            "set-cookie",
+            "session_id",
+            "credentials",
+        ] {
+            assert!(is_secret_name(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn other_names() {
+        for name in [
+            "author",
+            "monkey",
+            "keyboard",
+            "cache_key_prefix",
+            "x-idempotency-key",
+            "key",
+            "tokenizer",
+            "authorName",
+            "content-type",
+            "",
+        ] {
+            assert!(!is_secret_name(name), "{}", name);
+        }
+    }
+}
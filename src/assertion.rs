@@ -0,0 +1,407 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{bail, Context, Error, Result};
+use regex::Regex;
+use serde_json::Value;
+use tonic::{metadata::MetadataMap, Code, Status};
+
+use crate::{capture, error::fmt_code, snapshot::Snapshot};
+
+const METADATA_PREFIX: &str = "metadata:";
+
+/// A check on the result of a call.
+#[derive(Debug, Clone)]
+pub enum Assertion {
+    /// The call finished with the given status code.
+    Status(Code),
+    /// The call took less than the given time to return its first response.
+    Latency(Duration),
+    /// A check on the value at a JSON Pointer into the last response message.
+    Json(String, Check),
+    /// A check on the value of a key in the response headers or trailers.
+    Metadata(String, Check),
+    /// The last response message matches the saved snapshot, except at the given JSON Pointers.
+    Snapshot(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    Exists,
+    Equals(Value),
+    Matches(Regex),
+}
+
+/// The result of a call, which assertions are checked against.
+#[derive(Debug)]
+pub struct Outcome {
+    code: Code,
+    message: Option<Value>,
+    metadata: MetadataMap,
+    latency: Option<Duration>,
+    snapshot: Result<Snapshot, String>,
+}
+
+impl FromStr for Assertion {
+    type Err = Error;
+
+    /// Parses an assertion such as `status OK`, `latency < 200ms`, `/id exists`,
+    /// `$.name == "bob"`, `/name =~ ^b`, `metadata:x-request-id` or
+    /// `snapshot ignore /createdAt /items/*/id`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (target, rest) = match s.split_once(char::is_whitespace) {
+            Some((target, rest)) => (target, rest.trim()),
+            None => (s, ""),
+        };
+
+        if target == "status" {
+            Ok(Assertion::Status(parse_code(rest)?))
+        } else if target == "latency" {
+            let Some(limit) = rest.strip_prefix('<') else {
+                bail!("expected 'latency < <duration>'")
+            };
+            Ok(Assertion::Latency(parse_duration(limit.trim())?))
+        } else if let Some(key) = target.strip_prefix(METADATA_PREFIX) {
+            if key.is_empty() {
+                bail!("expected a metadata key after '{}'", METADATA_PREFIX)
+            }
+            let check = Check::parse(rest, |value| Value::String(value.to_owned()))?;
+            Ok(Assertion::Metadata(key.to_ascii_lowercase(), check))
+        } else if target == "snapshot" {
+            let ignore = if rest.is_empty() {
+                Vec::new()
+            } else if let Some(paths) = rest.strip_prefix("ignore") {
+                paths
+                    .split_whitespace()
+                    .map(capture::json_pointer_pattern)
+                    .collect::<Result<_>>()?
+            } else {
+                bail!("expected 'snapshot' or 'snapshot ignore <paths>'")
+            };
+            Ok(Assertion::Snapshot(ignore))
+        } else if target.starts_with('/') || target.starts_with('$') {
+            let pointer = capture::json_pointer(target)?;
+            let check = Check::parse(rest, |value| {
+                serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()))
+            })?;
+            Ok(Assertion::Json(pointer, check))
+        } else {
+            bail!(
+                "expected 'status', 'latency', 'snapshot', a JSON Pointer, a JSONPath or '{}<key>'",
+                METADATA_PREFIX
+            )
+        }
+    }
+}
+
+impl Assertion {
+    /// Checks the outcome of a call, returning a description of the failure if it does not hold.
+    pub fn check(&self, outcome: &Outcome) -> Result<(), String> {
+        match self {
+            Assertion::Status(code) => {
+                if outcome.code == *code {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "expected status {}, got {}",
+                        fmt_code(*code),
+                        fmt_code(outcome.code)
+                    ))
+                }
+            }
+            Assertion::Latency(limit) => match outcome.latency {
+                Some(latency) if latency < *limit => Ok(()),
+                Some(latency) => Err(format!(
+                    "expected latency below {:?}, got {:?}",
+                    limit, latency
+                )),
+                None => Err("no response was received".to_owned()),
+            },
+            Assertion::Json(pointer, check) => {
+                let Some(message) = &outcome.message else {
+                    return Err("no response was received".to_owned());
+                };
+                check.check(pointer, message.pointer(pointer))
+            }
+            Assertion::Metadata(key, check) => {
+                let value = capture::Source::Metadata(key.clone())
+                    .from_metadata(&outcome.metadata)
+                    .map(Value::String);
+                check.check(key, value.as_ref())
+            }
+            Assertion::Snapshot(ignore) => {
+                let snapshot = outcome.snapshot.as_ref().map_err(Clone::clone)?;
+                let Some(message) = &outcome.message else {
+                    return Err("no response was received".to_owned());
+                };
+                let differences = snapshot.diff(message, ignore);
+                if differences.is_empty() {
+                    Ok(())
+                } else {
+                    let mut error =
+                        format!("{} field(s) differ from the snapshot:", differences.len());
+                    for difference in differences {
+                        error.push_str(&format!("\n  {}", difference));
+                    }
+                    Err(error)
+                }
+            }
+        }
+    }
+}
+
+impl Check {
+    fn parse(s: &str, parse_value: impl FnOnce(&str) -> Value) -> Result<Self> {
+        if s.is_empty() || s == "exists" {
+            Ok(Check::Exists)
+        } else if let Some(value) = s.strip_prefix("==") {
+            Ok(Check::Equals(parse_value(value.trim())))
+        } else if let Some(pattern) = s.strip_prefix("=~") {
+            let regex = Regex::new(pattern.trim()).context("invalid regex")?;
+            Ok(Check::Matches(regex))
+        } else {
+            bail!("expected 'exists', '== <value>' or '=~ <regex>'")
+        }
+    }
+
+    fn check(&self, name: &str, value: Option<&Value>) -> Result<(), String> {
+        let Some(value) = value else {
+            return Err(format!("expected '{}' to exist", name));
+        };
+
+        match self {
+            Check::Exists => Ok(()),
+            Check::Equals(expected) if value == expected => Ok(()),
+            Check::Equals(expected) => Err(format!(
+                "expected '{}' to equal {}, got {}",
+                name, expected, value
+            )),
+            Check::Matches(regex) => {
+                let text = match value {
+                    Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                };
+                if regex.is_match(&text) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "expected '{}' to match '{}', got {}",
+                        name, regex, value
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl Outcome {
+    pub fn new() -> Self {
+        Outcome {
+            code: Code::Ok,
+            message: None,
+            metadata: MetadataMap::new(),
+            latency: None,
+            snapshot: Err("no snapshot has been saved for this request".to_owned()),
+        }
+    }
+
+    /// Records a response message. The time taken for the first response is its latency.
+    pub fn add_response(&mut self, json: &str, latency: Option<Duration>) {
+        match serde_json::from_str(json) {
+            Ok(message) => self.message = Some(message),
+            Err(err) => tracing::warn!("Failed to parse response for assertions: {}", err),
+        }
+        if self.latency.is_none() {
+            self.latency = latency;
+        }
+    }
+
+    pub fn add_metadata(&mut self, metadata: &MetadataMap) {
+        let mut headers = self.metadata.clone().into_headers();
+        headers.extend(metadata.clone().into_headers());
+        self.metadata = MetadataMap::from_headers(headers);
+    }
+
+    /// Sets the snapshot which `snapshot` assertions compare against.
+    pub fn set_snapshot(&mut self, snapshot: Result<Option<Snapshot>>) {
+        match snapshot {
+            Ok(Some(snapshot)) => self.snapshot = Ok(snapshot),
+            Ok(None) => (),
+            Err(err) => self.snapshot = Err(format!("failed to load snapshot: {:#}", err)),
+        }
+    }
+
+    pub fn set_error(&mut self, error: &Error, metadata: &MetadataMap) {
+        self.code = match error.downcast_ref::<Status>() {
+            Some(status) => status.code(),
+            None => Code::Unknown,
+        };
+        self.add_metadata(metadata);
+    }
+}
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Outcome::new()
+    }
+}
+
+fn parse_code(s: &str) -> Result<Code> {
+    if let Ok(value) = s.parse::<i32>() {
+        let code = Code::from_i32(value);
+        if code as i32 == value {
+            return Ok(code);
+        }
+    }
+
+    (0..=16)
+        .map(Code::from_i32)
+        .find(|&code| fmt_code(code).eq_ignore_ascii_case(s))
+        .with_context(|| format!("unknown status code '{}'", s))
+}
+
+fn parse_duration(s: &str) -> Result<Duration> {
+    let (value, scale) = if let Some(value) = s.strip_suffix("ms") {
+        (value, 0.001)
+    } else if let Some(value) = s.strip_suffix('s') {
+        (value, 1.0)
+    } else {
+        bail!("expected a duration such as '200ms' or '1.5s'")
+    };
+
+    let value: f64 = value.trim().parse().context("invalid duration")?;
+    if !value.is_finite() || value < 0.0 {
+        bail!("invalid duration")
+    }
+    Ok(Duration::from_secs_f64(value * scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn outcome(message: Value) -> Outcome {
+        let mut outcome = Outcome::new();
+        outcome.add_response(&message.to_string(), Some(Duration::from_millis(10)));
+        outcome
+    }
+
+    #[test]
+    fn parse_code_by_name_or_number() {
+        assert_eq!(parse_code("OK").unwrap(), Code::Ok);
+        assert_eq!(parse_code("not_found").unwrap(), Code::NotFound);
+        assert_eq!(parse_code("5").unwrap(), Code::NotFound);
+        assert_eq!(parse_code("16").unwrap(), Code::Unauthenticated);
+        assert!(parse_code("17").is_err());
+        assert!(parse_code("-1").is_err());
+        assert!(parse_code("NOPE").is_err());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("200ms").unwrap(), Duration::from_millis(200));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("2 s").unwrap(), Duration::from_secs(2));
+        assert!(parse_duration("200").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("infs").is_err());
+        assert!(parse_duration("abcms").is_err());
+    }
+
+    #[test]
+    fn exists() {
+        let outcome = outcome(json!({ "id": 1, "items": [{ "name": "a" }] }));
+
+        let assertion: Assertion = "/id exists".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = "/items/0/name".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = "/missing exists".parse().unwrap();
+        assert_eq!(
+            assertion.check(&outcome),
+            Err("expected '/missing' to exist".to_owned())
+        );
+    }
+
+    #[test]
+    fn equals() {
+        let outcome = outcome(json!({ "id": 1, "name": "bob", "tags": ["x"] }));
+
+        let assertion: Assertion = "/id == 1".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = r#"/name == "bob""#.parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        // Values which aren't valid JSON are compared as strings.
+        let assertion: Assertion = "/name == bob".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = r#"$.name == "bob""#.parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = r#"/tags == ["x"]"#.parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = "/id == 2".parse().unwrap();
+        assert_eq!(
+            assertion.check(&outcome),
+            Err("expected '/id' to equal 2, got 1".to_owned())
+        );
+    }
+
+    #[test]
+    fn matches() {
+        let outcome = outcome(json!({ "id": 123, "name": "bob" }));
+
+        let assertion: Assertion = "/name =~ ^b".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        // Values which aren't strings are matched against their JSON text.
+        let assertion: Assertion = r"/id =~ ^\d+$".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = "/name =~ ^a".parse().unwrap();
+        assert_eq!(
+            assertion.check(&outcome),
+            Err(r#"expected '/name' to match '^a', got "bob""#.to_owned())
+        );
+        assert!("/name =~ (".parse::<Assertion>().is_err());
+    }
+
+    #[test]
+    fn metadata_checks() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("x-request-id", "abc-123".parse().unwrap());
+        let mut outcome = Outcome::new();
+        outcome.add_metadata(&metadata);
+
+        let assertion: Assertion = "metadata:X-Request-Id".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = "metadata:x-request-id == abc-123".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = "metadata:x-request-id =~ ^abc".parse().unwrap();
+        assert_eq!(assertion.check(&outcome), Ok(()));
+        let assertion: Assertion = "metadata:x-missing".parse().unwrap();
+        assert!(assertion.check(&outcome).is_err());
+    }
+
+    #[test]
+    fn snapshot_ignore_paths() {
+        let assertion: Assertion = "snapshot ignore /createdAt $.items[*].id $.meta.*"
+            .parse()
+            .unwrap();
+        match assertion {
+            Assertion::Snapshot(ignore) => {
+                assert_eq!(ignore, ["/createdAt", "/items/*/id", "/meta/*"])
+            }
+            _ => panic!("expected a snapshot assertion"),
+        }
+
+        assert!("$.items[*].id == 1".parse::<Assertion>().is_err());
+    }
+
+    #[test]
+    fn no_response() {
+        let assertion: Assertion = "/id exists".parse().unwrap();
+        assert_eq!(
+            assertion.check(&Outcome::new()),
+            Err("no response was received".to_owned())
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{header::AUTHORIZATION, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use super::JwtConfig;
+
+/// Authorization metadata generated by the application, without running an external hook.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BuiltinAuth {
+    /// A static token, sent as `authorization: Bearer <token>`.
+    Bearer { token: String },
+    /// A username and password, sent as `authorization: Basic <credentials>`.
+    Basic { username: String, password: String },
+    /// A static key sent in a custom header.
+    ApiKey { header: String, key: String },
+    /// A JWT signed with a local private key, sent as a bearer token.
+    Jwt(JwtConfig),
+}
+
+impl BuiltinAuth {
+    /// Checks that the metadata can be generated, without reading any files.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            BuiltinAuth::Bearer { token } => {
+                if token.is_empty() {
+                    bail!("token is required")
+                }
+                bearer(token)?;
+            }
+            BuiltinAuth::Basic { username, password } => {
+                if username.is_empty() {
+                    bail!("username is required")
+                }
+                basic(username, password)?;
+            }
+            BuiltinAuth::ApiKey { header, key } => {
+                if header.is_empty() {
+                    bail!("header name is required")
+                }
+                api_key(header, key)?;
+            }
+            BuiltinAuth::Jwt(config) => config.validate()?,
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_headers(&self) -> Result<http::HeaderMap> {
+        let mut headers = http::HeaderMap::new();
+        match self {
+            BuiltinAuth::Bearer { token } => {
+                headers.insert(AUTHORIZATION, bearer(token)?);
+            }
+            BuiltinAuth::Basic { username, password } => {
+                headers.insert(AUTHORIZATION, basic(username, password)?);
+            }
+            BuiltinAuth::ApiKey { header, key } => {
+                let (name, value) = api_key(header, key)?;
+                headers.insert(name, value);
+            }
+            BuiltinAuth::Jwt(config) => {
+                let token = config.sign().await?;
+                headers.insert(AUTHORIZATION, bearer(&token)?);
+            }
+        }
+        Ok(headers)
+    }
+}
+
+fn bearer(token: &str) -> Result<HeaderValue> {
+    sensitive_value(&format!("Bearer {}", token)).context("invalid token")
+}
+
+fn basic(username: &str, password: &str) -> Result<HeaderValue> {
+    let credentials = STANDARD.encode(format!("{}:{}", username, password));
+    sensitive_value(&format!("Basic {}", credentials)).context("invalid username or password")
+}
+
+fn api_key(header: &str, key: &str) -> Result<(HeaderName, HeaderValue)> {
+    let name = HeaderName::from_bytes(header.as_bytes()).context("invalid header name")?;
+    let value = sensitive_value(key).context("invalid key")?;
+    Ok((name, value))
+}
+
+fn sensitive_value(value: &str) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(value)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+impl fmt::Debug for BuiltinAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid writing secrets to the log.
+        match self {
+            BuiltinAuth::Bearer { .. } => f.debug_struct("Bearer").finish_non_exhaustive(),
+            BuiltinAuth::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish_non_exhaustive(),
+            BuiltinAuth::ApiKey { header, .. } => f
+                .debug_struct("ApiKey")
+                .field("header", header)
+                .finish_non_exhaustive(),
+            BuiltinAuth::Jwt(config) => f.debug_tuple("Jwt").field(config).finish(),
+        }
+    }
+}
@@ -0,0 +1,650 @@
+use std::{fmt, process::Stdio, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use time::OffsetDateTime;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout, Command},
+    sync::{broadcast, Mutex, MutexGuard},
+};
+
+use super::DEFAULT_REFRESH_MARGIN;
+
+/// How long to wait for a response from a persistent hook with no timeout set, so that a hook
+/// which stops responding is restarted rather than blocking every later request.
+const DEFAULT_PERSISTENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+pub struct AuthorizationResult {
+    #[serde(with = "http_serde::header_map")]
+    metadata: http::HeaderMap,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    expiry: Option<OffsetDateTime>,
+    /// The fields of the call context used to generate this result. If empty, the result may be
+    /// reused for any call.
+    #[serde(default)]
+    depends_on: Vec<ContextField>,
+}
+
+/// Information about the call being authorized, passed to the hook on stdin.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct CallContext {
+    address: String,
+    service: String,
+    method: String,
+    /// The base64-encoded protobuf request message, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ContextField {
+    Address,
+    Service,
+    Method,
+    Request,
+}
+
+struct CacheEntry {
+    key: CallContext,
+    result: AuthorizationResult,
+}
+
+/// How the authorization hook process is run.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookMode {
+    /// A new process is started each time metadata is needed, and writes a single result to stdout.
+    #[default]
+    OneShot,
+    /// A single process is kept running, and receives a JSON request on each line of stdin. It
+    /// should write one JSON result to stdout for each request.
+    Persistent,
+}
+
+/// Settings controlling how an authorization hook is run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HookOptions {
+    pub mode: HookMode,
+    /// How long to wait for the hook before giving up and killing the process. Persistent hooks
+    /// default to [`DEFAULT_PERSISTENT_TIMEOUT`].
+    pub timeout: Option<Duration>,
+    /// How long before the expiry time cached metadata is considered stale.
+    pub refresh_margin: Duration,
+}
+
+pub struct AuthorizationHook {
+    shell: String,
+    args: Vec<String>,
+    options: HookOptions,
+    stderr: broadcast::Sender<String>,
+    cache: Mutex<Vec<CacheEntry>>,
+    process: Mutex<Option<HookProcess>>,
+}
+
+struct HookProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+#[derive(Serialize)]
+struct HookRequest<'a> {
+    force: bool,
+    context: &'a CallContext,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HookResponse {
+    Error { error: String },
+    Result(AuthorizationResult),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum AuthorizationHookSerde {
+    Shell(String),
+    Config {
+        shell: String,
+        #[serde(default)]
+        mode: HookMode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_secs: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        refresh_margin_secs: Option<u64>,
+    },
+}
+
+impl AuthorizationHook {
+    pub fn new(shell: String, options: HookOptions) -> Result<Self> {
+        let args = split_shell(&shell)?;
+        if args.is_empty() {
+            bail!("no arguments")
+        }
+
+        Ok(AuthorizationHook {
+            shell,
+            args,
+            options,
+            stderr: broadcast::channel(64).0,
+            cache: Mutex::new(Vec::new()),
+            process: Mutex::new(None),
+        })
+    }
+
+    pub async fn get_headers(&self, context: &CallContext) -> Result<http::HeaderMap> {
+        let cache_lock = self.cache.lock().await;
+
+        if let Some(entry) = cache_lock.iter().rev().find(|entry| entry.matches(context)) {
+            if let Some(expires_at) = &entry.result.expiry {
+                if *expires_at - OffsetDateTime::now_utc() > self.options.refresh_margin {
+                    return Ok(entry.result.metadata.clone());
+                }
+            }
+        }
+
+        self.get_header_inner(cache_lock, context, false).await
+    }
+
+    pub async fn get_headers_force(&self, context: &CallContext) -> Result<http::HeaderMap> {
+        let cache_lock = self.cache.lock().await;
+        self.get_header_inner(cache_lock, context, true).await
+    }
+
+    async fn get_header_inner<'a>(
+        &self,
+        mut lock: MutexGuard<'a, Vec<CacheEntry>>,
+        context: &CallContext,
+        force: bool,
+    ) -> Result<http::HeaderMap> {
+        let run = async {
+            match self.options.mode {
+                HookMode::OneShot => self.run_once(context).await,
+                HookMode::Persistent => self.run_persistent(&HookRequest { force, context }).await,
+            }
+        };
+        // Dropping the future kills the hook process, so a hook that hangs doesn't linger after
+        // the timeout elapses.
+        let result = match self.timeout() {
+            Some(timeout) => tokio::time::timeout(timeout, run).await.map_err(|_| {
+                anyhow!(
+                    "authorization plugin timed out after {} seconds",
+                    timeout.as_secs()
+                )
+            })??,
+            None => run.await?,
+        };
+
+        let header = result.metadata.clone();
+
+        let now = OffsetDateTime::now_utc();
+        lock.retain(|entry| {
+            !entry.matches(context) && entry.result.expiry.map_or(false, |expiry| expiry > now)
+        });
+        lock.push(CacheEntry {
+            key: context.project(&result.depends_on),
+            result,
+        });
+
+        Ok(header)
+    }
+
+    async fn run_once(&self, context: &CallContext) -> Result<AuthorizationResult> {
+        let mut child = Command::new(&self.args[0])
+            .args(&self.args[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("failed to run authorization plugin")?;
+        tracing::info!(
+            "Executing command with process id {}: {}",
+            child.id().unwrap_or_default(),
+            self.shell
+        );
+
+        let input = serde_json::to_vec(context)?;
+        let mut stdin = child.stdin.take().context("failed to open stdin")?;
+        let write_input = async move {
+            // The hook may not read its input, so failure to write it isn't an error.
+            if let Err(err) = stdin.write_all(&input).await {
+                tracing::debug!("Failed to write to authorization plugin stdin: {}", err);
+            }
+        };
+
+        let stderr = child.stderr.take().context("failed to open stderr")?;
+        let read_stderr = read_stderr(stderr, self.stderr.clone());
+
+        let ((), stderr, output) = tokio::join!(write_input, read_stderr, child.wait_with_output());
+        let output = output.context("failed to run authorization plugin")?;
+
+        if !output.status.success() {
+            let detail = if !stderr.is_empty() {
+                format!(": {}", stderr.trim())
+            } else if !output.stdout.is_empty() {
+                format!(": {}", String::from_utf8_lossy(&output.stdout).trim())
+            } else {
+                String::new()
+            };
+
+            bail!("authorization plugin returned {}{}", output.status, detail)
+        }
+
+        serde_json::from_slice(&output.stdout).context("authorization plugin returned invalid data")
+    }
+
+    async fn run_persistent(&self, request: &HookRequest) -> Result<AuthorizationResult> {
+        let request = serde_json::to_string(request)?;
+        let mut process_lock = self.process.lock().await;
+
+        // The process is taken out of the lock while a request is in progress, so that if this
+        // future is cancelled the process is killed rather than left with a pending response.
+        let mut process = match process_lock.take() {
+            Some(mut process) => {
+                if process.is_running() {
+                    process
+                } else {
+                    self.spawn_process()?
+                }
+            }
+            None => self.spawn_process()?,
+        };
+        let response = match process.request(&request).await {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::warn!("Restarting authorization plugin: {:#}", err);
+                drop(process);
+                process = self.spawn_process()?;
+                process.request(&request).await?
+            }
+        };
+        *process_lock = Some(process);
+
+        match serde_json::from_str(&response)
+            .context("authorization plugin returned invalid data")?
+        {
+            HookResponse::Result(result) => Ok(result),
+            HookResponse::Error { error } => bail!("authorization plugin failed: {}", error),
+        }
+    }
+
+    fn spawn_process(&self) -> Result<HookProcess> {
+        let mut child = Command::new(&self.args[0])
+            .args(&self.args[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("failed to run authorization plugin")?;
+        tracing::info!(
+            "Started persistent command with process id {}: {}",
+            child.id().unwrap_or_default(),
+            self.shell
+        );
+
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(read_stderr(stderr, self.stderr.clone()));
+        }
+
+        Ok(HookProcess {
+            stdin: child.stdin.take().context("failed to open stdin")?,
+            stdout: BufReader::new(child.stdout.take().context("failed to open stdout")?).lines(),
+            child,
+        })
+    }
+
+    pub fn shell(&self) -> &str {
+        &self.shell
+    }
+
+    /// Gets how long to wait for the hook, if there is a limit.
+    fn timeout(&self) -> Option<Duration> {
+        match self.options.mode {
+            HookMode::OneShot => self.options.timeout,
+            HookMode::Persistent => {
+                Some(self.options.timeout.unwrap_or(DEFAULT_PERSISTENT_TIMEOUT))
+            }
+        }
+    }
+
+    pub fn options(&self) -> HookOptions {
+        self.options
+    }
+
+    /// Subscribe to lines written to stderr by the hook process.
+    pub fn subscribe_stderr(&self) -> broadcast::Receiver<String> {
+        self.stderr.subscribe()
+    }
+}
+
+impl Default for HookOptions {
+    fn default() -> Self {
+        HookOptions {
+            mode: HookMode::default(),
+            timeout: None,
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+        }
+    }
+}
+
+impl CallContext {
+    pub fn new(
+        address: impl Into<String>,
+        service: impl Into<String>,
+        method: impl Into<String>,
+    ) -> Self {
+        CallContext {
+            address: address.into(),
+            service: service.into(),
+            method: method.into(),
+            request: None,
+        }
+    }
+
+    pub fn with_request(mut self, request: &[u8]) -> Self {
+        self.request = Some(STANDARD.encode(request));
+        self
+    }
+
+    /// Returns a copy of this context containing only the given fields.
+    fn project(&self, fields: &[ContextField]) -> CallContext {
+        CallContext {
+            address: if fields.contains(&ContextField::Address) {
+                self.address.clone()
+            } else {
+                String::new()
+            },
+            service: if fields.contains(&ContextField::Service) {
+                self.service.clone()
+            } else {
+                String::new()
+            },
+            method: if fields.contains(&ContextField::Method) {
+                self.method.clone()
+            } else {
+                String::new()
+            },
+            request: if fields.contains(&ContextField::Request) {
+                self.request.clone()
+            } else {
+                None
+            },
+        }
+    }
+}
+
+impl CacheEntry {
+    fn matches(&self, context: &CallContext) -> bool {
+        self.key == context.project(&self.result.depends_on)
+    }
+}
+
+impl HookProcess {
+    fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    async fn request(&mut self, request: &str) -> Result<String> {
+        self.stdin.write_all(request.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await?;
+
+        match self.stdout.next_line().await? {
+            Some(response) => Ok(response),
+            None => bail!("authorization plugin exited unexpectedly"),
+        }
+    }
+}
+
+impl fmt::Debug for AuthorizationHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthorizationHook")
+            .field("shell", &self.shell)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl Serialize for AuthorizationHook {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.options == HookOptions::default() {
+            AuthorizationHookSerde::Shell(self.shell.clone())
+        } else {
+            AuthorizationHookSerde::Config {
+                shell: self.shell.clone(),
+                mode: self.options.mode,
+                timeout_secs: self.options.timeout.map(|timeout| timeout.as_secs()),
+                refresh_margin_secs: Some(self.options.refresh_margin)
+                    .filter(|&margin| margin != DEFAULT_REFRESH_MARGIN)
+                    .map(|margin| margin.as_secs()),
+            }
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AuthorizationHook {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (shell, options) = match AuthorizationHookSerde::deserialize(deserializer)? {
+            AuthorizationHookSerde::Shell(shell) => (shell, HookOptions::default()),
+            AuthorizationHookSerde::Config {
+                shell,
+                mode,
+                timeout_secs,
+                refresh_margin_secs,
+            } => (
+                shell,
+                HookOptions {
+                    mode,
+                    timeout: timeout_secs.map(Duration::from_secs),
+                    refresh_margin: refresh_margin_secs
+                        .map_or(DEFAULT_REFRESH_MARGIN, Duration::from_secs),
+                },
+            ),
+        };
+        AuthorizationHook::new(shell, options).map_err(<D::Error as serde::de::Error>::custom)
+    }
+}
+
+/// Reads lines from the hook's stderr until it is closed, forwarding them to the log and any
+/// subscribers. Returns everything that was read.
+async fn read_stderr(stderr: ChildStderr, sender: broadcast::Sender<String>) -> String {
+    let mut output = String::new();
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        tracing::info!("Authorization plugin: {}", line);
+        output.push_str(&line);
+        output.push('\n');
+        // There may be no subscribers, which isn't an error.
+        let _ = sender.send(line);
+    }
+    output
+}
+
+#[cfg(windows)]
+fn split_shell(shell: &str) -> Result<Vec<String>> {
+    use std::{ffi::OsStr, io, os::windows::ffi::OsStrExt};
+
+    use windows::{
+        core::PCWSTR,
+        Win32::{Foundation::HLOCAL, System::Memory::LocalFree, UI::Shell::CommandLineToArgvW},
+    };
+
+    unsafe {
+        let mut shell_utf16 = Vec::with_capacity(shell.len() * 2 + 1);
+        shell_utf16.extend(OsStr::new(shell).encode_wide());
+        shell_utf16.push(0);
+
+        let mut num_args: i32 = 0;
+        let arg_list = CommandLineToArgvW(PCWSTR(shell_utf16.as_ptr()), &mut num_args);
+        if arg_list.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let mut results = Vec::with_capacity(num_args as usize);
+        for i in 0..num_args {
+            results.push((*arg_list.offset(i as isize)).to_string().unwrap());
+        }
+
+        _ = LocalFree(HLOCAL(arg_list as isize));
+        Ok(results)
+    }
+}
+
+#[cfg(not(windows))]
+fn split_shell(shell: &str) -> Result<Vec<String>> {
+    shell_words::split(shell).map_err(Into::into)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Responds to each request with a count of the requests received, and whether it was forced.
+    const COUNTER: &str = r#"
+        n=0
+        while read -r line; do
+            n=$((n + 1))
+            case "$line" in
+                *'"force":true'*) force=true ;;
+                *) force=false ;;
+            esac
+            echo "{\"metadata\":{\"x-count\":\"$n\",\"x-force\":\"$force\"}}"
+        done
+    "#;
+
+    fn persistent(script: &str, timeout: Option<Duration>) -> AuthorizationHook {
+        AuthorizationHook::new(
+            format!("sh -c {}", shell_words::quote(script)),
+            HookOptions {
+                mode: HookMode::Persistent,
+                timeout,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn header<'a>(headers: &'a http::HeaderMap, name: &str) -> &'a str {
+        headers[name].to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn persistent_line_protocol() {
+        let hook = persistent(COUNTER, None);
+        let context = CallContext::new("http://localhost:50051", "echo.Echo", "Get");
+
+        // Results without an expiry aren't reused, so each call sends a request to the same
+        // process.
+        let headers = hook.get_headers(&context).await.unwrap();
+        assert_eq!(header(&headers, "x-count"), "1");
+        assert_eq!(header(&headers, "x-force"), "false");
+
+        let headers = hook.get_headers_force(&context).await.unwrap();
+        assert_eq!(header(&headers, "x-count"), "2");
+        assert_eq!(header(&headers, "x-force"), "true");
+    }
+
+    #[tokio::test]
+    async fn persistent_error_response() {
+        let hook = persistent(
+            r#"while read -r line; do echo '{"error":"not logged in"}'; done"#,
+            None,
+        );
+
+        let err = hook.get_headers(&CallContext::default()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "authorization plugin failed: not logged in"
+        );
+    }
+
+    #[tokio::test]
+    async fn persistent_restarts_after_exit() {
+        // Responds to a single request, then exits.
+        let hook = persistent(
+            r#"read -r line; echo "{\"metadata\":{\"x-pid\":\"$$\"}}""#,
+            None,
+        );
+        let context = CallContext::default();
+
+        let first = hook.get_headers(&context).await.unwrap();
+        let second = hook.get_headers(&context).await.unwrap();
+        assert_ne!(header(&first, "x-pid"), header(&second, "x-pid"));
+    }
+
+    #[tokio::test]
+    async fn persistent_fails_if_restart_fails() {
+        let hook = persistent("exit 1", None);
+
+        assert!(hook.get_headers(&CallContext::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn persistent_timeout() {
+        // Reads requests without ever responding.
+        let hook = persistent(
+            "while read -r line; do :; done",
+            Some(Duration::from_millis(200)),
+        );
+
+        let err = hook.get_headers(&CallContext::default()).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+    }
+
+    #[test]
+    fn persistent_default_timeout() {
+        assert_eq!(
+            persistent(COUNTER, None).timeout(),
+            Some(DEFAULT_PERSISTENT_TIMEOUT)
+        );
+        assert_eq!(
+            persistent(COUNTER, Some(Duration::from_secs(5))).timeout(),
+            Some(Duration::from_secs(5))
+        );
+
+        let one_shot = AuthorizationHook::new("true".to_owned(), HookOptions::default()).unwrap();
+        assert_eq!(one_shot.timeout(), None);
+    }
+
+    #[tokio::test]
+    async fn depends_on_cache_key() {
+        let hook = persistent(
+            r#"
+                n=0
+                while read -r line; do
+                    n=$((n + 1))
+                    echo "{\"metadata\":{\"x-count\":\"$n\"},\"expiry\":\"2999-01-01T00:00:00Z\",\"depends_on\":[\"method\"]}"
+                done
+            "#,
+            None,
+        );
+        let get = CallContext::new("http://localhost:50051", "echo.Echo", "Get");
+        let get_elsewhere = CallContext::new("http://localhost:50052", "echo.Echo", "Get");
+        let list = CallContext::new("http://localhost:50051", "echo.Echo", "List");
+
+        let count = |headers: http::HeaderMap| header(&headers, "x-count").to_owned();
+        assert_eq!(count(hook.get_headers(&get).await.unwrap()), "1");
+        // The address isn't part of the key, so the cached result is reused.
+        assert_eq!(count(hook.get_headers(&get_elsewhere).await.unwrap()), "1");
+        assert_eq!(count(hook.get_headers(&list).await.unwrap()), "2");
+        assert_eq!(count(hook.get_headers(&get).await.unwrap()), "1");
+        assert_eq!(count(hook.get_headers(&list).await.unwrap()), "2");
+        // Forcing a refresh replaces only the entry for the same method.
+        assert_eq!(count(hook.get_headers_force(&get).await.unwrap()), "3");
+        assert_eq!(count(hook.get_headers(&get).await.unwrap()), "3");
+        assert_eq!(count(hook.get_headers(&list).await.unwrap()), "2");
+    }
+}
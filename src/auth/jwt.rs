@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    rand::SystemRandom,
+    signature::{
+        EcdsaKeyPair, Ed25519KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+        ECDSA_P384_SHA384_FIXED_SIGNING, RSA_PKCS1_SHA256,
+    },
+};
+use rustls_pemfile::Item;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+
+/// Settings for generating a self-signed JWT.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JwtConfig {
+    /// A PEM or DER encoded RSA, ECDSA or Ed25519 private key.
+    pub key_file: PathBuf,
+    /// Claims to include in the token. The `iat` and `exp` claims are always set from the
+    /// current time and `lifetime_secs`.
+    #[serde(default)]
+    pub claims: Map<String, Value>,
+    pub lifetime_secs: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+}
+
+enum SigningKey {
+    Rsa(RsaKeyPair),
+    Ecdsa(EcdsaKeyPair, &'static str),
+    Ed25519(Ed25519KeyPair),
+}
+
+impl JwtConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.key_file.as_os_str().is_empty() {
+            bail!("key file is required")
+        }
+        if self.lifetime_secs == 0 {
+            bail!("lifetime must be greater than zero")
+        }
+        Ok(())
+    }
+
+    pub async fn sign(&self) -> Result<String> {
+        let key = tokio::fs::read(&self.key_file)
+            .await
+            .with_context(|| format!("failed to read key file '{}'", self.key_file.display()))?;
+        let key = SigningKey::from_file(&key)?;
+
+        let mut header = Map::new();
+        header.insert("alg".to_owned(), key.algorithm().into());
+        header.insert("typ".to_owned(), "JWT".into());
+        if let Some(key_id) = &self.key_id {
+            header.insert("kid".to_owned(), key_id.as_str().into());
+        }
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut claims = self.claims.clone();
+        claims.insert("iat".to_owned(), now.into());
+        claims.insert(
+            "exp".to_owned(),
+            now.saturating_add(self.lifetime_secs as i64).into(),
+        );
+
+        let message = format!("{}.{}", encode_json(&header)?, encode_json(&claims)?);
+        let signature = key.sign(message.as_bytes())?;
+        Ok(format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature)))
+    }
+}
+
+impl SigningKey {
+    fn from_file(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(b"-----BEGIN") {
+            return SigningKey::from_der(data);
+        }
+
+        let mut reader = data;
+        loop {
+            match rustls_pemfile::read_one(&mut reader).context("invalid PEM file")? {
+                Some(Item::PKCS8Key(der)) => return SigningKey::from_der(&der),
+                Some(Item::RSAKey(der)) => {
+                    let key = RsaKeyPair::from_der(&der)
+                        .map_err(|err| anyhow!("invalid private key: {}", err))?;
+                    return Ok(SigningKey::Rsa(key));
+                }
+                Some(Item::ECKey(_)) => bail!("EC private keys must be in PKCS#8 format"),
+                Some(_) => continue,
+                None => bail!("no private key found in key file"),
+            }
+        }
+    }
+
+    fn from_der(der: &[u8]) -> Result<Self> {
+        if let Ok(key) = RsaKeyPair::from_pkcs8(der).or_else(|_| RsaKeyPair::from_der(der)) {
+            Ok(SigningKey::Rsa(key))
+        } else if let Ok(key) = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, der) {
+            Ok(SigningKey::Ecdsa(key, "ES256"))
+        } else if let Ok(key) = EcdsaKeyPair::from_pkcs8(&ECDSA_P384_SHA384_FIXED_SIGNING, der) {
+            Ok(SigningKey::Ecdsa(key, "ES384"))
+        } else if let Ok(key) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+            Ok(SigningKey::Ed25519(key))
+        } else {
+            bail!("unsupported private key type, expected an RSA, ECDSA or Ed25519 key")
+        }
+    }
+
+    fn algorithm(&self) -> &'static str {
+        match self {
+            SigningKey::Rsa(_) => "RS256",
+            SigningKey::Ecdsa(_, algorithm) => algorithm,
+            SigningKey::Ed25519(_) => "EdDSA",
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let rng = SystemRandom::new();
+        match self {
+            SigningKey::Rsa(key) => {
+                let mut signature = vec![0; key.public_modulus_len()];
+                key.sign(&RSA_PKCS1_SHA256, &rng, message, &mut signature)
+                    .map_err(|_| anyhow!("failed to sign token"))?;
+                Ok(signature)
+            }
+            SigningKey::Ecdsa(key, _) => {
+                let signature = key
+                    .sign(&rng, message)
+                    .map_err(|_| anyhow!("failed to sign token"))?;
+                Ok(signature.as_ref().to_vec())
+            }
+            SigningKey::Ed25519(key) => Ok(key.sign(message).as_ref().to_vec()),
+        }
+    }
+}
+
+fn encode_json(value: &Map<String, Value>) -> Result<String> {
+    Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(value)?))
+}
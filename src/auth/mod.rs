@@ -0,0 +1,67 @@
+//! Sources of authorization metadata for requests, such as hook commands and OAuth 2.0 providers.
+
+mod builtin;
+mod hook;
+mod jwt;
+mod oauth2;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+pub use self::{
+    builtin::BuiltinAuth,
+    hook::{AuthorizationHook, CallContext, HookMode, HookOptions},
+    jwt::JwtConfig,
+    oauth2::{OAuth2Config, OAuth2Grant, OAuth2Provider},
+};
+
+/// How long before their expiry time cached credentials are refreshed, by default.
+const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// A source of authorization metadata for requests to a service.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AuthProvider {
+    Hook(AuthorizationHook),
+    Builtin(BuiltinAuth),
+    OAuth2(OAuth2Provider),
+}
+
+impl AuthProvider {
+    pub async fn get_headers(&self, context: &CallContext) -> Result<http::HeaderMap> {
+        match self {
+            AuthProvider::Hook(hook) => hook.get_headers(context).await,
+            AuthProvider::Builtin(builtin) => builtin.get_headers().await,
+            AuthProvider::OAuth2(oauth2) => oauth2.get_headers(false).await,
+        }
+    }
+
+    /// Gets metadata for a request, ignoring any cached values.
+    pub async fn get_headers_force(&self, context: &CallContext) -> Result<http::HeaderMap> {
+        match self {
+            AuthProvider::Hook(hook) => hook.get_headers_force(context).await,
+            AuthProvider::Builtin(builtin) => builtin.get_headers().await,
+            AuthProvider::OAuth2(oauth2) => oauth2.get_headers(true).await,
+        }
+    }
+
+    /// Whether fetching the metadata again may give a different result, so it is worth retrying
+    /// requests rejected as unauthenticated.
+    pub fn can_refresh(&self) -> bool {
+        match self {
+            AuthProvider::Hook(_) | AuthProvider::OAuth2(_) => true,
+            AuthProvider::Builtin(_) => false,
+        }
+    }
+
+    /// Subscribe to diagnostic output from the provider, if it produces any.
+    pub fn subscribe_stderr(&self) -> Option<broadcast::Receiver<String>> {
+        match self {
+            AuthProvider::Hook(hook) => Some(hook.subscribe_stderr()),
+            AuthProvider::Builtin(_) | AuthProvider::OAuth2(_) => None,
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use std::{fmt, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    uri::Scheme,
+    HeaderValue, Uri,
+};
+use hyper::{client::HttpConnector, Body, Client};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+
+use super::DEFAULT_REFRESH_MARGIN;
+use crate::grpc::channel;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Settings for fetching access tokens from an OAuth2 token endpoint.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    pub token_url: String,
+    #[serde(flatten)]
+    pub grant: OAuth2Grant,
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub client_secret: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub scope: String,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "grant_type", rename_all = "snake_case")]
+pub enum OAuth2Grant {
+    ClientCredentials,
+    RefreshToken { refresh_token: String },
+}
+
+pub struct OAuth2Provider {
+    config: OAuth2Config,
+    state: Mutex<TokenState>,
+}
+
+#[derive(Default)]
+struct TokenState {
+    cached: Option<(http::HeaderMap, OffsetDateTime)>,
+    /// The latest refresh token issued by the server, if it has replaced the configured one.
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum OAuth2ProviderSerde {
+    #[serde(rename = "oauth2")]
+    OAuth2(OAuth2Config),
+}
+
+impl OAuth2Config {
+    pub fn validate(&self) -> Result<()> {
+        let uri: Uri = self.token_url.parse().context("invalid token URL")?;
+        match uri.scheme() {
+            Some(scheme) if *scheme == Scheme::HTTP || *scheme == Scheme::HTTPS => (),
+            _ => bail!("token URL must start with http:// or https://"),
+        }
+        if self.client_id.is_empty() {
+            bail!("client ID is required")
+        }
+        if let OAuth2Grant::RefreshToken { refresh_token } = &self.grant {
+            if refresh_token.is_empty() {
+                bail!("refresh token is required")
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OAuth2Provider {
+    pub fn new(config: OAuth2Config) -> Result<Self> {
+        config.validate()?;
+        Ok(OAuth2Provider {
+            config,
+            state: Mutex::new(TokenState::default()),
+        })
+    }
+
+    pub fn config(&self) -> &OAuth2Config {
+        &self.config
+    }
+
+    pub async fn get_headers(&self, force: bool) -> Result<http::HeaderMap> {
+        let mut state = self.state.lock().await;
+
+        if !force {
+            if let Some((headers, expires_at)) = &state.cached {
+                if *expires_at - OffsetDateTime::now_utc() > DEFAULT_REFRESH_MARGIN {
+                    return Ok(headers.clone());
+                }
+            }
+        }
+
+        let refresh_token = match &self.config.grant {
+            OAuth2Grant::ClientCredentials => None,
+            OAuth2Grant::RefreshToken { refresh_token } => Some(
+                state
+                    .refresh_token
+                    .as_ref()
+                    .unwrap_or(refresh_token)
+                    .clone(),
+            ),
+        };
+
+        let now = OffsetDateTime::now_utc();
+        let response = tokio::time::timeout(
+            REQUEST_TIMEOUT,
+            self.request_token(refresh_token.as_deref()),
+        )
+        .await
+        .context("token request timed out")??;
+
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", response.access_token))
+            .context("token endpoint returned an invalid access token")?;
+        value.set_sensitive(true);
+        let mut headers = http::HeaderMap::new();
+        headers.insert(AUTHORIZATION, value);
+
+        // Like authorization hooks, tokens without an expiry time are not cached.
+        state.cached = response
+            .expires_in
+            .map(|secs| (headers.clone(), now + Duration::from_secs(secs)));
+        if response.refresh_token.is_some() && refresh_token.is_some() {
+            state.refresh_token = response.refresh_token;
+        }
+
+        Ok(headers)
+    }
+
+    async fn request_token(&self, refresh_token: Option<&str>) -> Result<TokenResponse> {
+        let mut body = form_urlencoded::Serializer::new(String::new());
+        match refresh_token {
+            None => body.append_pair("grant_type", "client_credentials"),
+            Some(refresh_token) => body
+                .append_pair("grant_type", "refresh_token")
+                .append_pair("refresh_token", refresh_token),
+        };
+        if !self.config.scope.is_empty() {
+            body.append_pair("scope", &self.config.scope);
+        }
+
+        // Client credentials are form-encoded before being used for basic authentication, as
+        // described in RFC 6749 section 2.3.1.
+        let credentials = format!(
+            "{}:{}",
+            form_urlencoded::byte_serialize(self.config.client_id.as_bytes()).collect::<String>(),
+            form_urlencoded::byte_serialize(self.config.client_secret.as_bytes())
+                .collect::<String>(),
+        );
+
+        let request = http::Request::post(&self.config.token_url)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(ACCEPT, "application/json")
+            .header(
+                AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode(credentials)),
+            )
+            .body(Body::from(body.finish()))?;
+
+        tracing::debug!("Requesting access token from {}", self.config.token_url);
+        let response = client()?
+            .request(request)
+            .await
+            .context("failed to connect to token endpoint")?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .context("failed to read response from token endpoint")?;
+
+        if !status.is_success() {
+            match serde_json::from_slice::<ErrorResponse>(&body) {
+                Ok(ErrorResponse {
+                    error,
+                    error_description: Some(description),
+                }) => bail!(
+                    "token endpoint returned {}: {}: {}",
+                    status,
+                    error,
+                    description
+                ),
+                Ok(ErrorResponse { error, .. }) => {
+                    bail!("token endpoint returned {}: {}", status, error)
+                }
+                Err(_) => bail!("token endpoint returned {}", status),
+            }
+        }
+
+        serde_json::from_slice(&body).context("token endpoint returned invalid data")
+    }
+}
+
+fn client() -> Result<&'static Client<HttpsConnector<HttpConnector>>> {
+    static CLIENT: OnceCell<Client<HttpsConnector<HttpConnector>>> = OnceCell::new();
+
+    CLIENT.get_or_try_init(|| {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+
+        // Plain HTTP is allowed so that a local stand-in token server can be used for testing.
+        let https = HttpsConnectorBuilder::new()
+            .with_tls_config(channel::tls_config(true)?)
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(http);
+
+        Ok(Client::builder().build(https))
+    })
+}
+
+impl fmt::Debug for OAuth2Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.config, f)
+    }
+}
+
+impl fmt::Debug for OAuth2Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid writing secrets to the log.
+        f.debug_struct("OAuth2Config")
+            .field("token_url", &self.token_url)
+            .field("client_id", &self.client_id)
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Serialize for OAuth2Provider {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        OAuth2ProviderSerde::OAuth2(self.config.clone()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OAuth2Provider {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let OAuth2ProviderSerde::OAuth2(config) = OAuth2ProviderSerde::deserialize(deserializer)?;
+        OAuth2Provider::new(config).map_err(<D::Error as serde::de::Error>::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Binds a stand-in token endpoint, returning its listener and URL.
+    async fn bind() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let token_url = format!("http://{}/token", listener.local_addr().unwrap());
+        (listener, token_url)
+    }
+
+    fn provider(token_url: String, grant: OAuth2Grant) -> OAuth2Provider {
+        OAuth2Provider::new(OAuth2Config {
+            token_url,
+            grant,
+            client_id: "client".to_owned(),
+            client_secret: String::new(),
+            scope: String::new(),
+        })
+        .unwrap()
+    }
+
+    /// Accepts a single token request and responds with the given status and body. Returns the
+    /// headers and body of the request.
+    async fn serve_token(listener: &TcpListener, status: &str, response: &str) -> (String, String) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut request = Vec::new();
+        let (head, body) = loop {
+            let mut buf = [0; 1024];
+            let len = stream.read(&mut buf).await.unwrap();
+            assert_ne!(len, 0, "connection closed before the request was read");
+            request.extend_from_slice(&buf[..len]);
+
+            let text = String::from_utf8_lossy(&request).into_owned();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let content_length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if body.len() >= content_length {
+                    break (head.to_owned(), body.to_owned());
+                }
+            }
+        };
+
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        (head, body)
+    }
+
+    #[tokio::test]
+    async fn client_credentials() {
+        let (listener, token_url) = bind().await;
+        let server = tokio::spawn(async move {
+            serve_token(
+                &listener,
+                "200 OK",
+                r#"{"access_token":"abc123","token_type":"Bearer","expires_in":3600}"#,
+            )
+            .await
+        });
+
+        let provider = OAuth2Provider::new(OAuth2Config {
+            token_url,
+            grant: OAuth2Grant::ClientCredentials,
+            client_id: "my client".to_owned(),
+            client_secret: "s3cret".to_owned(),
+            scope: "read write".to_owned(),
+        })
+        .unwrap();
+
+        let headers = provider.get_headers(false).await.unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Bearer abc123");
+
+        let (head, body) = server.await.unwrap();
+        assert!(head.starts_with("POST /token HTTP/1.1\r\n"));
+        assert!(head
+            .to_ascii_lowercase()
+            .contains("content-type: application/x-www-form-urlencoded"));
+        let credentials = STANDARD.encode("my+client:s3cret");
+        assert!(head.contains(&format!("Basic {}", credentials)));
+        assert_eq!(body, "grant_type=client_credentials&scope=read+write");
+
+        // The token has an expiry time, so it is cached rather than requested again.
+        let headers = provider.get_headers(false).await.unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Bearer abc123");
+    }
+
+    #[tokio::test]
+    async fn refresh_token_grant() {
+        let (listener, token_url) = bind().await;
+        let server = tokio::spawn(async move {
+            serve_token(
+                &listener,
+                "200 OK",
+                r#"{"access_token":"abc123","expires_in":3600}"#,
+            )
+            .await
+        });
+
+        let provider = provider(
+            token_url,
+            OAuth2Grant::RefreshToken {
+                refresh_token: "refresh/1".to_owned(),
+            },
+        );
+        let headers = provider.get_headers(false).await.unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Bearer abc123");
+
+        let (_, body) = server.await.unwrap();
+        assert_eq!(body, "grant_type=refresh_token&refresh_token=refresh%2F1");
+    }
+
+    #[tokio::test]
+    async fn rotated_refresh_token_replaces_configured_one() {
+        let (listener, token_url) = bind().await;
+        let server = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for response in [
+                r#"{"access_token":"first","refresh_token":"rotated"}"#,
+                r#"{"access_token":"second"}"#,
+                r#"{"access_token":"third"}"#,
+            ] {
+                let (_, body) = serve_token(&listener, "200 OK", response).await;
+                bodies.push(body);
+            }
+            bodies
+        });
+
+        let provider = provider(
+            token_url,
+            OAuth2Grant::RefreshToken {
+                refresh_token: "original".to_owned(),
+            },
+        );
+        // The tokens have no expiry time, so each call requests a new one.
+        for token in ["first", "second", "third"] {
+            let headers = provider.get_headers(false).await.unwrap();
+            assert_eq!(headers[AUTHORIZATION], format!("Bearer {}", token).as_str());
+        }
+
+        // The rotated token is kept until the server issues another one.
+        assert_eq!(
+            server.await.unwrap(),
+            [
+                "grant_type=refresh_token&refresh_token=original",
+                "grant_type=refresh_token&refresh_token=rotated",
+                "grant_type=refresh_token&refresh_token=rotated",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let (listener, token_url) = bind().await;
+        let server = tokio::spawn(async move {
+            serve_token(
+                &listener,
+                "400 Bad Request",
+                r#"{"error":"invalid_grant","error_description":"refresh token expired"}"#,
+            )
+            .await;
+            serve_token(&listener, "503 Service Unavailable", "").await;
+        });
+
+        let provider = provider(token_url, OAuth2Grant::ClientCredentials);
+        let err = provider.get_headers(false).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "token endpoint returned 400 Bad Request: invalid_grant: refresh token expired"
+        );
+        let err = provider.get_headers(false).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "token endpoint returned 503 Service Unavailable"
+        );
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_access_token() {
+        let (listener, token_url) = bind().await;
+        let server = tokio::spawn(async move {
+            serve_token(
+                &listener,
+                "200 OK",
+                r#"{"token_type":"Bearer","expires_in":3600}"#,
+            )
+            .await
+        });
+
+        let provider = provider(token_url, OAuth2Grant::ClientCredentials);
+        let err = provider.get_headers(false).await.unwrap_err();
+        assert_eq!(err.to_string(), "token endpoint returned invalid data");
+
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,220 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Error, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+use tonic::metadata::MetadataMap;
+
+const METADATA_PREFIX: &str = "metadata:";
+
+/// Where to find a value to capture in a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A JSON Pointer into the response message.
+    Json(String),
+    /// A key in the response headers or trailers.
+    Metadata(String),
+}
+
+impl FromStr for Source {
+    type Err = Error;
+
+    /// Parses a JSON Pointer such as `/id`, a JSONPath such as `$.items[0].id`, or a metadata key
+    /// such as `metadata:x-session-token`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(key) = s.strip_prefix(METADATA_PREFIX) {
+            let key = key.trim();
+            if key.is_empty() {
+                bail!("expected a metadata key after '{}'", METADATA_PREFIX)
+            }
+            Ok(Source::Metadata(key.to_ascii_lowercase()))
+        } else if s.starts_with('/') || s.starts_with('$') {
+            Ok(Source::Json(json_pointer(s)?))
+        } else {
+            bail!(
+                "expected a JSON Pointer, a JSONPath or '{}<key>'",
+                METADATA_PREFIX
+            )
+        }
+    }
+}
+
+impl Source {
+    /// Gets the value from a response message. Strings are returned as is, and other values are
+    /// formatted as JSON.
+    pub fn from_json(&self, json: &Value) -> Option<String> {
+        match self {
+            Source::Json(pointer) => json.pointer(pointer).map(|value| match value {
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            }),
+            Source::Metadata(_) => None,
+        }
+    }
+
+    /// Gets the value from response headers or trailers. Binary values are encoded as base64.
+    pub fn from_metadata(&self, metadata: &MetadataMap) -> Option<String> {
+        match self {
+            Source::Metadata(key) if key.ends_with("-bin") => {
+                let value = metadata.get_bin(key.as_str())?.to_bytes().ok()?;
+                Some(STANDARD.encode(value))
+            }
+            Source::Metadata(key) => {
+                let value = metadata.get(key.as_str())?.to_str().ok()?;
+                Some(value.to_owned())
+            }
+            Source::Json(_) => None,
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        matches!(self, Source::Json(_))
+    }
+}
+
+/// Parses a JSON Pointer such as `/id`, or converts a JSONPath such as `$.items[0].id` to one.
+pub(crate) fn json_pointer(s: &str) -> Result<String> {
+    if s.starts_with('/') {
+        Ok(s.to_owned())
+    } else if s.starts_with('$') {
+        json_path_to_pointer(s, false)
+    } else {
+        bail!("expected a JSON Pointer or a JSONPath")
+    }
+}
+
+/// Like [`json_pointer`], but a JSONPath may also use the `.*` and `[*]` wildcards, which become
+/// a `*` segment of the pointer.
+pub(crate) fn json_pointer_pattern(s: &str) -> Result<String> {
+    if s.starts_with('$') {
+        json_path_to_pointer(s, true)
+    } else {
+        json_pointer(s)
+    }
+}
+
+/// Converts a simple JSONPath, made of `.name`, `['name']` and `[index]` selectors, to a JSON
+/// Pointer. If `wildcards` is set, `.*` and `[*]` are converted to a `*` segment.
+fn json_path_to_pointer(path: &str, wildcards: bool) -> Result<String> {
+    let Some(mut rest) = path.strip_prefix('$') else {
+        bail!("a JSONPath must start with '$'")
+    };
+
+    let mut pointer = String::new();
+    while !rest.is_empty() {
+        let token = if let Some(tail) = rest.strip_prefix('.') {
+            let end = tail
+                .find(|c: char| c == '.' || c == '[')
+                .unwrap_or(tail.len());
+            let name = &tail[..end];
+            if name.is_empty() {
+                bail!("recursive descent is not supported")
+            } else if name == "*" && !wildcards {
+                bail!("wildcards are not supported")
+            }
+            rest = &tail[end..];
+            name
+        } else if let Some(tail) = rest.strip_prefix('[') {
+            let end = tail.find(']').context("unterminated '['")?;
+            let selector = tail[..end].trim();
+            rest = &tail[end + 1..];
+            if let Some(name) = unquote(selector) {
+                name
+            } else if selector == "*" {
+                if !wildcards {
+                    bail!("wildcards are not supported")
+                }
+                selector
+            } else if !selector.is_empty() && selector.bytes().all(|b| b.is_ascii_digit()) {
+                selector
+            } else {
+                bail!("unsupported selector '[{}]'", selector)
+            }
+        } else {
+            bail!("unexpected '{}'", rest)
+        };
+
+        pointer.push('/');
+        pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+    }
+
+    Ok(pointer)
+}
+
+fn unquote(s: &str) -> Option<&str> {
+    s.strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .or_else(|| s.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pointer(path: &str) -> String {
+        json_path_to_pointer(path, false).unwrap()
+    }
+
+    fn error(path: &str) -> String {
+        json_path_to_pointer(path, false).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn json_path_names() {
+        assert_eq!(pointer("$"), "");
+        assert_eq!(pointer("$.a"), "/a");
+        assert_eq!(pointer("$.a.b"), "/a/b");
+        assert_eq!(pointer("$['a.b']"), "/a.b");
+        assert_eq!(pointer("$[\"a.b\"].c"), "/a.b/c");
+        assert_eq!(pointer("$['a']['b']"), "/a/b");
+    }
+
+    #[test]
+    fn json_path_indices() {
+        assert_eq!(pointer("$.items[0]"), "/items/0");
+        assert_eq!(pointer("$.items[12].id"), "/items/12/id");
+        assert_eq!(pointer("$[ 3 ]"), "/3");
+    }
+
+    #[test]
+    fn json_path_escapes_pointer_characters() {
+        assert_eq!(pointer("$['a/b']"), "/a~1b");
+        assert_eq!(pointer("$['~c']"), "/~0c");
+        assert_eq!(pointer("$.x~y"), "/x~0y");
+        assert_eq!(pointer("$['~/']"), "/~0~1");
+    }
+
+    #[test]
+    fn json_path_wildcards() {
+        assert_eq!(
+            json_path_to_pointer("$.items[*].id", true).unwrap(),
+            "/items/*/id"
+        );
+        assert_eq!(json_path_to_pointer("$.meta.*", true).unwrap(), "/meta/*");
+        assert_eq!(error("$.items[*].id"), "wildcards are not supported");
+        assert_eq!(error("$.meta.*"), "wildcards are not supported");
+    }
+
+    #[test]
+    fn invalid_json_path() {
+        assert_eq!(error("a.b"), "a JSONPath must start with '$'");
+        assert_eq!(error("$..a"), "recursive descent is not supported");
+        assert_eq!(error("$."), "recursive descent is not supported");
+        assert_eq!(error("$.items[0"), "unterminated '['");
+        assert_eq!(error("$.items[-1]"), "unsupported selector '[-1]'");
+        assert_eq!(error("$.items[]"), "unsupported selector '[]'");
+        assert_eq!(
+            error("$.items[?(@.id)]"),
+            "unsupported selector '[?(@.id)]'"
+        );
+        assert_eq!(error("$a"), "unexpected 'a'");
+    }
+
+    #[test]
+    fn json_pointer_forms() {
+        assert_eq!(json_pointer("/items/0").unwrap(), "/items/0");
+        assert_eq!(json_pointer("$.items[0]").unwrap(), "/items/0");
+        assert!(json_pointer("items").is_err());
+    }
+}
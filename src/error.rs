@@ -41,7 +41,11 @@ pub fn fmt_grpc_err(err: &anyhow::Error) -> ArcStr {
     }
 }
 
-fn fmt_code(code: Code) -> &'static str {
+pub fn is_unauthenticated(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<Status>(), Some(status) if status.code() == Code::Unauthenticated)
+}
+
+pub fn fmt_code(code: Code) -> &'static str {
     match code {
         Code::Ok => "OK",
         Code::Cancelled => "CANCELLED",
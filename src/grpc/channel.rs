@@ -76,33 +76,8 @@ async fn connect(uri: Uri, verify_certs: bool) -> Result<Channel> {
         http.enforce_http(false);
         http.set_nodelay(true);
 
-        let rustls_config = if verify_certs {
-            static ROOT_STORE: OnceCell<RootCertStore> = OnceCell::new();
-
-            let root_store = ROOT_STORE
-                .get_or_try_init::<_, Error>(|| {
-                    let mut roots = RootCertStore::empty();
-                    for cert in rustls_native_certs::load_native_certs()? {
-                        roots.add(&rustls::Certificate(cert.0))?;
-                    }
-                    Ok(roots)
-                })
-                .context("failed to load trusted root certificate store")?
-                .clone();
-
-            rustls::ClientConfig::builder()
-                .with_safe_defaults()
-                .with_root_certificates(root_store)
-                .with_no_client_auth()
-        } else {
-            rustls::ClientConfig::builder()
-                .with_safe_defaults()
-                .with_custom_certificate_verifier(Arc::new(DangerousCertificateVerifier))
-                .with_no_client_auth()
-        };
-
         let https = HttpsConnectorBuilder::new()
-            .with_tls_config(rustls_config)
+            .with_tls_config(tls_config(verify_certs)?)
             .https_only()
             .enable_http2()
             .wrap_connector(http);
@@ -113,6 +88,33 @@ async fn connect(uri: Uri, verify_certs: bool) -> Result<Channel> {
     }
 }
 
+pub fn tls_config(verify_certs: bool) -> Result<rustls::ClientConfig> {
+    if verify_certs {
+        static ROOT_STORE: OnceCell<RootCertStore> = OnceCell::new();
+
+        let root_store = ROOT_STORE
+            .get_or_try_init::<_, Error>(|| {
+                let mut roots = RootCertStore::empty();
+                for cert in rustls_native_certs::load_native_certs()? {
+                    roots.add(&rustls::Certificate(cert.0))?;
+                }
+                Ok(roots)
+            })
+            .context("failed to load trusted root certificate store")?
+            .clone();
+
+        Ok(rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store)
+            .with_no_client_auth())
+    } else {
+        Ok(rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(DangerousCertificateVerifier))
+            .with_no_client_auth())
+    }
+}
+
 struct DangerousCertificateVerifier;
 
 impl rustls::client::ServerCertVerifier for DangerousCertificateVerifier {
@@ -1,14 +1,20 @@
-pub mod channel;
+//! A client for calling methods of any gRPC service, using messages described at runtime.
+
+pub(crate) mod channel;
 mod codec;
+pub mod reflection;
 
 use std::{
     str::FromStr,
     time::{Duration, Instant},
 };
 
-use anyhow::{Error, Result};
+use anyhow::{bail, Context, Error, Result};
 use futures::{Stream, StreamExt};
-use http::{uri::PathAndQuery, HeaderMap, Uri};
+use http::{
+    uri::{PathAndQuery, Scheme},
+    HeaderMap, Uri,
+};
 use prost_reflect::{DeserializeOptions, DynamicMessage, MessageDescriptor, SerializeOptions};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
@@ -16,25 +22,38 @@ use tonic::{client::Grpc, metadata::MetadataMap, transport::Channel, Extensions,
 
 pub type ConnectResult = Result<Client>;
 
+/// An event in the progress of a call.
 pub enum ResponseResult {
+    /// Response headers or trailers from the server.
     Metadata(MetadataMap),
     Response(Response),
+    /// The call completed successfully. No further results are sent.
     Finished,
+    /// The call failed, with any metadata sent by the server. No further results are sent. The
+    /// error can be downcast to a [`tonic::Status`] if it was returned by the server.
     Error(Error, MetadataMap),
 }
 
+/// A request message.
 #[derive(Debug, Clone)]
 pub struct Request {
     pub message: DynamicMessage,
 }
 
+/// A response message, and the time it was received.
 #[derive(Debug, Clone)]
 pub struct Response {
     pub message: DynamicMessage,
     pub timestamp: Instant,
 }
 
-#[derive(Debug, Copy, Clone, druid::Data, PartialEq, Eq)]
+/// Options for connecting to a server.
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    verify_certs: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MethodKind {
     Unary,
     ClientStreaming,
@@ -42,25 +61,90 @@ pub enum MethodKind {
     Streaming,
 }
 
+/// A handle to a call in progress, used to send further requests for client streaming methods.
 #[derive(Debug)]
 pub struct Call {
     last_request: Option<Instant>,
     request_sender: Option<mpsc::UnboundedSender<Request>>,
 }
 
+/// A connection to a server. Connections are shared between clients with the same address and
+/// options, so clients are cheap to create and clone.
 #[derive(Clone, Debug)]
 pub struct Client {
     grpc: Grpc<Channel>,
 }
 
+impl ClientOptions {
+    pub fn new() -> Self {
+        ClientOptions { verify_certs: true }
+    }
+
+    /// Sets whether the server's TLS certificate is verified. Defaults to `true`.
+    pub fn verify_certs(mut self, verify_certs: bool) -> Self {
+        self.verify_certs = verify_certs;
+        self
+    }
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions::new()
+    }
+}
+
 impl Client {
-    pub async fn new(uri: &Uri, verify_certs: bool) -> ConnectResult {
-        let channel = channel::get(uri, verify_certs).await?;
+    pub async fn new(uri: &Uri, options: &ClientOptions) -> ConnectResult {
+        let channel = channel::get(uri, options.verify_certs).await?;
         Ok(Client {
             grpc: Grpc::new(channel),
         })
     }
 
+    /// Makes a unary call, returning the response message and the response metadata.
+    pub async fn call_unary(
+        self,
+        method: prost_reflect::MethodDescriptor,
+        request: Request,
+        metadata: MetadataMap,
+    ) -> Result<(Response, MetadataMap)> {
+        if method.is_client_streaming() || method.is_server_streaming() {
+            bail!("method '{}' is not unary", method.full_name())
+        }
+
+        let (_, mut results) = self.call_stream(method, request, metadata);
+        let mut response = None;
+        let mut response_metadata = MetadataMap::new();
+        while let Some(result) = results.next().await {
+            match result {
+                ResponseResult::Metadata(metadata) => response_metadata = metadata,
+                ResponseResult::Response(message) => response = Some(message),
+                ResponseResult::Finished => break,
+                ResponseResult::Error(err, _) => return Err(err),
+            }
+        }
+
+        let response = response.context("no response was received")?;
+        Ok((response, response_metadata))
+    }
+
+    /// Starts a call of any kind, returning a stream of its results. For client streaming methods,
+    /// further requests can be sent with the returned [`Call`], and the request stream is closed
+    /// when it is finished or dropped.
+    pub fn call_stream(
+        self,
+        method: prost_reflect::MethodDescriptor,
+        request: Request,
+        metadata: MetadataMap,
+    ) -> (Call, impl Stream<Item = ResponseResult> + Send + Unpin) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let call = self.call(method, request, metadata, move |result| {
+            let _ = sender.send(result);
+        });
+        (call, UnboundedReceiverStream::new(receiver))
+    }
+
+    /// Starts a call of any kind, passing each of its results to `on_response`.
     pub fn call<F>(
         self,
         method: prost_reflect::MethodDescriptor,
@@ -312,6 +396,11 @@ impl Client {
 }
 
 impl Request {
+    pub fn new(message: DynamicMessage) -> Self {
+        Request { message }
+    }
+
+    /// Parses a request message from its JSON representation.
     pub fn from_json(desc: MessageDescriptor, s: &str) -> Result<Self> {
         let mut de = serde_json::Deserializer::from_str(s);
         let message =
@@ -329,6 +418,7 @@ impl Response {
         }
     }
 
+    /// Formats the response message as JSON, including fields with default values.
     pub fn to_json(&self) -> String {
         let mut s = serde_json::Serializer::new(Vec::new());
         self.message
@@ -344,7 +434,18 @@ impl Response {
     }
 }
 
+impl From<DynamicMessage> for Request {
+    fn from(message: DynamicMessage) -> Self {
+        Request::new(message)
+    }
+}
+
 impl Call {
+    /// Sends another request for a client streaming method.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is not client streaming.
     pub fn send(&mut self, request: Request) {
         self.last_request = Some(Instant::now());
         let _ = self
@@ -354,10 +455,13 @@ impl Call {
             .send(request);
     }
 
+    /// Closes the request stream of a client streaming method.
     pub fn finish(&mut self) {
         self.request_sender = None;
     }
 
+    /// The time between the last request and the given response, if this is the first response
+    /// since that request.
     pub fn duration(&mut self, response: &Response) -> Option<Duration> {
         self.last_request.take().and_then(|request_timestamp| {
             response.timestamp.checked_duration_since(request_timestamp)
@@ -385,3 +489,28 @@ impl MethodKind {
         }
     }
 }
+
+/// Whether an address connects without TLS.
+pub(crate) fn is_plaintext(uri: &Uri) -> bool {
+    uri.scheme() == Some(&Scheme::HTTP)
+}
+
+/// The `host:port` of an address, with the default port for the scheme if none is given.
+pub(crate) fn authority(uri: &Uri) -> String {
+    let host = uri.host().unwrap_or_default();
+    let port = uri
+        .port_u16()
+        .unwrap_or(if is_plaintext(uri) { 80 } else { 443 });
+    format!("{}:{}", host, port)
+}
+
+/// Sets the deadline of a call as its `grpc-timeout` header. The channel also enforces the header,
+/// so the call fails after the timeout even if the server ignores it.
+pub fn set_timeout(metadata: &mut MetadataMap, timeout: Duration) {
+    // The value may have at most eight digits.
+    let value = match timeout.as_millis() {
+        millis if millis < 100_000_000 => format!("{}m", millis),
+        _ => format!("{}S", timeout.as_secs().min(99_999_999)),
+    };
+    metadata.insert("grpc-timeout", value.parse().unwrap());
+}
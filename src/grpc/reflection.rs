@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use http::Uri;
+use prost_reflect::{DescriptorPool, ServiceDescriptor};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{
+    metadata::MetadataMap, transport::Channel, Code, Extensions, Request, Status, Streaming,
+};
+use tonic_reflection::pb::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse, ServerReflectionRequest, ServerReflectionResponse,
+};
+
+use crate::{
+    auth::{AuthProvider, CallContext},
+    error::is_unauthenticated,
+    grpc,
+};
+
+pub const SERVICE_NAME: &str = "grpc.reflection.v1alpha.ServerReflection";
+pub const METHOD_NAME: &str = "ServerReflectionInfo";
+
+/// A stream to a server's reflection service, used to list its services and load their
+/// descriptors.
+pub struct ReflectionSession {
+    channel: Channel,
+    metadata: MetadataMap,
+    auth: Option<Arc<AuthProvider>>,
+    auth_context: CallContext,
+    sender: mpsc::UnboundedSender<ServerReflectionRequest>,
+    receiver: Streaming<ServerReflectionResponse>,
+    host: String,
+    services: Arc<Vec<String>>,
+    pool: DescriptorPool,
+}
+
+impl ReflectionSession {
+    pub async fn connect(
+        address: Uri,
+        options: &grpc::ClientOptions,
+        metadata: MetadataMap,
+        auth: Option<Arc<AuthProvider>>,
+        auth_context: CallContext,
+        authorization: http::HeaderMap,
+    ) -> Result<Self> {
+        let channel = grpc::channel::get(&address, options.verify_certs).await?;
+        let host = address.host().unwrap_or_default().to_owned();
+
+        let (sender, receiver) = match (
+            open_stream(channel.clone(), &metadata, authorization).await,
+            &auth,
+        ) {
+            (Err(err), Some(auth)) if is_unauthenticated(&err) && auth.can_refresh() => {
+                let authorization = auth.get_headers_force(&auth_context).await?;
+                open_stream(channel.clone(), &metadata, authorization).await?
+            }
+            (result, _) => result?,
+        };
+        let mut session = ReflectionSession {
+            channel,
+            metadata,
+            auth,
+            auth_context,
+            sender,
+            receiver,
+            host,
+            services: Arc::default(),
+            pool: DescriptorPool::new(),
+        };
+
+        let response = session
+            .request(MessageRequest::ListServices(String::default()))
+            .await?;
+        let service_list = match response {
+            MessageResponse::ListServicesResponse(service_list) => service_list,
+            _ => bail!("unexpected response type"),
+        };
+
+        session.services = Arc::new(service_list.service.into_iter().map(|s| s.name).collect());
+        Ok(session)
+    }
+
+    /// The names of the services exposed by the server.
+    pub fn services(&self) -> &Arc<Vec<String>> {
+        &self.services
+    }
+
+    pub async fn load_service(&mut self, name: &str) -> Result<ServiceDescriptor> {
+        let response = self
+            .request(MessageRequest::FileContainingSymbol(name.to_owned()))
+            .await?;
+        let file_response = match response {
+            MessageResponse::FileDescriptorResponse(file_response) => file_response,
+            _ => bail!("unexpected response type"),
+        };
+
+        for file in file_response.file_descriptor_proto {
+            self.pool
+                .decode_file_descriptor_proto(file.as_ref())
+                .context("failed to load file descriptor from server")?;
+        }
+
+        let Some(service) = self.pool.get_service_by_name(name) else {
+            bail!(
+                "service '{}' not found in file descriptor from server",
+                name
+            )
+        };
+
+        Ok(service)
+    }
+
+    /// Sends a request on the reflection stream. If the server rejects our credentials, the
+    /// stream is reopened with fresh metadata from the authorization hook and the request retried.
+    async fn request(&mut self, message_request: MessageRequest) -> Result<MessageResponse> {
+        match (self.try_request(message_request.clone()).await, &self.auth) {
+            (Err(err), Some(auth)) if is_unauthenticated(&err) && auth.can_refresh() => {
+                tracing::info!("Reflection request was unauthenticated, refreshing authorization");
+
+                let authorization = auth.get_headers_force(&self.auth_context).await?;
+                let (sender, receiver) =
+                    open_stream(self.channel.clone(), &self.metadata, authorization).await?;
+                self.sender = sender;
+                self.receiver = receiver;
+
+                self.try_request(message_request).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    async fn try_request(&mut self, message_request: MessageRequest) -> Result<MessageResponse> {
+        self.sender.send(ServerReflectionRequest {
+            host: self.host.clone(),
+            message_request: Some(message_request),
+        })?;
+        let Some(response) = self.receiver.message().await? else {
+            bail!("unexpected end of response stream");
+        };
+        match response.message_response {
+            Some(MessageResponse::ErrorResponse(error)) => {
+                Err(Status::new(Code::from_i32(error.error_code), error.error_message).into())
+            }
+            Some(message_response) => Ok(message_response),
+            None => bail!("unexpected response type"),
+        }
+    }
+}
+
+async fn open_stream(
+    channel: Channel,
+    metadata: &MetadataMap,
+    authorization: http::HeaderMap,
+) -> Result<(
+    mpsc::UnboundedSender<ServerReflectionRequest>,
+    Streaming<ServerReflectionResponse>,
+)> {
+    let mut headers = metadata.clone().into_headers();
+    headers.extend(authorization);
+
+    let mut client = ServerReflectionClient::new(channel);
+    let (sender, request_receiver) = mpsc::unbounded_channel::<ServerReflectionRequest>();
+    let receiver = client
+        .server_reflection_info(Request::from_parts(
+            MetadataMap::from_headers(headers),
+            Extensions::default(),
+            UnboundedReceiverStream::new(request_receiver),
+        ))
+        .await?
+        .into_inner();
+
+    Ok((sender, receiver))
+}
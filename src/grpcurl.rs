@@ -0,0 +1,478 @@
+use std::{
+    ffi::OsStr,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use http::{
+    header::{HeaderName, HeaderValue},
+    HeaderMap, Uri,
+};
+use prost_reflect::MethodDescriptor;
+use shell_words::quote;
+use tonic::metadata::MetadataMap;
+
+use crate::grpc;
+
+/// A grpcurl command line which calls a method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    /// The method, in the form `package.Service/Method`.
+    pub method: String,
+    pub address: Uri,
+    pub verify_certs: bool,
+    pub source: Source,
+    pub headers: Vec<(String, String)>,
+    /// The JSON body of the request.
+    pub data: String,
+}
+
+/// Where grpcurl finds the descriptor of the service.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// Use server reflection, grpcurl's default.
+    Reflection,
+    /// Compile proto files, relative to one of the import paths.
+    Proto {
+        import_paths: Vec<PathBuf>,
+        files: Vec<String>,
+    },
+    /// Read encoded file descriptor sets.
+    Protoset(Vec<PathBuf>),
+}
+
+/// Flags of grpcurl which don't take a value.
+const BOOL_FLAGS: &[&str] = &[
+    "plaintext",
+    "insecure",
+    "use-reflection",
+    "allow-unknown-fields",
+    "emit-defaults",
+    "format-error",
+    "msg-template",
+    "expand-headers",
+    "unix",
+    "alts",
+    "v",
+    "vv",
+    "veryverbose",
+];
+
+impl Command {
+    /// Parses a grpcurl command line which calls a method, as written for a POSIX shell.
+    pub fn parse(s: &str) -> Result<Self> {
+        let words = shell_words::split(s).context("invalid command line")?;
+        let mut words = words.into_iter();
+        match words.next() {
+            Some(program) if Path::new(&program).file_stem() == Some(OsStr::new("grpcurl")) => (),
+            _ => bail!("expected a command starting with 'grpcurl'"),
+        }
+
+        let mut plaintext = false;
+        let mut insecure = false;
+        let mut import_paths = Vec::new();
+        let mut protos = Vec::new();
+        let mut protosets = Vec::new();
+        let mut headers = Vec::new();
+        let mut data = String::new();
+        let mut positional = Vec::new();
+
+        while let Some(word) = words.next() {
+            let Some(flag) = word.strip_prefix("--").or_else(|| word.strip_prefix('-')) else {
+                positional.push(word);
+                continue;
+            };
+            if flag.is_empty() {
+                positional.extend(words.by_ref());
+                break;
+            }
+
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (flag, None),
+            };
+
+            if BOOL_FLAGS.contains(&name) {
+                let enabled = match value.as_deref() {
+                    None | Some("true") => true,
+                    Some("false") => false,
+                    Some(value) => bail!("invalid value '{}' for flag '-{}'", value, name),
+                };
+                match name {
+                    "plaintext" => plaintext = enabled,
+                    "insecure" => insecure = enabled,
+                    _ => (),
+                }
+                continue;
+            }
+
+            let value = match value {
+                Some(value) => value,
+                None => words
+                    .next()
+                    .with_context(|| format!("expected a value for flag '-{}'", name))?,
+            };
+            match name {
+                "d" if value == "@" => bail!("requests read from stdin can't be imported"),
+                "d" => data = value,
+                "H" | "rpc-header" => {
+                    let (key, value) = value.split_once(':').with_context(|| {
+                        format!("invalid header '{}', expected 'key: value'", value)
+                    })?;
+                    headers.push((key.trim().to_owned(), value.trim().to_owned()));
+                }
+                "import-path" => import_paths.push(PathBuf::from(value)),
+                "proto" => protos.push(value),
+                "protoset" => protosets.push(PathBuf::from(value)),
+                _ => tracing::debug!("Ignoring grpcurl flag '-{}'", name),
+            }
+        }
+
+        let (address, method) = match positional.as_slice() {
+            [address, method] => (address, method),
+            [_] => bail!("expected an address before the method"),
+            _ => bail!("expected an address and a method"),
+        };
+        if method == "list" || method == "describe" {
+            bail!("only commands which call a method can be imported")
+        }
+
+        let scheme = if plaintext { "http" } else { "https" };
+        let address: Uri = format!("{}://{}", scheme, address)
+            .parse()
+            .with_context(|| format!("invalid address '{}'", address))?;
+
+        let source = if !protosets.is_empty() {
+            Source::Protoset(protosets)
+        } else if !protos.is_empty() {
+            Source::Proto {
+                import_paths,
+                files: protos,
+            }
+        } else {
+            Source::Reflection
+        };
+
+        Ok(Command {
+            method: parse_method(method)?,
+            address,
+            verify_certs: !insecure,
+            source,
+            headers,
+            data,
+        })
+    }
+
+    pub fn new(method: &MethodDescriptor, address: Uri, verify_certs: bool) -> Self {
+        Command {
+            method: format!("{}/{}", method.parent_service().full_name(), method.name()),
+            address,
+            verify_certs,
+            source: Source::Reflection,
+            headers: Vec::new(),
+            data: String::new(),
+        }
+    }
+
+    pub fn set_metadata(&mut self, metadata: MetadataMap) {
+        // Binary values are already base64 encoded in the header map, as grpcurl expects.
+        self.headers = metadata
+            .into_headers()
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.as_str().to_owned(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+    }
+
+    /// Gets the headers as request metadata.
+    pub fn metadata(&self) -> Result<MetadataMap> {
+        let mut headers = HeaderMap::with_capacity(self.headers.len());
+        for (key, value) in &self.headers {
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .with_context(|| format!("invalid metadata key '{}'", key))?;
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("invalid metadata value for '{}'", key))?;
+            headers.append(name, value);
+        }
+        Ok(MetadataMap::from_headers(headers))
+    }
+
+    /// Sets the request body, formatted as compact JSON if it is valid.
+    pub fn set_data(&mut self, json: &str) {
+        self.data = match serde_json::from_str::<serde_json::Value>(json) {
+            Ok(value) => value.to_string(),
+            Err(_) => json.trim().to_owned(),
+        };
+    }
+}
+
+impl Source {
+    /// Chooses how grpcurl should load the service of `method`. If its file can be found in one of
+    /// the include paths, or next to `proto`, the file the service was loaded from, it is compiled
+    /// from source. Otherwise its descriptors are written to a protoset file in `protoset_dir`,
+    /// which must be shared along with the command.
+    pub fn for_method(
+        method: &MethodDescriptor,
+        proto: Option<&Path>,
+        includes: &[PathBuf],
+        protoset_dir: &Path,
+    ) -> Result<Self> {
+        let file = method.parent_service().parent_file();
+        if includes
+            .iter()
+            .any(|include| include.join(file.name()).is_file())
+        {
+            return Ok(Source::Proto {
+                import_paths: includes.to_vec(),
+                files: vec![file.name().to_owned()],
+            });
+        }
+
+        // Without include paths, a file's imports are resolved from its own directory.
+        if let Some(import_path) = proto.and_then(Path::parent) {
+            if import_path.join(file.name()).is_file() {
+                return Ok(Source::Proto {
+                    import_paths: vec![import_path.to_owned()],
+                    files: vec![file.name().to_owned()],
+                });
+            }
+        }
+
+        let path = protoset_dir.join(format!("{}.protoset", method.parent_service().full_name()));
+        fs_err::create_dir_all(protoset_dir)?;
+        fs_err::write(&path, method.parent_pool().encode_to_vec())?;
+        Ok(Source::Protoset(vec![path]))
+    }
+
+    /// Gets the paths of the files to load, resolving proto files against the import paths.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        match self {
+            Source::Reflection => Vec::new(),
+            Source::Proto {
+                import_paths,
+                files,
+            } => files
+                .iter()
+                .map(|file| {
+                    import_paths
+                        .iter()
+                        .map(|import_path| import_path.join(file))
+                        .find(|path| path.is_file())
+                        .unwrap_or_else(|| PathBuf::from(file))
+                })
+                .collect(),
+            Source::Protoset(paths) => paths.clone(),
+        }
+    }
+
+    /// Gets the import paths used to compile proto files.
+    pub fn import_paths(&self) -> &[PathBuf] {
+        match self {
+            Source::Proto { import_paths, .. } => import_paths,
+            Source::Reflection | Source::Protoset(_) => &[],
+        }
+    }
+}
+
+/// Normalizes a method given as `package.Service/Method`, `package.Service.Method` or
+/// `/package.Service/Method` to the first form.
+fn parse_method(method: &str) -> Result<String> {
+    let method = method.trim_start_matches('/');
+    match method.rsplit_once('/').or_else(|| method.rsplit_once('.')) {
+        Some((service, name)) if !service.is_empty() && !name.is_empty() => {
+            Ok(format!("{}/{}", service, name))
+        }
+        _ => bail!(
+            "expected a method in the form 'package.Service/Method', got '{}'",
+            method
+        ),
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("grpcurl")?;
+        if grpc::is_plaintext(&self.address) {
+            f.write_str(" -plaintext")?;
+        } else if !self.verify_certs {
+            f.write_str(" -insecure")?;
+        }
+
+        match &self.source {
+            Source::Reflection => (),
+            Source::Proto {
+                import_paths,
+                files,
+            } => {
+                for path in import_paths {
+                    write!(f, " -import-path {}", quote(&path.to_string_lossy()))?;
+                }
+                for file in files {
+                    write!(f, " -proto {}", quote(file))?;
+                }
+            }
+            Source::Protoset(paths) => {
+                for path in paths {
+                    write!(f, " -protoset {}", quote(&path.to_string_lossy()))?;
+                }
+            }
+        }
+
+        for (key, value) in &self.headers {
+            write!(f, " -H {}", quote(&format!("{}: {}", key, value)))?;
+        }
+        if !self.data.is_empty() {
+            write!(f, " -d {}", quote(&self.data))?;
+        }
+
+        write!(
+            f,
+            " {} {}",
+            quote(&grpc::authority(&self.address)),
+            quote(&self.method)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(address: &str, verify_certs: bool, source: Source) -> Command {
+        Command {
+            method: "helloworld.Greeter/SayHello".to_owned(),
+            address: address.parse().unwrap(),
+            verify_certs,
+            source,
+            headers: vec![
+                ("authorization".to_owned(), "Bearer abc".to_owned()),
+                ("x-note".to_owned(), "it's \"quoted\"".to_owned()),
+            ],
+            data: r#"{"name":"O'Brien","tags":["a b"]}"#.to_owned(),
+        }
+    }
+
+    #[test]
+    fn round_trip_plaintext_proto() {
+        let command = command(
+            "http://localhost:50051",
+            true,
+            Source::Proto {
+                import_paths: vec![PathBuf::from("my protos"), PathBuf::from("/usr/include")],
+                files: vec!["hello world.proto".to_owned()],
+            },
+        );
+        assert_eq!(Command::parse(&command.to_string()).unwrap(), command);
+    }
+
+    #[test]
+    fn round_trip_insecure_protoset() {
+        let command = command(
+            "https://example.com:8443",
+            false,
+            Source::Protoset(vec![PathBuf::from("/tmp/a b.protoset")]),
+        );
+        assert_eq!(Command::parse(&command.to_string()).unwrap(), command);
+    }
+
+    #[test]
+    fn round_trip_reflection() {
+        let mut command = command("https://example.com:443", true, Source::Reflection);
+        command.headers.clear();
+        command.data.clear();
+        assert_eq!(
+            command.to_string(),
+            "grpcurl example.com:443 helloworld.Greeter/SayHello"
+        );
+        assert_eq!(Command::parse(&command.to_string()).unwrap(), command);
+    }
+
+    #[test]
+    fn parse_readme_example() {
+        let command = Command::parse(
+            r#"grpcurl -plaintext -d '{"name": "world"}' localhost:50051 helloworld.Greeter/SayHello"#,
+        )
+        .unwrap();
+        assert_eq!(command.method, "helloworld.Greeter/SayHello");
+        assert_eq!(
+            command.address,
+            "http://localhost:50051".parse::<Uri>().unwrap()
+        );
+        assert!(command.verify_certs);
+        assert_eq!(command.source, Source::Reflection);
+        assert!(command.headers.is_empty());
+        assert_eq!(command.data, r#"{"name": "world"}"#);
+    }
+
+    #[test]
+    fn parse_flag_forms() {
+        let command = Command::parse(
+            "/usr/local/bin/grpcurl --insecure -v -max-time 10 -rpc-header='X-Id: 1' \
+                -H 'authorization: Bearer abc' -import-path ../protos -import-path=vendor \
+                -proto api/service.proto example.com:443 /my.pkg.Service.Method",
+        )
+        .unwrap();
+        assert_eq!(command.method, "my.pkg.Service/Method");
+        assert_eq!(
+            command.address,
+            "https://example.com:443".parse::<Uri>().unwrap()
+        );
+        assert!(!command.verify_certs);
+        assert_eq!(
+            command.source,
+            Source::Proto {
+                import_paths: vec![PathBuf::from("../protos"), PathBuf::from("vendor")],
+                files: vec!["api/service.proto".to_owned()],
+            }
+        );
+        assert_eq!(
+            command.headers,
+            vec![
+                ("X-Id".to_owned(), "1".to_owned()),
+                ("authorization".to_owned(), "Bearer abc".to_owned()),
+            ]
+        );
+        assert_eq!(command.data, "");
+    }
+
+    #[test]
+    fn parse_protosets() {
+        let command = Command::parse(
+            "grpcurl -plaintext=false -protoset a.protoset -protoset b.protoset -d '{}' \
+                localhost:8080 pkg.Service/Method",
+        )
+        .unwrap();
+        assert_eq!(
+            command.address,
+            "https://localhost:8080".parse::<Uri>().unwrap()
+        );
+        assert_eq!(
+            command.source,
+            Source::Protoset(vec![
+                PathBuf::from("a.protoset"),
+                PathBuf::from("b.protoset")
+            ])
+        );
+        assert_eq!(command.data, "{}");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Command::parse("curl localhost:8080").is_err());
+        assert!(Command::parse("grpcurl -plaintext localhost:8080 list").is_err());
+        assert!(Command::parse("grpcurl -plaintext localhost:8080 describe").is_err());
+        assert!(Command::parse("grpcurl pkg.Service/Method").is_err());
+        assert!(Command::parse("grpcurl -d @ localhost:8080 pkg.Service/Method").is_err());
+        assert!(Command::parse("grpcurl -H 'no colon' localhost:8080 pkg.Service/Method").is_err());
+        assert!(
+            Command::parse("grpcurl -plaintext=maybe localhost:8080 pkg.Service/Method").is_err()
+        );
+        assert!(Command::parse("grpcurl localhost:8080 Method").is_err());
+        assert!(Command::parse("grpcurl -d").is_err());
+    }
+}
@@ -1,11 +1,43 @@
+//! Lanquetta is a GUI application for testing gRPC services. The modules exposed here can also
+//! be used as a library to load service descriptors, authorize requests and call methods of any
+//! service, with messages described at runtime.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use lanquetta::{grpc, protoc};
+//!
+//! let file = protoc::load_file("greeter.proto".as_ref(), &[])?;
+//! let method = file
+//!     .parent_pool()
+//!     .get_service_by_name("helloworld.Greeter")
+//!     .and_then(|service| service.methods().find(|method| method.name() == "SayHello"))
+//!     .unwrap();
+//!
+//! let uri = "http://localhost:50051".parse()?;
+//! let client = grpc::Client::new(&uri, &grpc::ClientOptions::new()).await?;
+//! let request = grpc::Request::from_json(method.input(), r#"{ "name": "world" }"#)?;
+//! let (response, _) = client
+//!     .call_unary(method, request, Default::default())
+//!     .await?;
+//! println!("{}", response.to_json());
+//! # Ok(())
+//! # }
+//! ```
+
 #![allow(clippy::type_complexity)]
 
 pub mod app;
-mod auth;
+mod assertion;
+pub mod auth;
+mod capture;
 mod error;
-mod grpc;
+pub mod grpc;
+mod grpcurl;
 mod json;
 mod lens;
-mod protoc;
+pub mod protoc;
+mod snapshot;
+mod snippet;
+mod template;
 mod theme;
 mod widget;
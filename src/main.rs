@@ -1,22 +1,53 @@
 #![windows_subsystem = "windows"]
 #![allow(clippy::type_complexity)]
 
+use std::{env, ffi::OsString, io, process::ExitCode};
+
 use anyhow::{Context, Result};
+use lanquetta::app::cli;
 use tokio::runtime::Runtime;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-pub fn main() -> Result<()> {
-    let filter_layer = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))?;
+pub fn main() -> Result<ExitCode> {
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    let headless = cli::is_command(&args);
+
+    if headless {
+        attach_console();
+    }
+
+    // Logs go to stderr in headless mode so they don't mix with responses on stdout.
+    let default_level = if headless { "warn" } else { "info" };
+    let filter_layer =
+        EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(default_level))?;
 
     tracing_subscriber::registry()
-        .with(fmt::layer())
+        .with(fmt::layer().with_writer(io::stderr))
         .with(filter_layer)
         .init();
 
     let runtime = Runtime::new().context("failed to initialize the tokio runtime")?;
     let _guard = runtime.enter();
 
+    if headless {
+        return Ok(runtime.block_on(cli::run(args)));
+    }
+
     lanquetta::app::launch()?;
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
+
+/// Windows GUI applications don't have a console, so attach to the console of the parent process
+/// to print output.
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
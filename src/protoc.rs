@@ -1,3 +1,5 @@
+//! Loading descriptors from `.proto` source files or encoded file descriptor sets.
+
 use std::{
     ffi::OsStr,
     path::{Path, PathBuf},
@@ -6,13 +8,21 @@ use std::{
 use anyhow::{bail, Result};
 use prost_reflect::{DescriptorPool, FileDescriptor};
 
-pub const ERRORS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/errors.bin"));
+/// Descriptors for the standard error detail messages, so they can be decoded in responses.
+const ERRORS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/errors.bin"));
 
+/// Loads a `.proto` file, resolving imports from `includes` or else the file's directory, or an
+/// encoded file descriptor set. The file's pool contains all of its dependencies.
 pub fn load_file(path: &Path, includes: &[PathBuf]) -> Result<FileDescriptor> {
     let mut pool = load_pool(path, includes)?;
     Ok(add_error_definitions(&mut pool))
 }
 
+/// Whether a file is a `.proto` source file, rather than an encoded file descriptor set.
+pub fn is_proto(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new("proto"))
+}
+
 fn add_error_definitions(pool: &mut DescriptorPool) -> FileDescriptor {
     let primary_file = pool.files().last().unwrap().name().to_owned();
 
@@ -35,7 +45,7 @@ fn load_pool(path: &Path, includes: &[PathBuf]) -> Result<DescriptorPool> {
         Err(err) if err.is_parse() => match compile_file_set(path) {
             Ok(pool) => Ok(pool),
             Err(_) => {
-                if path.extension() == Some(OsStr::new("proto")) {
+                if is_proto(path) {
                     bail!("{:?}", err)
                 } else {
                     bail!("failed to parse file as either a protobuf source file or encoded file descriptor set: {}", err)
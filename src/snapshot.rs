@@ -0,0 +1,371 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use prost_reflect::MethodDescriptor;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A golden response for a request, which later responses are compared against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The method, in the form `package.Service/Method`.
+    pub method: String,
+    /// The name of the saved request.
+    pub request: String,
+    pub response: Value,
+}
+
+/// A field which differs between a snapshot and a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// A JSON Pointer to the field.
+    pub path: String,
+    pub kind: DifferenceKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DifferenceKind {
+    /// The field is in the snapshot but not the response.
+    Missing(Value),
+    /// The field is in the response but not the snapshot.
+    Unexpected(Value),
+    Changed {
+        expected: Value,
+        actual: Value,
+    },
+}
+
+impl Snapshot {
+    /// Gets the path of the snapshot file for a request, within the snapshot directory.
+    pub fn path(directory: &Path, method: &MethodDescriptor, request: &str) -> PathBuf {
+        let request = if request.trim().is_empty() {
+            "default"
+        } else {
+            request.trim()
+        };
+
+        directory
+            .join(file_name(method.parent_service().full_name()))
+            .join(file_name(method.name()))
+            .join(format!("{}.json", file_name(request)))
+    }
+
+    /// Loads a snapshot, or returns `None` if no snapshot has been saved.
+    pub fn load(path: &Path) -> Result<Option<Snapshot>> {
+        let text = match fs_err::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let snapshot = serde_json::from_str(&text)
+            .with_context(|| format!("invalid snapshot file `{}`", path.display()))?;
+        Ok(Some(snapshot))
+    }
+
+    pub fn store(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs_err::create_dir_all(dir)?;
+        }
+
+        let mut text = serde_json::to_string_pretty(self)?;
+        text.push('\n');
+        fs_err::write(path, text)?;
+        Ok(())
+    }
+
+    /// Compares a response against this snapshot. Fields matching any of the `ignore` paths,
+    /// which are JSON Pointers where `*` matches any key or index, are skipped.
+    pub fn diff(&self, actual: &Value, ignore: &[String]) -> Vec<Difference> {
+        let ignore: Vec<Vec<String>> = ignore.iter().map(|path| segments(path)).collect();
+
+        let mut differences = Vec::new();
+        diff(
+            &mut Vec::new(),
+            &self.response,
+            actual,
+            &ignore,
+            &mut differences,
+        );
+        differences
+    }
+}
+
+fn diff(
+    path: &mut Vec<String>,
+    expected: &Value,
+    actual: &Value,
+    ignore: &[Vec<String>],
+    differences: &mut Vec<Difference>,
+) {
+    if is_ignored(path, ignore) {
+        return;
+    }
+
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                path.push(key.clone());
+                match actual.get(key) {
+                    Some(actual) => diff(path, expected, actual, ignore, differences),
+                    None if !is_ignored(path, ignore) => differences.push(Difference {
+                        path: pointer(path),
+                        kind: DifferenceKind::Missing(expected.clone()),
+                    }),
+                    None => (),
+                }
+                path.pop();
+            }
+            for (key, actual) in actual {
+                if !expected.contains_key(key) {
+                    path.push(key.clone());
+                    if !is_ignored(path, ignore) {
+                        differences.push(Difference {
+                            path: pointer(path),
+                            kind: DifferenceKind::Unexpected(actual.clone()),
+                        });
+                    }
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            for index in 0..expected.len().max(actual.len()) {
+                path.push(index.to_string());
+                match (expected.get(index), actual.get(index)) {
+                    (Some(expected), Some(actual)) => {
+                        diff(path, expected, actual, ignore, differences)
+                    }
+                    (Some(expected), None) if !is_ignored(path, ignore) => {
+                        differences.push(Difference {
+                            path: pointer(path),
+                            kind: DifferenceKind::Missing(expected.clone()),
+                        })
+                    }
+                    (None, Some(actual)) if !is_ignored(path, ignore) => {
+                        differences.push(Difference {
+                            path: pointer(path),
+                            kind: DifferenceKind::Unexpected(actual.clone()),
+                        })
+                    }
+                    _ => (),
+                }
+                path.pop();
+            }
+        }
+        (expected, actual) if expected != actual => differences.push(Difference {
+            path: pointer(path),
+            kind: DifferenceKind::Changed {
+                expected: expected.clone(),
+                actual: actual.clone(),
+            },
+        }),
+        _ => (),
+    }
+}
+
+fn is_ignored(path: &[String], ignore: &[Vec<String>]) -> bool {
+    ignore.iter().any(|pattern| {
+        pattern.len() == path.len()
+            && pattern
+                .iter()
+                .zip(path)
+                .all(|(pattern, segment)| pattern == "*" || pattern == segment)
+    })
+}
+
+fn segments(pointer: &str) -> Vec<String> {
+    pointer
+        .split('/')
+        .skip(1)
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+fn pointer(path: &[String]) -> String {
+    path.iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Replaces characters which may not be valid in file names.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "response"
+        } else {
+            &self.path
+        };
+
+        match &self.kind {
+            DifferenceKind::Missing(expected) => {
+                write!(f, "{}: missing, expected {}", path, expected)
+            }
+            DifferenceKind::Unexpected(actual) => write!(f, "{}: unexpected {}", path, actual),
+            DifferenceKind::Changed { expected, actual } => {
+                write!(f, "{}: expected {}, got {}", path, expected, actual)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn snapshot(response: Value) -> Snapshot {
+        Snapshot {
+            method: "echo.Echo/Get".to_owned(),
+            request: "default".to_owned(),
+            response,
+        }
+    }
+
+    fn ignore(paths: &[&str]) -> Vec<Vec<String>> {
+        paths.iter().map(|path| segments(path)).collect()
+    }
+
+    fn path(segments: &[&str]) -> Vec<String> {
+        segments.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn equal() {
+        let response = json!({ "id": 1, "items": [{ "name": "a" }] });
+        assert!(snapshot(response.clone()).diff(&response, &[]).is_empty());
+    }
+
+    #[test]
+    fn missing_and_extra_fields() {
+        let snapshot = snapshot(json!({ "id": 1, "name": "a", "nested": { "x": true } }));
+        let actual = json!({ "id": 2, "nested": { "x": true, "y": null }, "extra": "b" });
+
+        assert_eq!(
+            snapshot.diff(&actual, &[]),
+            [
+                Difference {
+                    path: "/id".to_owned(),
+                    kind: DifferenceKind::Changed {
+                        expected: json!(1),
+                        actual: json!(2),
+                    },
+                },
+                Difference {
+                    path: "/name".to_owned(),
+                    kind: DifferenceKind::Missing(json!("a")),
+                },
+                Difference {
+                    path: "/nested/y".to_owned(),
+                    kind: DifferenceKind::Unexpected(json!(null)),
+                },
+                Difference {
+                    path: "/extra".to_owned(),
+                    kind: DifferenceKind::Unexpected(json!("b")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn array_length_changes() {
+        let snapshot = snapshot(json!({ "items": [1, 2, 3] }));
+
+        assert_eq!(
+            snapshot.diff(&json!({ "items": [1, 2] }), &[]),
+            [Difference {
+                path: "/items/2".to_owned(),
+                kind: DifferenceKind::Missing(json!(3)),
+            }]
+        );
+        assert_eq!(
+            snapshot.diff(&json!({ "items": [1, 2, 3, 4] }), &[]),
+            [Difference {
+                path: "/items/3".to_owned(),
+                kind: DifferenceKind::Unexpected(json!(4)),
+            }]
+        );
+    }
+
+    #[test]
+    fn changed_type() {
+        let snapshot = snapshot(json!({ "items": [1] }));
+
+        assert_eq!(
+            snapshot.diff(&json!({ "items": { "0": 1 } }), &[]),
+            [Difference {
+                path: "/items".to_owned(),
+                kind: DifferenceKind::Changed {
+                    expected: json!([1]),
+                    actual: json!({ "0": 1 }),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn ignored_fields() {
+        let snapshot = snapshot(json!({
+            "createdAt": "2024-01-01",
+            "items": [{ "id": "a", "name": "x" }, { "id": "b", "name": "y" }],
+        }));
+        let actual = json!({
+            "createdAt": "2024-02-02",
+            "items": [{ "id": "c", "name": "x" }, { "name": "y" }, { "id": "d" }],
+            "requestId": "123",
+        });
+        let ignore = [
+            "/createdAt".to_owned(),
+            "/items/*/id".to_owned(),
+            "/requestId".to_owned(),
+        ];
+
+        assert_eq!(
+            snapshot.diff(&actual, &ignore),
+            [Difference {
+                path: "/items/2".to_owned(),
+                kind: DifferenceKind::Unexpected(json!({ "id": "d" })),
+            }]
+        );
+    }
+
+    #[test]
+    fn ignored_subtree() {
+        let snapshot = snapshot(json!({ "meta": { "a": 1 }, "id": 1 }));
+        let actual = json!({ "meta": { "b": 2 }, "id": 1 });
+
+        assert!(snapshot.diff(&actual, &["/meta".to_owned()]).is_empty());
+    }
+
+    #[test]
+    fn is_ignored_wildcards() {
+        let ignore = ignore(&["/items/*/id", "/*/updatedAt", "/a~1b/~0c"]);
+
+        assert!(is_ignored(&path(&["items", "0", "id"]), &ignore));
+        assert!(is_ignored(&path(&["items", "12", "id"]), &ignore));
+        assert!(is_ignored(&path(&["user", "updatedAt"]), &ignore));
+        assert!(is_ignored(&path(&["a/b", "~c"]), &ignore));
+
+        assert!(!is_ignored(&path(&["items", "0"]), &ignore));
+        assert!(!is_ignored(&path(&["items", "0", "id", "x"]), &ignore));
+        assert!(!is_ignored(&path(&["items", "0", "name"]), &ignore));
+        assert!(!is_ignored(&path(&["updatedAt"]), &ignore));
+        assert!(!is_ignored(&path(&[]), &ignore));
+    }
+}
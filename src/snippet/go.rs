@@ -0,0 +1,421 @@
+//! Snippets using grpc-go and a client generated by `protoc-gen-go-grpc`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use prost_reflect::{
+    DynamicMessage, EnumDescriptor, FieldDescriptor, FileDescriptor, Kind, MessageDescriptor, Value,
+};
+
+use crate::grpc::MethodKind;
+
+use super::{
+    float, is_optional_scalar, map_key_value, quote, quote_bytes, real_oneof, relative_name,
+    set_fields, sorted_map, Snippet,
+};
+
+const GRPC: &str = "google.golang.org/grpc";
+const PROTO: &str = "google.golang.org/protobuf/proto";
+
+pub(super) fn generate(snippet: &Snippet) -> String {
+    let service = snippet.method.parent_service();
+    let mut gen = Generator {
+        service_file: service.parent_file(),
+        std_imports: BTreeSet::from(["context", "log"]),
+        imports: BTreeMap::new(),
+    };
+    let client = gen.package(&service.parent_file());
+
+    let mut body = String::new();
+    let credentials = if snippet.is_plaintext() {
+        gen.imports
+            .insert(format!("{}/credentials/insecure", GRPC), String::new());
+        "insecure.NewCredentials()".to_owned()
+    } else {
+        gen.std_imports.insert("crypto/tls");
+        gen.imports
+            .insert(format!("{}/credentials", GRPC), String::new());
+        if snippet.verify_certs {
+            "credentials.NewTLS(&tls.Config{})".to_owned()
+        } else {
+            "credentials.NewTLS(&tls.Config{InsecureSkipVerify: true})".to_owned()
+        }
+    };
+    gen.imports.insert(GRPC.to_owned(), String::new());
+    writeln!(
+        body,
+        "\tconn, err := grpc.NewClient({}, grpc.WithTransportCredentials({}))",
+        quote(&snippet.authority()),
+        credentials
+    )
+    .unwrap();
+    write_check(&mut body);
+    writeln!(body, "\tdefer conn.Close()").unwrap();
+    writeln!(
+        body,
+        "\tclient := {}.New{}Client(conn)",
+        client,
+        go_camel_case(service.name())
+    )
+    .unwrap();
+    writeln!(body).unwrap();
+
+    writeln!(body, "\tctx := context.Background()").unwrap();
+    if !snippet.metadata.is_empty() {
+        gen.imports
+            .insert(format!("{}/metadata", GRPC), String::new());
+        let pairs: Vec<String> = snippet
+            .metadata
+            .iter()
+            .map(|(key, value)| format!("{}, {}", quote(key), quote_bytes(value)))
+            .collect();
+        writeln!(
+            body,
+            "\tctx = metadata.AppendToOutgoingContext(ctx, {})",
+            pairs.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(body).unwrap();
+
+    let request = gen.message(snippet.request, 1);
+    writeln!(body, "\trequest := {}", request).unwrap();
+
+    let method = go_camel_case(snippet.method.name());
+    match snippet.kind() {
+        MethodKind::Unary => {
+            writeln!(body, "\tresponse, err := client.{}(ctx, request)", method).unwrap();
+            write_check(&mut body);
+            writeln!(body, "\tlog.Println(response)").unwrap();
+        }
+        MethodKind::ServerStreaming => {
+            writeln!(body, "\tstream, err := client.{}(ctx, request)", method).unwrap();
+            write_check(&mut body);
+            gen.write_receive_loop(&mut body);
+        }
+        MethodKind::ClientStreaming => {
+            writeln!(body, "\tstream, err := client.{}(ctx)", method).unwrap();
+            write_check(&mut body);
+            writeln!(body, "\tif err := stream.Send(request); err != nil {{").unwrap();
+            writeln!(body, "\t\tlog.Fatal(err)").unwrap();
+            writeln!(body, "\t}}").unwrap();
+            writeln!(body, "\tresponse, err := stream.CloseAndRecv()").unwrap();
+            write_check(&mut body);
+            writeln!(body, "\tlog.Println(response)").unwrap();
+        }
+        MethodKind::Streaming => {
+            writeln!(body, "\tstream, err := client.{}(ctx)", method).unwrap();
+            write_check(&mut body);
+            writeln!(body, "\tif err := stream.Send(request); err != nil {{").unwrap();
+            writeln!(body, "\t\tlog.Fatal(err)").unwrap();
+            writeln!(body, "\t}}").unwrap();
+            writeln!(body, "\tif err := stream.CloseSend(); err != nil {{").unwrap();
+            writeln!(body, "\t\tlog.Fatal(err)").unwrap();
+            writeln!(body, "\t}}").unwrap();
+            gen.write_receive_loop(&mut body);
+        }
+    }
+
+    let mut s = "package main\n\nimport (\n".to_owned();
+    for import in &gen.std_imports {
+        writeln!(s, "\t{}", quote(import)).unwrap();
+    }
+    writeln!(s).unwrap();
+    for (path, alias) in &gen.imports {
+        if alias.is_empty() {
+            writeln!(s, "\t{}", quote(path)).unwrap();
+        } else {
+            writeln!(s, "\t{} {}", alias, quote(path)).unwrap();
+        }
+    }
+    writeln!(s, ")\n\nfunc main() {{").unwrap();
+    s.push_str(&body);
+    writeln!(s, "}}").unwrap();
+    s
+}
+
+fn write_check(s: &mut String) {
+    writeln!(s, "\tif err != nil {{").unwrap();
+    writeln!(s, "\t\tlog.Fatal(err)").unwrap();
+    writeln!(s, "\t}}").unwrap();
+}
+
+struct Generator {
+    service_file: FileDescriptor,
+    std_imports: BTreeSet<&'static str>,
+    /// Maps the path of imported packages to their alias, or an empty string if none is needed.
+    imports: BTreeMap<String, String>,
+}
+
+impl Generator {
+    fn write_receive_loop(&mut self, s: &mut String) {
+        self.std_imports.insert("io");
+        writeln!(s, "\tfor {{").unwrap();
+        writeln!(s, "\t\tresponse, err := stream.Recv()").unwrap();
+        writeln!(s, "\t\tif err == io.EOF {{").unwrap();
+        writeln!(s, "\t\t\tbreak").unwrap();
+        writeln!(s, "\t\t}}").unwrap();
+        writeln!(s, "\t\tif err != nil {{").unwrap();
+        writeln!(s, "\t\t\tlog.Fatal(err)").unwrap();
+        writeln!(s, "\t\t}}").unwrap();
+        writeln!(s, "\t\tlog.Println(response)").unwrap();
+        writeln!(s, "\t}}").unwrap();
+    }
+
+    /// Imports the Go package generated for a file, and returns its name.
+    fn package(&mut self, file: &FileDescriptor) -> String {
+        let (path, name) = if file.package_name() == "google.protobuf" {
+            let stem = file
+                .name()
+                .trim_start_matches("google/protobuf/")
+                .trim_end_matches(".proto")
+                .replace('_', "");
+            (
+                format!("google.golang.org/protobuf/types/known/{}pb", stem),
+                format!("{}pb", stem),
+            )
+        } else if *file == self.service_file {
+            (go_package_path(file), "pb".to_owned())
+        } else {
+            let path = go_package_path(file);
+            let name = match file
+                .file_descriptor_proto()
+                .options
+                .as_ref()
+                .and_then(|options| options.go_package().split_once(';'))
+            {
+                Some((_, name)) => name.to_owned(),
+                None => path
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .replace(['-', '.'], "_"),
+            };
+            (path, name)
+        };
+
+        self.imports.entry(path).or_insert(name).clone()
+    }
+
+    fn message(&mut self, message: &DynamicMessage, depth: usize) -> String {
+        let desc = message.descriptor();
+        let ty = self.message_type(&desc);
+        let fields = set_fields(message);
+        if fields.is_empty() {
+            return format!("&{}{{}}", ty);
+        }
+
+        let mut s = format!("&{}{{\n", ty);
+        for (field, value) in &fields {
+            let (name, value) = match real_oneof(field) {
+                Some(oneof) => {
+                    let field_name = go_camel_case(field.name());
+                    let value = self.single(&field.kind(), value, depth + 1);
+                    (
+                        go_camel_case(oneof.name()),
+                        format!("&{}_{}{{{}: {}}}", ty, field_name, field_name, value),
+                    )
+                }
+                None => (
+                    go_camel_case(field.name()),
+                    self.field_value(field, value, depth + 1),
+                ),
+            };
+            writeln!(s, "{}{}: {},", tabs(depth + 1), name, value).unwrap();
+        }
+        write!(s, "{}}}", tabs(depth)).unwrap();
+        s
+    }
+
+    fn field_value(&mut self, field: &FieldDescriptor, value: &Value, depth: usize) -> String {
+        let kind = field.kind();
+        match value {
+            Value::List(items) => {
+                let ty = self.element_type(&kind);
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| self.single(&kind, item, depth + 1))
+                    .collect();
+                if matches!(kind, Kind::Message(_)) {
+                    let mut s = format!("[]{}{{\n", ty);
+                    for item in items {
+                        writeln!(s, "{}{},", tabs(depth + 1), item).unwrap();
+                    }
+                    write!(s, "{}}}", tabs(depth)).unwrap();
+                    s
+                } else {
+                    format!("[]{}{{{}}}", ty, items.join(", "))
+                }
+            }
+            Value::Map(map) => {
+                let entry = kind.as_message().expect("map fields have an entry type");
+                let key_kind = entry.map_entry_key_field().kind();
+                let value_kind = entry.map_entry_value_field().kind();
+
+                let mut s = format!(
+                    "map[{}]{}{{\n",
+                    self.element_type(&key_kind),
+                    self.element_type(&value_kind)
+                );
+                for (key, value) in sorted_map(map) {
+                    let key = self.single(&key_kind, &map_key_value(key), depth + 1);
+                    let value = self.single(&value_kind, value, depth + 1);
+                    writeln!(s, "{}{}: {},", tabs(depth + 1), key, value).unwrap();
+                }
+                write!(s, "{}}}", tabs(depth)).unwrap();
+                s
+            }
+            value if is_optional_scalar(field) => {
+                let single = self.single(&kind, value, depth);
+                let wrapper = match value {
+                    Value::Bool(_) => "Bool",
+                    Value::I32(_) => "Int32",
+                    Value::I64(_) => "Int64",
+                    Value::U32(_) => "Uint32",
+                    Value::U64(_) => "Uint64",
+                    Value::F32(_) => "Float32",
+                    Value::F64(_) => "Float64",
+                    Value::String(_) => "String",
+                    Value::EnumNumber(_) if matches!(kind, Kind::Enum(_)) => {
+                        return format!("{}.Enum()", single)
+                    }
+                    _ => return single,
+                };
+                self.imports.insert(PROTO.to_owned(), String::new());
+                format!("proto.{}({})", wrapper, single)
+            }
+            value => self.single(&kind, value, depth),
+        }
+    }
+
+    fn single(&mut self, kind: &Kind, value: &Value, depth: usize) -> String {
+        match (kind, value) {
+            (_, Value::Bool(value)) => value.to_string(),
+            (_, Value::I32(value)) => value.to_string(),
+            (_, Value::I64(value)) => value.to_string(),
+            (_, Value::U32(value)) => value.to_string(),
+            (_, Value::U64(value)) => value.to_string(),
+            (_, Value::F32(value)) => match self.float_literal(*value as f64) {
+                literal if value.is_finite() => literal,
+                literal => format!("float32({})", literal),
+            },
+            (_, Value::F64(value)) => self.float_literal(*value),
+            (_, Value::String(value)) => quote(value),
+            (_, Value::Bytes(value)) => format!("[]byte({})", quote_bytes(value)),
+            (Kind::Enum(enum_ty), Value::EnumNumber(number)) => match enum_ty.get_value(*number) {
+                Some(value) => format!("{}_{}", self.enum_value_prefix(enum_ty), value.name()),
+                None => format!("{}({})", self.enum_type(enum_ty), number),
+            },
+            (_, Value::EnumNumber(number)) => number.to_string(),
+            (_, Value::Message(value)) => self.message(value, depth),
+            (_, Value::List(_) | Value::Map(_)) => "nil".to_owned(),
+        }
+    }
+
+    fn float_literal(&mut self, value: f64) -> String {
+        if value.is_finite() {
+            return float(value);
+        }
+
+        self.std_imports.insert("math");
+        if value.is_nan() {
+            "math.NaN()".to_owned()
+        } else if value > 0.0 {
+            "math.Inf(1)".to_owned()
+        } else {
+            "math.Inf(-1)".to_owned()
+        }
+    }
+
+    /// Gets the Go type of a list element, or a map key or value.
+    fn element_type(&mut self, kind: &Kind) -> String {
+        match kind {
+            Kind::Double => "float64".to_owned(),
+            Kind::Float => "float32".to_owned(),
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => "int32".to_owned(),
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => "int64".to_owned(),
+            Kind::Uint32 | Kind::Fixed32 => "uint32".to_owned(),
+            Kind::Uint64 | Kind::Fixed64 => "uint64".to_owned(),
+            Kind::Bool => "bool".to_owned(),
+            Kind::String => "string".to_owned(),
+            Kind::Bytes => "[]byte".to_owned(),
+            Kind::Message(message) => format!("*{}", self.message_type(message)),
+            Kind::Enum(enum_ty) => self.enum_type(enum_ty),
+        }
+    }
+
+    fn message_type(&mut self, message: &MessageDescriptor) -> String {
+        let package = self.package(&message.parent_file());
+        format!(
+            "{}.{}",
+            package,
+            go_camel_case(relative_name(message.full_name(), message.package_name()))
+        )
+    }
+
+    fn enum_type(&mut self, enum_ty: &EnumDescriptor) -> String {
+        let package = self.package(&enum_ty.parent_file());
+        format!(
+            "{}.{}",
+            package,
+            go_camel_case(relative_name(enum_ty.full_name(), enum_ty.package_name()))
+        )
+    }
+
+    /// Values of nested enums are prefixed with the name of the parent message, while values of
+    /// top-level enums are prefixed with the name of the enum.
+    fn enum_value_prefix(&mut self, enum_ty: &EnumDescriptor) -> String {
+        match enum_ty.parent_message() {
+            Some(parent) => self.message_type(&parent),
+            None => self.enum_type(enum_ty),
+        }
+    }
+}
+
+/// Gets the import path of the Go package generated for a file, from its `go_package` option.
+fn go_package_path(file: &FileDescriptor) -> String {
+    let go_package = file
+        .file_descriptor_proto()
+        .options
+        .as_ref()
+        .map(|options| options.go_package())
+        .unwrap_or_default();
+    match go_package.split_once(';') {
+        Some((path, _)) => path.to_owned(),
+        None if !go_package.is_empty() => go_package.to_owned(),
+        None => format!("example.com/{}", file.package_name().replace('.', "/")),
+    }
+}
+
+/// Converts a protobuf name to a Go identifier, in the same way as `protoc-gen-go`.
+fn go_camel_case(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next_is_lower = bytes.get(i + 1).map_or(false, u8::is_ascii_lowercase);
+        match c {
+            b'.' if next_is_lower => (),
+            b'.' => result.push('_'),
+            b'_' if i == 0 || bytes[i - 1] == b'.' => result.push('X'),
+            b'_' if next_is_lower => (),
+            c if c.is_ascii_digit() => result.push(c as char),
+            c => {
+                result.push(c.to_ascii_uppercase() as char);
+                while bytes.get(i + 1).map_or(false, u8::is_ascii_lowercase) {
+                    i += 1;
+                    result.push(bytes[i] as char);
+                }
+            }
+        }
+        i += 1;
+    }
+    result
+}
+
+fn tabs(depth: usize) -> String {
+    "\t".repeat(depth)
+}
@@ -0,0 +1,446 @@
+//! Client code which builds a request message and calls a method, for several languages.
+
+mod go;
+mod python;
+mod rust;
+mod typescript;
+
+use std::fmt::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::Uri;
+use prost_reflect::{
+    DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor, MethodDescriptor,
+    OneofDescriptor, Value,
+};
+use tonic::metadata::MetadataMap;
+
+use crate::grpc::{self, MethodKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Go,
+    Python,
+    TypeScript,
+}
+
+/// The details of a call to generate a snippet for.
+pub struct Snippet<'a> {
+    pub method: &'a MethodDescriptor,
+    pub request: &'a DynamicMessage,
+    pub address: &'a Uri,
+    pub verify_certs: bool,
+    /// The metadata entries, with the values of binary keys decoded.
+    pub metadata: Vec<(String, Vec<u8>)>,
+}
+
+impl<'a> Snippet<'a> {
+    pub fn new(
+        method: &'a MethodDescriptor,
+        request: &'a DynamicMessage,
+        address: &'a Uri,
+        verify_certs: bool,
+        metadata: MetadataMap,
+    ) -> Self {
+        Snippet {
+            method,
+            request,
+            address,
+            verify_certs,
+            metadata: metadata
+                .into_headers()
+                .iter()
+                .map(|(key, value)| {
+                    let value = if is_binary_key(key.as_str()) {
+                        STANDARD
+                            .decode(value.as_bytes())
+                            .unwrap_or_else(|_| value.as_bytes().to_vec())
+                    } else {
+                        value.as_bytes().to_vec()
+                    };
+                    (key.as_str().to_owned(), value)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn generate(&self, language: Language) -> String {
+        match language {
+            Language::Rust => rust::generate(self),
+            Language::Go => go::generate(self),
+            Language::Python => python::generate(self),
+            Language::TypeScript => typescript::generate(self),
+        }
+    }
+
+    fn kind(&self) -> MethodKind {
+        MethodKind::for_method(self.method)
+    }
+
+    fn is_plaintext(&self) -> bool {
+        grpc::is_plaintext(self.address)
+    }
+
+    fn authority(&self) -> String {
+        grpc::authority(self.address)
+    }
+
+    /// The address with its scheme and no path, e.g. `https://example.com:443`.
+    fn base_url(&self) -> String {
+        format!(
+            "{}://{}",
+            if self.is_plaintext() { "http" } else { "https" },
+            self.authority()
+        )
+    }
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::Rust,
+        Language::Go,
+        Language::Python,
+        Language::TypeScript,
+    ];
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::Rust => "Rust (tonic)",
+            Language::Go => "Go (grpc-go)",
+            Language::Python => "Python (grpcio)",
+            Language::TypeScript => "TypeScript (connect-es)",
+        })
+    }
+}
+
+/// Gets the name of a type relative to its package, e.g. `Outer.Inner`.
+fn relative_name<'a>(full_name: &'a str, package: &str) -> &'a str {
+    if package.is_empty() {
+        full_name
+    } else {
+        full_name
+            .strip_prefix(package)
+            .and_then(|name| name.strip_prefix('.'))
+            .unwrap_or(full_name)
+    }
+}
+
+fn is_binary_key(key: &str) -> bool {
+    key.ends_with("-bin")
+}
+
+/// Formats a string as a double-quoted literal, with escapes common to all the languages.
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_ascii_control() => write!(quoted, "\\x{:02x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats bytes as a double-quoted literal, escaping anything that isn't
+/// printable ASCII.
+fn quote_bytes(bytes: &[u8]) -> String {
+    let mut quoted = String::with_capacity(bytes.len() + 2);
+    quoted.push('"');
+    for &b in bytes {
+        match b {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b' '..=b'~' => quoted.push(b as char),
+            b => write!(quoted, "\\x{:02x}", b).unwrap(),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats a floating point value so that it always reads as a float, e.g. `1.0` rather than `1`.
+fn float(value: f64) -> String {
+    format!("{:?}", value)
+}
+
+fn indent(depth: usize) -> String {
+    "    ".repeat(depth)
+}
+
+/// Sorts the entries of a map field, so snippets are stable.
+fn sorted_map(map: &std::collections::HashMap<MapKey, Value>) -> Vec<(&MapKey, &Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(key, _)| map_key_string(key));
+    entries
+}
+
+fn map_key_string(key: &MapKey) -> String {
+    match key {
+        MapKey::Bool(value) => value.to_string(),
+        MapKey::I32(value) => value.to_string(),
+        MapKey::I64(value) => value.to_string(),
+        MapKey::U32(value) => value.to_string(),
+        MapKey::U64(value) => value.to_string(),
+        MapKey::String(value) => value.clone(),
+    }
+}
+
+fn map_key_value(key: &MapKey) -> Value {
+    match key {
+        MapKey::Bool(value) => Value::Bool(*value),
+        MapKey::I32(value) => Value::I32(*value),
+        MapKey::I64(value) => Value::I64(*value),
+        MapKey::U32(value) => Value::U32(*value),
+        MapKey::U64(value) => Value::U64(*value),
+        MapKey::String(value) => Value::String(value.clone()),
+    }
+}
+
+/// Gets the fields which are set in a message, in the order they are declared.
+fn set_fields(message: &DynamicMessage) -> Vec<(FieldDescriptor, Value)> {
+    message
+        .descriptor()
+        .fields()
+        .filter(|field| message.has_field(field))
+        .map(|field| {
+            let value = message.get_field(&field).into_owned();
+            (field, value)
+        })
+        .collect()
+}
+
+/// Gets the oneof containing a field, unless it is the synthetic oneof of a proto3 `optional` field.
+fn real_oneof(field: &FieldDescriptor) -> Option<OneofDescriptor> {
+    field
+        .containing_oneof()
+        .filter(|oneof| !oneof.is_synthetic())
+}
+
+/// Whether a scalar field outside a oneof tracks presence, e.g. a proto3 `optional` field.
+fn is_optional_scalar(field: &FieldDescriptor) -> bool {
+    !field.is_list()
+        && !field.is_map()
+        && field.supports_presence()
+        && !matches!(field.kind(), Kind::Message(_))
+        && real_oneof(field).is_none()
+}
+
+/// Whether a message is one of the wrapper types from `google/protobuf/wrappers.proto`.
+fn is_wrapper(message: &MessageDescriptor) -> bool {
+    matches!(
+        message.full_name(),
+        "google.protobuf.DoubleValue"
+            | "google.protobuf.FloatValue"
+            | "google.protobuf.Int64Value"
+            | "google.protobuf.UInt64Value"
+            | "google.protobuf.Int32Value"
+            | "google.protobuf.UInt32Value"
+            | "google.protobuf.BoolValue"
+            | "google.protobuf.StringValue"
+            | "google.protobuf.BytesValue"
+    )
+}
+
+/// Gets the value of a wrapper message, or the default for its type if unset.
+fn wrapped_value(message: &DynamicMessage) -> (Kind, Value) {
+    let field = message
+        .descriptor()
+        .get_field_by_name("value")
+        .expect("wrapper types have a value field");
+    let value = message.get_field(&field).into_owned();
+    (field.kind(), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    const REQUEST: &str = r#"{
+        "id": "o-1",
+        "status": "STATUS_PAID",
+        "quantities": { "b": 2, "a": 1 },
+        "note": "leave at door",
+        "card": "4242"
+    }"#;
+
+    fn generate(language: Language) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/snippet/testdata/shop.proto");
+        let file = crate::protoc::load_file(&path, &[]).unwrap();
+        let method = file.services().next().unwrap().methods().next().unwrap();
+        let request = crate::grpc::Request::from_json(method.input(), REQUEST)
+            .unwrap()
+            .message;
+        let address = "http://localhost:50051".parse().unwrap();
+
+        Snippet::new(&method, &request, &address, true, MetadataMap::new()).generate(language)
+    }
+
+    #[test]
+    fn rust_snippet() {
+        assert_eq!(
+            generate(Language::Rust),
+            r#"pub mod shop {
+    tonic::include_proto!("shop");
+}
+
+use shop::orders_client::OrdersClient;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = OrdersClient::connect("http://localhost:50051").await?;
+
+    let message = shop::Order {
+        id: "o-1".to_owned(),
+        status: shop::Status::Paid as i32,
+        quantities: [
+            ("a".to_owned(), 1),
+            ("b".to_owned(), 2),
+        ]
+        .into_iter()
+        .collect(),
+        note: Some("leave at door".to_owned()),
+        payment: Some(shop::order::Payment::Card("4242".to_owned())),
+    };
+    let request = tonic::Request::new(message);
+
+    let response = client.create(request).await?;
+    println!("{:?}", response.into_inner());
+
+    Ok(())
+}
+"#
+        );
+    }
+
+    #[test]
+    fn go_snippet() {
+        assert_eq!(
+            generate(Language::Go),
+            r#"package main
+
+import (
+	"context"
+	"log"
+
+	pb "example.com/shop"
+	"google.golang.org/grpc"
+	"google.golang.org/grpc/credentials/insecure"
+	wrapperspb "google.golang.org/protobuf/types/known/wrapperspb"
+)
+
+func main() {
+	conn, err := grpc.NewClient("localhost:50051", grpc.WithTransportCredentials(insecure.NewCredentials()))
+	if err != nil {
+		log.Fatal(err)
+	}
+	defer conn.Close()
+	client := pb.NewOrdersClient(conn)
+
+	ctx := context.Background()
+
+	request := &pb.Order{
+		Id: "o-1",
+		Status: pb.Status_STATUS_PAID,
+		Quantities: map[string]int32{
+			"a": 1,
+			"b": 2,
+		},
+		Note: &wrapperspb.StringValue{
+			Value: "leave at door",
+		},
+		Payment: &pb.Order_Card{Card: "4242"},
+	}
+	response, err := client.Create(ctx, request)
+	if err != nil {
+		log.Fatal(err)
+	}
+	log.Println(response)
+}
+"#
+        );
+    }
+
+    #[test]
+    fn python_snippet() {
+        assert_eq!(
+            generate(Language::Python),
+            r#"import grpc
+
+import shop_pb2, shop_pb2_grpc
+from google.protobuf import wrappers_pb2
+
+
+def main():
+    with grpc.insecure_channel("localhost:50051") as channel:
+        stub = shop_pb2_grpc.OrdersStub(channel)
+
+        request = shop_pb2.Order(
+            id="o-1",
+            status=shop_pb2.STATUS_PAID,
+            quantities={
+                "a": 1,
+                "b": 2,
+            },
+            note=wrappers_pb2.StringValue(
+                value="leave at door",
+            ),
+            card="4242",
+        )
+
+        response = stub.Create(request)
+        print(response)
+
+
+if __name__ == "__main__":
+    main()
+"#
+        );
+    }
+
+    #[test]
+    fn typescript_snippet() {
+        assert_eq!(
+            generate(Language::TypeScript),
+            r#"import { Orders } from "./gen/shop_connect";
+import { Order, Status } from "./gen/shop_pb";
+import { createPromiseClient } from "@connectrpc/connect";
+import { createGrpcTransport } from "@connectrpc/connect-node";
+
+const transport = createGrpcTransport({
+  baseUrl: "http://localhost:50051",
+  httpVersion: "2",
+});
+const client = createPromiseClient(Orders, transport);
+
+const request = new Order({
+  id: "o-1",
+  status: Status.PAID,
+  quantities: {
+    "a": 1,
+    "b": 2,
+  },
+  note: "leave at door",
+  payment: {
+    case: "card",
+    value: "4242",
+  },
+});
+
+const response = await client.create(request);
+console.log(response);
+"#
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Snippets using grpcio and modules generated by `grpc_tools.protoc`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use prost_reflect::{DynamicMessage, FieldDescriptor, FileDescriptor, Kind, Value};
+
+use crate::grpc::MethodKind;
+
+use super::{
+    float, indent, is_binary_key, map_key_value, quote, quote_bytes, relative_name, set_fields,
+    sorted_map, Snippet,
+};
+
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+pub(super) fn generate(snippet: &Snippet) -> String {
+    let service = snippet.method.parent_service();
+    let mut gen = Generator {
+        imports: BTreeMap::new(),
+    };
+    let stub_module = gen.module(&service.parent_file(), "_pb2_grpc");
+
+    let mut body = String::new();
+    if snippet.is_plaintext() {
+        writeln!(
+            body,
+            "    with grpc.insecure_channel({}) as channel:",
+            quote(&snippet.authority())
+        )
+        .unwrap();
+    } else {
+        if !snippet.verify_certs {
+            writeln!(
+                body,
+                "    # Note that grpcio always verifies the server's certificate."
+            )
+            .unwrap();
+        }
+        writeln!(
+            body,
+            "    with grpc.secure_channel({}, grpc.ssl_channel_credentials()) as channel:",
+            quote(&snippet.authority())
+        )
+        .unwrap();
+    }
+    writeln!(
+        body,
+        "        stub = {}.{}Stub(channel)",
+        stub_module,
+        service.name()
+    )
+    .unwrap();
+    writeln!(body).unwrap();
+
+    let request = gen.message(snippet.request, 2);
+    writeln!(body, "        request = {}", request).unwrap();
+    let mut arguments = match snippet.kind() {
+        MethodKind::Unary | MethodKind::ServerStreaming => "request".to_owned(),
+        MethodKind::ClientStreaming | MethodKind::Streaming => "iter([request])".to_owned(),
+    };
+    if !snippet.metadata.is_empty() {
+        let entries: Vec<String> = snippet
+            .metadata
+            .iter()
+            .map(|(key, value)| {
+                if is_binary_key(key) {
+                    format!("({}, b{})", quote(key), quote_bytes(value))
+                } else {
+                    format!(
+                        "({}, {})",
+                        quote(key),
+                        quote(&String::from_utf8_lossy(value))
+                    )
+                }
+            })
+            .collect();
+        writeln!(body, "        metadata = [{}]", entries.join(", ")).unwrap();
+        arguments.push_str(", metadata=metadata");
+    }
+    writeln!(body).unwrap();
+
+    let call = format!("stub.{}({})", snippet.method.name(), arguments);
+    match snippet.kind() {
+        MethodKind::Unary | MethodKind::ClientStreaming => {
+            writeln!(body, "        response = {}", call).unwrap();
+            writeln!(body, "        print(response)").unwrap();
+        }
+        MethodKind::ServerStreaming | MethodKind::Streaming => {
+            writeln!(body, "        for response in {}:", call).unwrap();
+            writeln!(body, "            print(response)").unwrap();
+        }
+    }
+
+    let mut s = "import grpc\n\n".to_owned();
+    for (package, modules) in &gen.imports {
+        let modules: Vec<&str> = modules.iter().map(String::as_str).collect();
+        match package {
+            Some(package) => writeln!(s, "from {} import {}", package, modules.join(", ")).unwrap(),
+            None => writeln!(s, "import {}", modules.join(", ")).unwrap(),
+        }
+    }
+    writeln!(s, "\n\ndef main():").unwrap();
+    s.push_str(&body);
+    writeln!(s, "\n\nif __name__ == \"__main__\":").unwrap();
+    writeln!(s, "    main()").unwrap();
+    s
+}
+
+struct Generator {
+    /// Maps the package of imported modules to their names.
+    imports: BTreeMap<Option<String>, BTreeSet<String>>,
+}
+
+impl Generator {
+    /// Imports the module generated for a file, and returns its name.
+    fn module(&mut self, file: &FileDescriptor, suffix: &str) -> String {
+        let path = file.name().trim_end_matches(".proto").replace('-', "_");
+        let (package, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => (Some(dir.replace('/', ".")), name.to_owned()),
+            None => (None, path),
+        };
+        let module = format!("{}{}", name, suffix);
+        self.imports
+            .entry(package)
+            .or_default()
+            .insert(module.clone());
+        module
+    }
+
+    fn message(&mut self, message: &DynamicMessage, depth: usize) -> String {
+        let desc = message.descriptor();
+        let ty = format!(
+            "{}.{}",
+            self.module(&desc.parent_file(), "_pb2"),
+            relative_name(desc.full_name(), desc.package_name())
+        );
+        let fields = set_fields(message);
+        if fields.is_empty() {
+            return format!("{}()", ty);
+        }
+
+        let mut s = format!("{}(\n", ty);
+        for (field, value) in &fields {
+            let value = self.field_value(field, value, depth + 1);
+            if KEYWORDS.contains(&field.name()) {
+                writeln!(
+                    s,
+                    "{}**{{{}: {}}},",
+                    indent(depth + 1),
+                    quote(field.name()),
+                    value
+                )
+                .unwrap();
+            } else {
+                writeln!(s, "{}{}={},", indent(depth + 1), field.name(), value).unwrap();
+            }
+        }
+        write!(s, "{})", indent(depth)).unwrap();
+        s
+    }
+
+    fn field_value(&mut self, field: &FieldDescriptor, value: &Value, depth: usize) -> String {
+        let kind = field.kind();
+        match value {
+            Value::List(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| self.single(&kind, item, depth + 1))
+                    .collect();
+                if matches!(kind, Kind::Message(_)) {
+                    let mut s = "[\n".to_owned();
+                    for item in items {
+                        writeln!(s, "{}{},", indent(depth + 1), item).unwrap();
+                    }
+                    write!(s, "{}]", indent(depth)).unwrap();
+                    s
+                } else {
+                    format!("[{}]", items.join(", "))
+                }
+            }
+            Value::Map(map) => {
+                let entry = kind.as_message().expect("map fields have an entry type");
+                let key_kind = entry.map_entry_key_field().kind();
+                let value_kind = entry.map_entry_value_field().kind();
+
+                let mut s = "{\n".to_owned();
+                for (key, value) in sorted_map(map) {
+                    let key = self.single(&key_kind, &map_key_value(key), depth + 1);
+                    let value = self.single(&value_kind, value, depth + 1);
+                    writeln!(s, "{}{}: {},", indent(depth + 1), key, value).unwrap();
+                }
+                write!(s, "{}}}", indent(depth)).unwrap();
+                s
+            }
+            value => self.single(&kind, value, depth),
+        }
+    }
+
+    fn single(&mut self, kind: &Kind, value: &Value, depth: usize) -> String {
+        match (kind, value) {
+            (_, Value::Bool(true)) => "True".to_owned(),
+            (_, Value::Bool(false)) => "False".to_owned(),
+            (_, Value::I32(value)) => value.to_string(),
+            (_, Value::I64(value)) => value.to_string(),
+            (_, Value::U32(value)) => value.to_string(),
+            (_, Value::U64(value)) => value.to_string(),
+            (_, Value::F32(value)) => float_literal(*value as f64),
+            (_, Value::F64(value)) => float_literal(*value),
+            (_, Value::String(value)) => quote(value),
+            (_, Value::Bytes(value)) => format!("b{}", quote_bytes(value)),
+            (Kind::Enum(enum_ty), Value::EnumNumber(number)) => {
+                match enum_ty.get_value(*number) {
+                    Some(value) => {
+                        // Enum values are defined in the scope containing the enum.
+                        let module = self.module(&enum_ty.parent_file(), "_pb2");
+                        match enum_ty.parent_message() {
+                            Some(parent) => format!(
+                                "{}.{}.{}",
+                                module,
+                                relative_name(parent.full_name(), parent.package_name()),
+                                value.name()
+                            ),
+                            None => format!("{}.{}", module, value.name()),
+                        }
+                    }
+                    None => number.to_string(),
+                }
+            }
+            (_, Value::EnumNumber(number)) => number.to_string(),
+            (_, Value::Message(value)) => self.message(value, depth),
+            (_, Value::List(_) | Value::Map(_)) => "None".to_owned(),
+        }
+    }
+}
+
+fn float_literal(value: f64) -> String {
+    if value.is_nan() {
+        "float(\"nan\")".to_owned()
+    } else if value == f64::INFINITY {
+        "float(\"inf\")".to_owned()
+    } else if value == f64::NEG_INFINITY {
+        "float(\"-inf\")".to_owned()
+    } else {
+        float(value)
+    }
+}
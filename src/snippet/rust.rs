@@ -0,0 +1,343 @@
+//! Snippets using a client generated by `tonic-build` and `prost`.
+
+use std::fmt::Write;
+
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use prost_reflect::{
+    DynamicMessage, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor, Value,
+};
+
+use crate::grpc::MethodKind;
+
+use super::{
+    float, indent, is_binary_key, is_optional_scalar, is_wrapper, map_key_value, quote,
+    quote_bytes, real_oneof, set_fields, sorted_map, wrapped_value, Snippet,
+};
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+pub(super) fn generate(snippet: &Snippet) -> String {
+    let service = snippet.method.parent_service();
+    let package = service.package_name();
+    let modules = package_modules(package);
+
+    let mut s = String::new();
+    for (depth, module) in modules.iter().enumerate() {
+        writeln!(s, "{}pub mod {} {{", indent(depth), module).unwrap();
+    }
+    writeln!(
+        s,
+        "{}tonic::include_proto!({});",
+        indent(modules.len()),
+        quote(if package.is_empty() { "_" } else { package })
+    )
+    .unwrap();
+    for depth in (0..modules.len()).rev() {
+        writeln!(s, "{}}}", indent(depth)).unwrap();
+    }
+
+    let mut client_path = modules.clone();
+    client_path.push(format!("{}_client", service.name().to_snake_case()));
+    client_path.push(format!("{}Client", service.name().to_upper_camel_case()));
+    writeln!(s).unwrap();
+    writeln!(s, "use {};", client_path.join("::")).unwrap();
+    writeln!(s).unwrap();
+
+    writeln!(s, "#[tokio::main]").unwrap();
+    writeln!(
+        s,
+        "async fn main() -> Result<(), Box<dyn std::error::Error>> {{"
+    )
+    .unwrap();
+    if !snippet.is_plaintext() {
+        writeln!(
+            s,
+            "    // Connecting with TLS requires the `tls-roots` feature of tonic."
+        )
+        .unwrap();
+        if !snippet.verify_certs {
+            writeln!(
+                s,
+                "    // Note that tonic always verifies the server's certificate."
+            )
+            .unwrap();
+        }
+    }
+    writeln!(
+        s,
+        "    let mut client = {}Client::connect({}).await?;",
+        service.name().to_upper_camel_case(),
+        quote(&snippet.base_url())
+    )
+    .unwrap();
+    writeln!(s).unwrap();
+
+    writeln!(s, "    let message = {};", message(snippet.request, 1)).unwrap();
+    let request = match snippet.kind() {
+        MethodKind::Unary | MethodKind::ServerStreaming => "message".to_owned(),
+        MethodKind::ClientStreaming | MethodKind::Streaming => {
+            "tokio_stream::iter(vec![message])".to_owned()
+        }
+    };
+    if snippet.metadata.is_empty() {
+        writeln!(s, "    let request = tonic::Request::new({});", request).unwrap();
+    } else {
+        writeln!(s, "    let mut request = tonic::Request::new({});", request).unwrap();
+        for (key, value) in &snippet.metadata {
+            if is_binary_key(key) {
+                writeln!(
+                    s,
+                    "    request.metadata_mut().insert_bin({}, tonic::metadata::MetadataValue::from_bytes(b{}));",
+                    quote(key),
+                    quote_bytes(value)
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    s,
+                    "    request.metadata_mut().insert({}, {}.parse()?);",
+                    quote(key),
+                    quote(&String::from_utf8_lossy(value))
+                )
+                .unwrap();
+            }
+        }
+    }
+    writeln!(s).unwrap();
+
+    let method = field_name(snippet.method.name());
+    match snippet.kind() {
+        MethodKind::Unary | MethodKind::ClientStreaming => {
+            writeln!(s, "    let response = client.{}(request).await?;", method).unwrap();
+            writeln!(s, "    println!(\"{{:?}}\", response.into_inner());").unwrap();
+        }
+        MethodKind::ServerStreaming | MethodKind::Streaming => {
+            writeln!(
+                s,
+                "    let mut stream = client.{}(request).await?.into_inner();",
+                method
+            )
+            .unwrap();
+            writeln!(
+                s,
+                "    while let Some(response) = stream.message().await? {{"
+            )
+            .unwrap();
+            writeln!(s, "        println!(\"{{:?}}\", response);").unwrap();
+            writeln!(s, "    }}").unwrap();
+        }
+    }
+    writeln!(s).unwrap();
+    writeln!(s, "    Ok(())").unwrap();
+    writeln!(s, "}}").unwrap();
+    s
+}
+
+fn message(message: &DynamicMessage, depth: usize) -> String {
+    let desc = message.descriptor();
+    if is_wrapper(&desc) {
+        let (kind, value) = wrapped_value(message);
+        return single(&kind, &value, depth);
+    } else if desc.full_name() == "google.protobuf.Empty" {
+        return "()".to_owned();
+    }
+
+    let path = message_path(&desc);
+    let fields = set_fields(message);
+    if fields.is_empty() {
+        return format!("{}::default()", path);
+    }
+
+    let mut s = format!("{} {{\n", path);
+    for (field, value) in &fields {
+        let (name, value) = match real_oneof(field) {
+            Some(oneof) => (
+                field_name(oneof.name()),
+                format!(
+                    "Some({}::{}::{}({}))",
+                    module_path(&desc).join("::"),
+                    oneof.name().to_upper_camel_case(),
+                    field.name().to_upper_camel_case(),
+                    single(&field.kind(), value, depth + 1)
+                ),
+            ),
+            None => (
+                field_name(field.name()),
+                field_value(field, value, depth + 1),
+            ),
+        };
+        writeln!(s, "{}{}: {},", indent(depth + 1), name, value).unwrap();
+    }
+
+    let slots = desc.fields().filter(|f| real_oneof(f).is_none()).count()
+        + desc.oneofs().filter(|o| !o.is_synthetic()).count();
+    if fields.len() < slots {
+        writeln!(s, "{}..Default::default()", indent(depth + 1)).unwrap();
+    }
+    write!(s, "{}}}", indent(depth)).unwrap();
+    s
+}
+
+fn field_value(field: &FieldDescriptor, value: &Value, depth: usize) -> String {
+    let kind = field.kind();
+    match value {
+        Value::List(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| single(&kind, item, depth + 1))
+                .collect();
+            if matches!(kind, Kind::Message(_)) {
+                let mut s = "vec![\n".to_owned();
+                for item in items {
+                    writeln!(s, "{}{},", indent(depth + 1), item).unwrap();
+                }
+                write!(s, "{}]", indent(depth)).unwrap();
+                s
+            } else {
+                format!("vec![{}]", items.join(", "))
+            }
+        }
+        Value::Map(map) => {
+            let entry = kind.as_message().expect("map fields have an entry type");
+            let key_kind = entry.map_entry_key_field().kind();
+            let value_kind = entry.map_entry_value_field().kind();
+
+            let mut s = "[\n".to_owned();
+            for (key, value) in sorted_map(map) {
+                writeln!(
+                    s,
+                    "{}({}, {}),",
+                    indent(depth + 1),
+                    single(&key_kind, &map_key_value(key), depth + 1),
+                    single(&value_kind, value, depth + 1)
+                )
+                .unwrap();
+            }
+            write!(
+                s,
+                "{}]\n{}.into_iter()\n{}.collect()",
+                indent(depth),
+                indent(depth),
+                indent(depth)
+            )
+            .unwrap();
+            s
+        }
+        value => {
+            let value = single(&kind, value, depth);
+            if matches!(kind, Kind::Message(_)) || is_optional_scalar(field) {
+                format!("Some({})", value)
+            } else {
+                value
+            }
+        }
+    }
+}
+
+fn single(kind: &Kind, value: &Value, depth: usize) -> String {
+    match (kind, value) {
+        (_, Value::Bool(value)) => value.to_string(),
+        (_, Value::I32(value)) => value.to_string(),
+        (_, Value::I64(value)) => value.to_string(),
+        (_, Value::U32(value)) => value.to_string(),
+        (_, Value::U64(value)) => value.to_string(),
+        (_, Value::F32(value)) => float_literal(*value as f64, "f32"),
+        (_, Value::F64(value)) => float_literal(*value, "f64"),
+        (_, Value::String(value)) => format!("{}.to_owned()", quote(value)),
+        (_, Value::Bytes(value)) => format!("b{}.to_vec()", quote_bytes(value)),
+        (Kind::Enum(enum_ty), Value::EnumNumber(number)) => match enum_ty.get_value(*number) {
+            Some(value) => format!(
+                "{}::{} as i32",
+                enum_path(enum_ty),
+                strip_enum_prefix(
+                    &enum_ty.name().to_upper_camel_case(),
+                    &value.name().to_upper_camel_case()
+                )
+            ),
+            None => number.to_string(),
+        },
+        (_, Value::EnumNumber(number)) => number.to_string(),
+        (_, Value::Message(value)) => message(value, depth),
+        (_, Value::List(_) | Value::Map(_)) => "Default::default()".to_owned(),
+    }
+}
+
+fn float_literal(value: f64, ty: &str) -> String {
+    if value.is_nan() {
+        format!("{}::NAN", ty)
+    } else if value == f64::INFINITY {
+        format!("{}::INFINITY", ty)
+    } else if value == f64::NEG_INFINITY {
+        format!("{}::NEG_INFINITY", ty)
+    } else {
+        float(value)
+    }
+}
+
+/// Removes the name of an enum from the start of one of its values, as prost does.
+fn strip_enum_prefix<'a>(prefix: &str, name: &'a str) -> &'a str {
+    match name.strip_prefix(prefix) {
+        Some(stripped) if stripped.starts_with(|c: char| c.is_ascii_alphabetic()) => stripped,
+        _ => name,
+    }
+}
+
+fn message_path(message: &MessageDescriptor) -> String {
+    type_path(
+        message.package_name(),
+        message.parent_message(),
+        message.name(),
+    )
+}
+
+fn enum_path(enum_ty: &EnumDescriptor) -> String {
+    type_path(
+        enum_ty.package_name(),
+        enum_ty.parent_message(),
+        enum_ty.name(),
+    )
+}
+
+fn type_path(package: &str, parent: Option<MessageDescriptor>, name: &str) -> String {
+    let mut path = match parent {
+        Some(parent) => module_path(&parent),
+        None if package == "google.protobuf" => vec!["prost_types".to_owned()],
+        None => package_modules(package),
+    };
+    path.push(name.to_upper_camel_case());
+    path.join("::")
+}
+
+/// Gets the path of the module containing the nested types of a message.
+fn module_path(message: &MessageDescriptor) -> Vec<String> {
+    let mut path = match message.parent_message() {
+        Some(parent) => module_path(&parent),
+        None if message.package_name() == "google.protobuf" => vec!["prost_types".to_owned()],
+        None => package_modules(message.package_name()),
+    };
+    path.push(field_name(message.name()));
+    path
+}
+
+fn package_modules(package: &str) -> Vec<String> {
+    package
+        .split('.')
+        .filter(|module| !module.is_empty())
+        .map(field_name)
+        .collect()
+}
+
+fn field_name(name: &str) -> String {
+    let name = name.to_snake_case();
+    match name.as_str() {
+        "self" | "super" | "crate" => format!("{}_", name),
+        name if KEYWORDS.contains(&name) => format!("r#{}", name),
+        _ => name,
+    }
+}
@@ -0,0 +1,272 @@
+//! Snippets using connect-es, with code generated by `protoc-gen-es` and `protoc-gen-connect-es`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use heck::{ToLowerCamelCase, ToShoutySnakeCase};
+use prost_reflect::{DynamicMessage, FieldDescriptor, FileDescriptor, Kind, Value};
+
+use crate::grpc::MethodKind;
+
+use super::{
+    float, is_binary_key, is_wrapper, map_key_string, quote, real_oneof, relative_name, set_fields,
+    sorted_map, wrapped_value, Snippet,
+};
+
+pub(super) fn generate(snippet: &Snippet) -> String {
+    let service = snippet.method.parent_service();
+    let mut gen = Generator {
+        imports: BTreeMap::new(),
+    };
+    gen.import(
+        "@connectrpc/connect".to_owned(),
+        "createPromiseClient".to_owned(),
+    );
+    gen.import(
+        "@connectrpc/connect-node".to_owned(),
+        "createGrpcTransport".to_owned(),
+    );
+    gen.import(
+        generated_module(&service.parent_file(), "_connect"),
+        service.name().to_owned(),
+    );
+
+    let mut body = String::new();
+    writeln!(body, "const transport = createGrpcTransport({{").unwrap();
+    writeln!(body, "  baseUrl: {},", quote(&snippet.base_url())).unwrap();
+    writeln!(body, "  httpVersion: \"2\",").unwrap();
+    if !snippet.is_plaintext() && !snippet.verify_certs {
+        writeln!(body, "  nodeOptions: {{ rejectUnauthorized: false }},").unwrap();
+    }
+    writeln!(body, "}});").unwrap();
+    writeln!(
+        body,
+        "const client = createPromiseClient({}, transport);",
+        service.name()
+    )
+    .unwrap();
+    writeln!(body).unwrap();
+
+    let desc = snippet.request.descriptor();
+    let ty = gen.message_type(&desc.parent_file(), desc.full_name(), desc.package_name());
+    let fields = gen.fields(snippet.request, 0);
+    writeln!(body, "const request = new {}({});", ty, fields).unwrap();
+
+    let mut arguments = match snippet.kind() {
+        MethodKind::Unary | MethodKind::ServerStreaming => "request".to_owned(),
+        MethodKind::ClientStreaming | MethodKind::Streaming => {
+            "(async function* () {\n  yield request;\n})()".to_owned()
+        }
+    };
+    if !snippet.metadata.is_empty() {
+        writeln!(body, "const headers = {{").unwrap();
+        for (key, value) in &snippet.metadata {
+            // Binary values are sent base64 encoded, as in the header.
+            let value = if is_binary_key(key) {
+                STANDARD.encode(value)
+            } else {
+                String::from_utf8_lossy(value).into_owned()
+            };
+            writeln!(body, "  {}: {},", quote(key), quote(&value)).unwrap();
+        }
+        writeln!(body, "}};").unwrap();
+        arguments.push_str(", { headers }");
+    }
+    writeln!(body).unwrap();
+
+    let call = format!(
+        "client.{}({})",
+        snippet.method.name().to_lower_camel_case(),
+        arguments
+    );
+    match snippet.kind() {
+        MethodKind::Unary | MethodKind::ClientStreaming => {
+            writeln!(body, "const response = await {};", call).unwrap();
+            writeln!(body, "console.log(response);").unwrap();
+        }
+        MethodKind::ServerStreaming | MethodKind::Streaming => {
+            writeln!(body, "for await (const response of {}) {{", call).unwrap();
+            writeln!(body, "  console.log(response);").unwrap();
+            writeln!(body, "}}").unwrap();
+        }
+    }
+
+    let mut s = String::new();
+    for (module, names) in &gen.imports {
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        writeln!(
+            s,
+            "import {{ {} }} from {};",
+            names.join(", "),
+            quote(module)
+        )
+        .unwrap();
+    }
+    writeln!(s).unwrap();
+    s.push_str(&body);
+    s
+}
+
+struct Generator {
+    /// Maps imported modules to the names imported from them.
+    imports: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Generator {
+    fn import(&mut self, module: String, name: String) {
+        self.imports.entry(module).or_default().insert(name);
+    }
+
+    /// Imports a message or enum type, and returns its name.
+    fn message_type(&mut self, file: &FileDescriptor, full_name: &str, package: &str) -> String {
+        let name = relative_name(full_name, package).replace('.', "_");
+        let module = if package == "google.protobuf" {
+            "@bufbuild/protobuf".to_owned()
+        } else {
+            generated_module(file, "_pb")
+        };
+        self.import(module, name.clone());
+        name
+    }
+
+    /// Formats the fields of a message as an object literal.
+    fn fields(&mut self, message: &DynamicMessage, depth: usize) -> String {
+        let fields = set_fields(message);
+        if fields.is_empty() {
+            return "{}".to_owned();
+        }
+
+        let mut s = "{\n".to_owned();
+        for (field, value) in &fields {
+            let (name, value) = match real_oneof(field) {
+                Some(oneof) => {
+                    let value = self.single(&field.kind(), value, depth + 2);
+                    (
+                        oneof.name().to_lower_camel_case(),
+                        format!(
+                            "{{\n{}case: {},\n{}value: {},\n{}}}",
+                            spaces(depth + 2),
+                            quote(&field.name().to_lower_camel_case()),
+                            spaces(depth + 2),
+                            value,
+                            spaces(depth + 1)
+                        ),
+                    )
+                }
+                None => (
+                    field.name().to_lower_camel_case(),
+                    self.field_value(field, value, depth + 1),
+                ),
+            };
+            writeln!(s, "{}{}: {},", spaces(depth + 1), name, value).unwrap();
+        }
+        write!(s, "{}}}", spaces(depth)).unwrap();
+        s
+    }
+
+    fn field_value(&mut self, field: &FieldDescriptor, value: &Value, depth: usize) -> String {
+        let kind = field.kind();
+        match value {
+            Value::List(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| self.single(&kind, item, depth + 1))
+                    .collect();
+                if matches!(kind, Kind::Message(_)) {
+                    let mut s = "[\n".to_owned();
+                    for item in items {
+                        writeln!(s, "{}{},", spaces(depth + 1), item).unwrap();
+                    }
+                    write!(s, "{}]", spaces(depth)).unwrap();
+                    s
+                } else {
+                    format!("[{}]", items.join(", "))
+                }
+            }
+            Value::Map(map) => {
+                let entry = kind.as_message().expect("map fields have an entry type");
+                let value_kind = entry.map_entry_value_field().kind();
+
+                let mut s = "{\n".to_owned();
+                for (key, value) in sorted_map(map) {
+                    let value = self.single(&value_kind, value, depth + 1);
+                    writeln!(
+                        s,
+                        "{}{}: {},",
+                        spaces(depth + 1),
+                        quote(&map_key_string(key)),
+                        value
+                    )
+                    .unwrap();
+                }
+                write!(s, "{}}}", spaces(depth)).unwrap();
+                s
+            }
+            value => self.single(&kind, value, depth),
+        }
+    }
+
+    fn single(&mut self, kind: &Kind, value: &Value, depth: usize) -> String {
+        match (kind, value) {
+            (_, Value::Bool(value)) => value.to_string(),
+            (_, Value::I32(value)) => value.to_string(),
+            (_, Value::I64(value)) => format!("{}n", value),
+            (_, Value::U32(value)) => value.to_string(),
+            (_, Value::U64(value)) => format!("{}n", value),
+            (_, Value::F32(value)) => float_literal(*value as f64),
+            (_, Value::F64(value)) => float_literal(*value),
+            (_, Value::String(value)) => quote(value),
+            (_, Value::Bytes(value)) => {
+                let bytes: Vec<String> = value.iter().map(|b| b.to_string()).collect();
+                format!("new Uint8Array([{}])", bytes.join(", "))
+            }
+            (Kind::Enum(enum_ty), Value::EnumNumber(number)) => match enum_ty.get_value(*number) {
+                Some(value) => {
+                    let ty = self.message_type(
+                        &enum_ty.parent_file(),
+                        enum_ty.full_name(),
+                        enum_ty.package_name(),
+                    );
+                    let prefix = format!("{}_", enum_ty.name().to_shouty_snake_case());
+                    let name = match value.name().strip_prefix(&prefix) {
+                        Some(name) if !name.starts_with(|c: char| c.is_ascii_digit()) => name,
+                        _ => value.name(),
+                    };
+                    format!("{}.{}", ty, name)
+                }
+                None => number.to_string(),
+            },
+            (_, Value::EnumNumber(number)) => number.to_string(),
+            (_, Value::Message(message)) if is_wrapper(&message.descriptor()) => {
+                let (kind, value) = wrapped_value(message);
+                self.single(&kind, &value, depth)
+            }
+            (_, Value::Message(message)) => self.fields(message, depth),
+            (_, Value::List(_) | Value::Map(_)) => "undefined".to_owned(),
+        }
+    }
+}
+
+/// Gets the path of a module generated for a file, relative to the snippet.
+fn generated_module(file: &FileDescriptor, suffix: &str) -> String {
+    format!("./gen/{}{}", file.name().trim_end_matches(".proto"), suffix)
+}
+
+fn float_literal(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_owned()
+    } else if value == f64::INFINITY {
+        "Infinity".to_owned()
+    } else if value == f64::NEG_INFINITY {
+        "-Infinity".to_owned()
+    } else {
+        float(value)
+    }
+}
+
+fn spaces(depth: usize) -> String {
+    "  ".repeat(depth)
+}
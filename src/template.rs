@@ -0,0 +1,299 @@
+use std::{borrow::Cow, env};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::Rng;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// Returns true if the text contains any `{{...}}` placeholders.
+pub fn has_placeholders(text: &str) -> bool {
+    text.contains(OPEN)
+}
+
+/// Checks that all placeholders in the text are well-formed, without expanding them.
+pub fn validate(text: &str) -> Result<()> {
+    expand(text, |_| Ok(String::new())).map(drop)
+}
+
+/// Replaces each `{{name}}` placeholder in the text with the value returned by `lookup`.
+pub fn expand<'a>(
+    text: &'a str,
+    lookup: impl FnMut(&str) -> Result<String>,
+) -> Result<Cow<'a, str>> {
+    expand_impl(text, lookup, false)
+}
+
+/// Replaces each `{{name}}` placeholder in JSON text with the value returned by `lookup`. Values
+/// of placeholders inside string literals are escaped, so quotes and backslashes can't end the
+/// string early. Placeholders outside strings are inserted as they are, so that they can hold
+/// numbers or objects.
+pub fn expand_json<'a>(
+    text: &'a str,
+    lookup: impl FnMut(&str) -> Result<String>,
+) -> Result<Cow<'a, str>> {
+    expand_impl(text, lookup, true)
+}
+
+fn expand_impl<'a>(
+    text: &'a str,
+    mut lookup: impl FnMut(&str) -> Result<String>,
+    json: bool,
+) -> Result<Cow<'a, str>> {
+    if !has_placeholders(text) {
+        return Ok(Cow::Borrowed(text));
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut string = JsonString::default();
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+        result.push_str(&rest[..start]);
+        string.scan(&rest[..start]);
+        rest = &rest[start + OPEN.len()..];
+
+        let Some(end) = rest.find(CLOSE) else {
+            bail!("unterminated placeholder, expected '{}'", CLOSE)
+        };
+        let name = rest[..end].trim();
+        if name.is_empty() {
+            bail!("empty placeholder")
+        }
+
+        let value = lookup(name)?;
+        if json && string.inside {
+            let quoted = serde_json::to_string(&value)?;
+            result.push_str(&quoted[1..quoted.len() - 1]);
+        } else {
+            result.push_str(&value);
+        }
+        rest = &rest[end + CLOSE.len()..];
+    }
+    result.push_str(rest);
+
+    Ok(Cow::Owned(result))
+}
+
+/// Tracks whether the end of some JSON text is inside a string literal.
+#[derive(Default)]
+struct JsonString {
+    inside: bool,
+    escaped: bool,
+}
+
+impl JsonString {
+    fn scan(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.escaped {
+                self.escaped = false;
+            } else if self.inside && c == '\\' {
+                self.escaped = true;
+            } else if c == '"' {
+                self.inside = !self.inside;
+            }
+        }
+    }
+}
+
+/// Evaluates a built-in function such as `uuid` or `randomInt 1 100`, returning `None` if the
+/// expression does not name a function.
+pub fn call(expr: &str) -> Option<Result<String>> {
+    let args = match shell_words::split(expr) {
+        Ok(args) => args,
+        Err(err) => return Some(Err(err).context("invalid function arguments")),
+    };
+    let (name, args) = args.split_first()?;
+
+    let result = match name.as_str() {
+        "uuid" => check_args(name, args, 0).map(|()| uuid()),
+        "now" => now(args),
+        "randomInt" => random_int(args),
+        "base64File" => check_args(name, args, 1).and_then(|()| base64_file(&args[0])),
+        "env" => check_args(name, args, 1).and_then(|()| {
+            env::var(&args[0])
+                .with_context(|| format!("failed to read environment variable '{}'", args[0]))
+        }),
+        _ => return None,
+    };
+
+    Some(result.with_context(|| format!("failed to evaluate '{}'", name)))
+}
+
+fn check_args(name: &str, args: &[String], count: usize) -> Result<()> {
+    if args.len() != count {
+        bail!(
+            "'{}' expects {} argument(s), but {} were given",
+            name,
+            count,
+            args.len()
+        )
+    }
+    Ok(())
+}
+
+/// Generates a random version 4 UUID.
+fn uuid() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn now(args: &[String]) -> Result<String> {
+    let now = OffsetDateTime::now_utc();
+    match args {
+        [] => Ok(now.format(&Rfc3339)?),
+        [format] if format == "rfc3339" => Ok(now.format(&Rfc3339)?),
+        [format] if format == "millis" => Ok((now.unix_timestamp_nanos() / 1_000_000).to_string()),
+        [format] => bail!(
+            "unknown format '{}', expected 'rfc3339' or 'millis'",
+            format
+        ),
+        _ => bail!("'now' expects at most 1 argument"),
+    }
+}
+
+fn random_int(args: &[String]) -> Result<String> {
+    let [min, max] = args else {
+        bail!("'randomInt' expects 2 arguments")
+    };
+    let min: i64 = min.parse().context("invalid minimum")?;
+    let max: i64 = max.parse().context("invalid maximum")?;
+    if min > max {
+        bail!("minimum must not be greater than maximum")
+    }
+    Ok(rand::thread_rng().gen_range(min..=max).to_string())
+}
+
+fn base64_file(path: &str) -> Result<String> {
+    let data = fs_err::read(path)?;
+    Ok(STANDARD.encode(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Result<String> {
+        match name {
+            "path" => Ok(r"C:\Users\me".to_owned()),
+            "quote" => Ok(r#"say "hi""#.to_owned()),
+            "line" => Ok("a\nb".to_owned()),
+            "count" => Ok("3".to_owned()),
+            _ => bail!("variable '{}' is not defined", name),
+        }
+    }
+
+    #[test]
+    fn expand_text() {
+        assert_eq!(
+            expand("dir={{ path }} n={{count}}", lookup).unwrap(),
+            r"dir=C:\Users\me n=3"
+        );
+        assert!(matches!(expand("plain", lookup).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn expand_json_escapes_strings() {
+        let json = r#"{"path": "{{path}}", "quote": "{{quote}}", "line": "x{{line}}y"}"#;
+        let expanded = expand_json(json, lookup).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&expanded).unwrap();
+        assert_eq!(value["path"], r"C:\Users\me");
+        assert_eq!(value["quote"], r#"say "hi""#);
+        assert_eq!(value["line"], "xa\nby");
+    }
+
+    #[test]
+    fn expand_json_outside_strings() {
+        let json = r#"{"count": {{count}}, "text": "a \"{{count}}\" \\", "after": {{count}}}"#;
+        assert_eq!(
+            expand_json(json, lookup).unwrap(),
+            r#"{"count": 3, "text": "a \"3\" \\", "after": 3}"#
+        );
+    }
+
+    #[test]
+    fn unterminated_placeholder() {
+        let err = expand("{{path", lookup).unwrap_err();
+        assert!(err.to_string().contains("unterminated placeholder"));
+        assert!(validate(r#"{"a": "{{path}"}"#).is_err());
+    }
+
+    #[test]
+    fn empty_placeholder() {
+        assert!(expand("{{ }}", lookup).is_err());
+    }
+
+    #[test]
+    fn undefined_variable() {
+        let err = expand("{{missing}}", lookup).unwrap_err();
+        assert_eq!(err.to_string(), "variable 'missing' is not defined");
+    }
+
+    #[test]
+    fn call_uuid() {
+        let uuid = call("uuid").unwrap().unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!(call("uuid extra").unwrap().is_err());
+    }
+
+    #[test]
+    fn call_now() {
+        let now = call("now").unwrap().unwrap();
+        assert!(OffsetDateTime::parse(&now, &Rfc3339).is_ok());
+        let now = call("now rfc3339").unwrap().unwrap();
+        assert!(OffsetDateTime::parse(&now, &Rfc3339).is_ok());
+        let millis: i64 = call("now millis").unwrap().unwrap().parse().unwrap();
+        assert!(millis > 0);
+        assert!(call("now weekday").unwrap().is_err());
+    }
+
+    #[test]
+    fn call_random_int() {
+        for _ in 0..20 {
+            let value: i64 = call("randomInt -2 2").unwrap().unwrap().parse().unwrap();
+            assert!((-2..=2).contains(&value));
+        }
+        assert_eq!(call("randomInt 5 5").unwrap().unwrap(), "5");
+        assert!(call("randomInt 2 1").unwrap().is_err());
+        assert!(call("randomInt 1").unwrap().is_err());
+        assert!(call("randomInt a 1").unwrap().is_err());
+    }
+
+    #[test]
+    fn call_base64_file() {
+        let path = env::temp_dir().join(format!("template-test-{}.bin", std::process::id()));
+        fs_err::write(&path, b"hello").unwrap();
+        let result = call(&format!("base64File '{}'", path.display()));
+        fs_err::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap().unwrap(), "aGVsbG8=");
+        assert!(call("base64File /does/not/exist").unwrap().is_err());
+        assert!(call("base64File").unwrap().is_err());
+    }
+
+    #[test]
+    fn call_env() {
+        env::set_var("TEMPLATE_TEST_VALUE", "value");
+        assert_eq!(call("env TEMPLATE_TEST_VALUE").unwrap().unwrap(), "value");
+        assert!(call("env TEMPLATE_TEST_MISSING").unwrap().is_err());
+    }
+
+    #[test]
+    fn call_unknown() {
+        assert!(call("path").is_none());
+        assert!(call("count 1 2").is_none());
+    }
+}